@@ -0,0 +1,49 @@
+/*
+ * C interface to homebox-label-maker. Build the library with the `ffi`
+ * feature and link against the resulting shared library.
+ */
+
+#ifndef HOMEBOX_LABEL_MAKER_H
+#define HOMEBOX_LABEL_MAKER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Generate a label sheet from a JSON configuration such as:
+ *
+ *     {
+ *         "server": "https://homebox.example.com",
+ *         "username": "user",
+ *         "password": "pass",
+ *         "assets": "000-001--000-010",
+ *         "layout": { "grid_rows": 13, "grid_columns": 5 },
+ *         "format": "html",
+ *         "lang": "en"
+ *     }
+ *
+ * "layout", "format" and "lang" are optional. The layout uses the same
+ * names as the command line options.
+ *
+ * Returns 0 and sets output and output_len on success, which must be
+ * freed with hlm_free_output. Otherwise returns 1 and, if error is not
+ * NULL, sets it to a description of the failure, which must be freed
+ * with hlm_free_error.
+ */
+int hlm_generate_sheet(const char *config, uint8_t **output, size_t *output_len, char **error);
+
+/* Free a sheet returned by hlm_generate_sheet */
+void hlm_free_output(uint8_t *output, size_t output_len);
+
+/* Free an error returned by hlm_generate_sheet */
+void hlm_free_error(char *error);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
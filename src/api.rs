@@ -1,5 +1,52 @@
+//! A client for the Homebox REST API.
+//!
+//! ```no_run
+//! use homebox_label_maker::{api::HomeboxClient, asset_list};
+//!
+//! let client = HomeboxClient::login("https://homebox.example.com", "user", "pass")?;
+//! for item in client.list_items()? {
+//!     println!("{} {}", item.asset_id, item.name);
+//! }
+//! let ids: Vec<_> = asset_list::parse("000-001")?.into_iter().flatten().collect();
+//! let png = client.get_asset_label(ids[0])?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::{
+    sync::{
+        OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use reqwest::{
+    Method, StatusCode,
+    blocking::{RequestBuilder, Response},
+    header::{ACCEPT, CONTENT_TYPE},
+};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    asset_list::AssetId,
+    error::{ApiError, AuthError},
+    image::ImageType,
+};
+
+/// The label formats accepted from the server, preferring the PNG that
+/// Homebox renders
+const LABEL_ACCEPT: &str = "image/png, image/*;q=0.8";
+
+/// The label formats accepted from the server when vector images are
+/// preferred
+const LABEL_ACCEPT_SVG: &str = "image/svg+xml, image/png;q=0.9, image/*;q=0.8";
+
+/// The number of items requested per page when listing items
+const PAGE_SIZE: usize = 100;
+
+/// The ID given to the next request in the logs
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LoginReq {
@@ -15,3 +62,661 @@ pub struct LoginRes {
     pub expires_at: String,
     pub token: String,
 }
+
+/// The health of a server, which it reports without logging in
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerStatus {
+    #[serde(default)]
+    pub health: bool,
+    #[serde(default)]
+    pub build: BuildInfo,
+}
+
+/// The version of Homebox a server is running
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BuildInfo {
+    #[serde(default)]
+    pub version: String,
+}
+
+/// The releases of Homebox whose label makers differ. Logging in and the
+/// rest of the API used here are the same in every release, taking the
+/// same form-encoded login, so only label requests are adapted to the
+/// server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApiVersion {
+    /// The original Homebox, before 0.11, which has no label maker
+    Legacy,
+    /// Homebox 0.11 and later
+    Current,
+}
+
+impl ApiVersion {
+    /// The API of the given Homebox version, as the server reports it,
+    /// e.g. `v0.15.2`. Versions that cannot be read, such as those of
+    /// development builds, are taken to be current.
+    pub fn from_version(version: &str) -> Self {
+        let mut parts = version
+            .trim()
+            .trim_start_matches('v')
+            .split(['.', '-'])
+            .map(str::parse::<u32>);
+        match (parts.next(), parts.next()) {
+            (Some(Ok(0)), Some(Ok(minor))) if minor < 11 => Self::Legacy,
+            _ => Self::Current,
+        }
+    }
+
+    /// Whether servers with this API can render labels
+    #[must_use]
+    pub fn has_label_maker(self) -> bool {
+        self != Self::Legacy
+    }
+
+    /// The path under the API of the label for an asset or location
+    fn label_path(self, kind: &str, id: &str) -> Result<String, ApiError> {
+        if !self.has_label_maker() {
+            return Err(ApiError::Unsupported("labels"));
+        }
+        Ok(format!("/v1/labelmaker/{kind}/{id}?print=false"))
+    }
+}
+
+/// A page of results from a paginated endpoint
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Page<T> {
+    items: Vec<T>,
+    total: usize,
+}
+
+/// An item as returned when listing items
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ItemSummary {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// The asset ID, formatted as on the labels, e.g. `000-001`
+    #[serde(default)]
+    pub asset_id: String,
+    #[serde(default)]
+    pub quantity: i64,
+    pub location: Option<LocationSummary>,
+    #[serde(default)]
+    pub labels: Vec<LabelSummary>,
+    /// When the item was created, as an RFC 3339 timestamp
+    #[serde(default)]
+    pub created_at: String,
+}
+
+impl ItemSummary {
+    /// Whether the item has been assigned an asset ID. Homebox reports
+    /// unassigned items with an asset ID of zero.
+    pub fn has_asset_id(&self) -> bool {
+        self.asset_id
+            .chars()
+            .any(|c| c.is_ascii_digit() && c != '0')
+    }
+}
+
+/// An item with all of its details
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Item {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub asset_id: String,
+    #[serde(default)]
+    pub fields: Vec<CustomField>,
+    #[serde(default)]
+    pub purchase_time: String,
+    #[serde(default)]
+    pub warranty_expires: String,
+    #[serde(default)]
+    pub lifetime_warranty: bool,
+    #[serde(default)]
+    pub manufacturer: String,
+    #[serde(default)]
+    pub model_number: String,
+    #[serde(default)]
+    pub serial_number: String,
+}
+
+impl Item {
+    /// The date the item was purchased, e.g. `2024-03-01`, if known
+    pub fn purchase_date(&self) -> Option<&str> {
+        date(&self.purchase_time)
+    }
+
+    /// The date the item's warranty expires, if known
+    pub fn warranty_expiry(&self) -> Option<&str> {
+        date(&self.warranty_expires)
+    }
+
+    /// The value of the custom field with the given name, if the item
+    /// has it
+    pub fn field(&self, name: &str) -> Option<String> {
+        self.fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(CustomField::value)
+    }
+}
+
+/// The date part of a timestamp from the server, which uses the zero
+/// time (year 1) when a date is not set
+fn date(timestamp: &str) -> Option<&str> {
+    let date = timestamp.get(..10)?;
+    (!date.starts_with("0001")).then_some(date)
+}
+
+/// A custom field set on an item
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomField {
+    pub name: String,
+    /// The type of value held: `text`, `number` or `boolean`
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub text_value: String,
+    #[serde(default)]
+    pub number_value: f64,
+    #[serde(default)]
+    pub boolean_value: bool,
+}
+
+impl CustomField {
+    /// The value of the field, formatted for display
+    pub fn value(&self) -> String {
+        match self.kind.as_str() {
+            "number" => self.number_value.to_string(),
+            "boolean" => self.boolean_value.to_string(),
+            _ => self.text_value.clone(),
+        }
+    }
+}
+
+/// A location as referenced by an item
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LocationSummary {
+    pub id: String,
+    pub name: String,
+}
+
+/// A label (tag) as referenced by an item
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LabelSummary {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// The color chosen for the label, e.g. `#ff0000`, or empty if none
+    #[serde(default)]
+    pub color: String,
+}
+
+/// A location and the locations within it, as returned by the location
+/// tree
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LocationNode {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub children: Vec<LocationNode>,
+}
+
+impl LocationNode {
+    /// Find the first location in this subtree with the given name
+    /// (ignoring case) or ID
+    pub fn find(&self, name_or_id: &str) -> Option<&LocationNode> {
+        if self.id == name_or_id || self.name.eq_ignore_ascii_case(name_or_id) {
+            return Some(self);
+        }
+        self.children
+            .iter()
+            .find_map(|child| child.find(name_or_id))
+    }
+
+    /// This location followed by every location within it, depth first
+    pub fn descendants(&self) -> Vec<&LocationNode> {
+        let mut nodes = vec![self];
+        for child in &self.children {
+            nodes.extend(child.descendants());
+        }
+        nodes
+    }
+}
+
+/// An authenticated connection to a Homebox server
+pub struct HomeboxClient {
+    http: reqwest::blocking::Client,
+    base_url: String,
+    token: String,
+    /// Found from the server's status before logging in, or when first
+    /// needed by a session reused from an earlier login
+    version: OnceLock<ApiVersion>,
+    prefer_svg: bool,
+}
+
+impl HomeboxClient {
+    /// Log in to the server at the given URL, e.g.
+    /// `https://homebox.example.com`
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`AuthError::Rejected`] if the server rejects the
+    /// credentials, or [`AuthError::Api`] if it cannot be reached.
+    pub fn login(server: &str, username: &str, password: &str) -> Result<Self, AuthError> {
+        Self::login_session(server, username, password, false).map(|(client, _)| client)
+    }
+
+    /// Log in to the server at the given URL, asking for a long-lived
+    /// session if `stay_logged_in` is set, and returning when the
+    /// session expires, as an RFC 3339 timestamp, along with the client
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`AuthError::Rejected`] if the server rejects the
+    /// credentials, or [`AuthError::Api`] if it cannot be reached.
+    pub fn login_session(
+        server: &str,
+        username: &str,
+        password: &str,
+        stay_logged_in: bool,
+    ) -> Result<(Self, String), AuthError> {
+        let http = reqwest::blocking::Client::new();
+        let base_url = format!("{}/api", server.trim_end_matches('/'));
+        tracing::debug!("Base API URL: {base_url}");
+        let version = detect_version(&http, &base_url);
+
+        tracing::info!("Authenticating...");
+        let response = send(
+            http.post(format!("{base_url}/v1/users/login"))
+                .form(&LoginReq {
+                    username: username.to_string(),
+                    password: password.to_string(),
+                    stay_logged_in,
+                }),
+        )
+        .map_err(ApiError::from)?;
+        let LoginRes {
+            token, expires_at, ..
+        } = match check_status(response) {
+            Err(ApiError::Unauthorized { message }) => Err(AuthError::Rejected { message })?,
+            response => response?.json().map_err(ApiError::from)?,
+        };
+        tracing::debug!("Token acquired: {token}");
+
+        Ok((
+            Self {
+                http,
+                base_url,
+                token,
+                version: OnceLock::from(version),
+                prefer_svg: false,
+            },
+            expires_at,
+        ))
+    }
+
+    /// Connect to the server at the given URL with the token from an
+    /// earlier login, without checking that it is still valid
+    pub fn with_token(server: &str, token: &str) -> Self {
+        Self {
+            http: reqwest::blocking::Client::new(),
+            base_url: format!("{}/api", server.trim_end_matches('/')),
+            token: token.to_string(),
+            version: OnceLock::new(),
+            prefer_svg: false,
+        }
+    }
+
+    /// Ask for labels as SVG images if the server can produce them, so
+    /// QR codes print sharply at any size, rather than as PNGs
+    pub fn set_prefer_svg(&mut self, prefer_svg: bool) {
+        self.prefer_svg = prefer_svg;
+    }
+
+    /// The session token, to reuse with [`HomeboxClient::with_token`]
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Ask the server at the given URL for its status, which needs no
+    /// login, e.g. to check that it can be reached
+    ///
+    /// # Errors
+    ///
+    /// Fails if the request fails, or the server is not Homebox.
+    pub fn status(server: &str) -> Result<ServerStatus, ApiError> {
+        let url = format!("{}/api/v1/status", server.trim_end_matches('/'));
+        Ok(check_status(send(reqwest::blocking::Client::new().get(url))?)?.json()?)
+    }
+
+    /// The base URL of the server's API
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// The API the server provides, found from the version of Homebox it
+    /// runs
+    pub fn api_version(&self) -> ApiVersion {
+        *self
+            .version
+            .get_or_init(|| detect_version(&self.http, &self.base_url))
+    }
+
+    /// Check that the server can render labels, before asking it for any
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ApiError::Unsupported`] if the server runs a version
+    /// of Homebox without a label maker.
+    pub fn require_label_maker(&self) -> Result<(), ApiError> {
+        if self.api_version().has_label_maker() {
+            Ok(())
+        } else {
+            Err(ApiError::Unsupported("labels"))
+        }
+    }
+
+    /// Start an authenticated request to a path under the API
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        self.http
+            .request(method, format!("{}{path}", self.base_url))
+            .header("Authorization", &self.token)
+    }
+
+    /// Send an authenticated GET request to a path under the API
+    fn get(&self, path: &str) -> Result<Response, ApiError> {
+        check_status(send(self.request(Method::GET, path))?)
+    }
+
+    /// Download a label image, asking for a PNG, or an SVG if preferred
+    /// and the server can produce one, but accepting any image
+    fn get_label(&self, path: &str) -> Result<bytes::Bytes, ApiError> {
+        image(check_status(send(
+            self.request(Method::GET, path).header(
+                ACCEPT,
+                if self.prefer_svg {
+                    LABEL_ACCEPT_SVG
+                } else {
+                    LABEL_ACCEPT
+                },
+            ),
+        )?)?)
+    }
+
+    /// Download the label image for an asset, as rendered by the server
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ApiError::NotFound`] if the server has no such
+    /// asset, or another [`ApiError`] if the request fails.
+    pub fn get_asset_label(&self, asset_id: AssetId) -> Result<bytes::Bytes, ApiError> {
+        self.get_label(
+            &self
+                .api_version()
+                .label_path("asset", &asset_id.to_string())?,
+        )
+    }
+
+    /// Download the label image for a location, as rendered by the
+    /// server
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ApiError::NotFound`] if the server has no such
+    /// location, or another [`ApiError`] if the request fails.
+    pub fn get_location_label(&self, location_id: &str) -> Result<bytes::Bytes, ApiError> {
+        self.get_label(&self.api_version().label_path("location", location_id)?)
+    }
+
+    /// List every label (tag) on the server
+    ///
+    /// # Errors
+    ///
+    /// Fails if the request fails.
+    pub fn list_tags(&self) -> Result<Vec<LabelSummary>, ApiError> {
+        Ok(self.get("/v1/labels")?.json()?)
+    }
+
+    /// Fetch the tree of locations on the server, one node per top
+    /// level location
+    ///
+    /// # Errors
+    ///
+    /// Fails if the request fails.
+    pub fn location_tree(&self) -> Result<Vec<LocationNode>, ApiError> {
+        Ok(self.get("/v1/locations/tree?withItems=false")?.json()?)
+    }
+
+    /// Get every detail of an item
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ApiError::NotFound`] if the server has no such
+    /// item, or another [`ApiError`] if the request fails.
+    pub fn get_item(&self, item_id: &str) -> Result<Item, ApiError> {
+        Ok(self.get(&format!("/v1/items/{item_id}"))?.json()?)
+    }
+
+    /// Set a text custom field on an item, adding the field if the item
+    /// does not have it yet
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ApiError::NotFound`] if the server has no such
+    /// item, or another [`ApiError`] if a request fails.
+    pub fn set_item_field(&self, item_id: &str, name: &str, value: &str) -> Result<(), ApiError> {
+        let path = format!("/v1/items/{item_id}");
+        let mut item: serde_json::Value = self.get(&path)?.json()?;
+
+        // The update takes references to the location and labels by ID,
+        // rather than the nested objects that are returned
+        let location_id = item["location"]["id"].clone();
+        let label_ids: Vec<_> = item["labels"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|label| label["id"].clone())
+            .collect();
+        let parent_id = item["parent"]["id"].clone();
+        item["locationId"] = location_id;
+        item["labelIds"] = label_ids.into();
+        item["parentId"] = parent_id;
+
+        let field = serde_json::json!({
+            "name": name,
+            "type": "text",
+            "textValue": value,
+        });
+        match item["fields"].as_array_mut() {
+            Some(fields) => match fields.iter_mut().find(|f| f["name"] == name) {
+                Some(existing) => existing["textValue"] = value.into(),
+                None => fields.push(field),
+            },
+            None => item["fields"] = vec![field].into(),
+        }
+
+        check_status(send(self.request(Method::PUT, &path).json(&item))?)?;
+        Ok(())
+    }
+
+    /// Assign the next free asset IDs to every item that has none,
+    /// returning the number of items updated
+    ///
+    /// # Errors
+    ///
+    /// Fails if the request fails.
+    pub fn ensure_asset_ids(&self) -> Result<usize, ApiError> {
+        #[derive(Deserialize)]
+        struct ActionRes {
+            completed: usize,
+        }
+
+        let response = send(self.request(Method::POST, "/v1/actions/ensure-asset-ids"))?;
+        Ok(check_status(response)?.json::<ActionRes>()?.completed)
+    }
+
+    /// List every item on the server, fetching all pages of results
+    ///
+    /// # Errors
+    ///
+    /// Fails if any request fails.
+    pub fn list_items(&self) -> Result<Vec<ItemSummary>, ApiError> {
+        let mut items = vec![];
+        for page in 1.. {
+            let Page {
+                items: page_items,
+                total,
+            } = self
+                .get(&format!("/v1/items?page={page}&pageSize={PAGE_SIZE}"))?
+                .json::<Page<ItemSummary>>()?;
+            let last = page_items.is_empty();
+            items.extend(page_items);
+            if last || items.len() >= total {
+                break;
+            }
+        }
+        Ok(items)
+    }
+}
+
+/// Send a request, logging it and its response with an ID that matches
+/// them up, and how long the server took to respond
+fn send(request: RequestBuilder) -> Result<Response, reqwest::Error> {
+    let (http, request) = request.build_split();
+    let request = request?;
+    let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    let span = tracing::debug_span!("request", id);
+    let _entered = span.enter();
+
+    tracing::debug!("{} {}", request.method(), request.url());
+    let start = Instant::now();
+    let response = http.execute(request);
+    match &response {
+        Ok(response) => tracing::debug!("{} after {:?}", response.status(), start.elapsed()),
+        Err(e) => tracing::debug!("Failed after {:?}: {e}", start.elapsed()),
+    }
+    response
+}
+
+/// Find the API of the server with the given API URL from its status,
+/// taking it to be current if the status cannot be read, as then the
+/// server is most likely too new to be recognised
+fn detect_version(http: &reqwest::blocking::Client, base_url: &str) -> ApiVersion {
+    let status = send(http.get(format!("{base_url}/v1/status")))
+        .map_err(ApiError::from)
+        .and_then(check_status)
+        .and_then(|response| Ok(response.json::<ServerStatus>()?));
+    match status {
+        Ok(status) => {
+            let version = ApiVersion::from_version(&status.build.version);
+            tracing::debug!(
+                "The server runs Homebox {}, using the {version:?} API",
+                status.build.version
+            );
+            version
+        }
+        Err(e) => {
+            tracing::debug!("Failed to find the server's version, assuming it is current: {e}");
+            ApiVersion::Current
+        }
+    }
+}
+
+/// Read an image from a response, checking its contents rather than
+/// trusting the content type, which proxies sometimes get wrong
+fn image(response: Response) -> Result<bytes::Bytes, ApiError> {
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("an unknown type")
+        .to_string();
+    let data = response.bytes()?;
+    match ImageType::detect(&data) {
+        Some(kind) => {
+            if !content_type.starts_with(kind.mime_type()) {
+                tracing::debug!(
+                    "The server said it returned {content_type}, but it returned {}",
+                    kind.mime_type()
+                );
+            }
+            Ok(data)
+        }
+        None => Err(ApiError::NotAnImage { content_type }),
+    }
+}
+
+/// Convert error statuses into the matching [`ApiError`], with the
+/// server's explanation
+fn check_status(response: Response) -> Result<Response, ApiError> {
+    match response.status() {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(ApiError::Unauthorized {
+            message: error_message(response),
+        }),
+        StatusCode::NOT_FOUND => Err(ApiError::NotFound {
+            message: error_message(response),
+        }),
+        status if !status.is_success() => Err(ApiError::Status {
+            status,
+            message: error_message(response),
+        }),
+        _ => Ok(response),
+    }
+}
+
+/// The message of an error response, which Homebox gives as JSON, e.g.
+/// `{"error": "item not found"}`, or the whole body if it is something
+/// else, such as text from a proxy
+fn error_message(response: Response) -> Option<String> {
+    #[derive(Deserialize)]
+    struct ErrorRes {
+        #[serde(alias = "message")]
+        error: String,
+    }
+
+    let body = response.text().ok()?;
+    let message = match serde_json::from_str::<ErrorRes>(&body) {
+        Ok(ErrorRes { error }) => error,
+        Err(_) => body,
+    };
+    let message = message.trim();
+    (!message.is_empty()).then(|| message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_is_read_from_status() {
+        assert_eq!(ApiVersion::from_version("v0.10.3"), ApiVersion::Legacy);
+        assert_eq!(ApiVersion::from_version("0.9.0"), ApiVersion::Legacy);
+        assert_eq!(ApiVersion::from_version("v0.11.0"), ApiVersion::Current);
+        assert_eq!(ApiVersion::from_version("v1.0.0-rc.1"), ApiVersion::Current);
+        assert_eq!(ApiVersion::from_version("nightly"), ApiVersion::Current);
+    }
+
+    #[test]
+    fn labels_are_routed_by_version() {
+        assert!(matches!(
+            ApiVersion::Legacy.label_path("asset", "000-001"),
+            Err(ApiError::Unsupported("labels"))
+        ));
+        assert_eq!(
+            ApiVersion::Current.label_path("asset", "000-001").unwrap(),
+            "/v1/labelmaker/asset/000-001?print=false"
+        );
+        assert_eq!(
+            ApiVersion::Current.label_path("location", "abc").unwrap(),
+            "/v1/labelmaker/location/abc?print=false"
+        );
+    }
+}
@@ -1,8 +1,9 @@
-use anyhow::anyhow;
 use derive_more::{Debug, Display};
 use pest::{Parser, iterators::Pair};
 use pest_derive::Parser;
 
+use crate::error::AssetListError;
+
 #[derive(Parser)]
 #[grammar_inline = r#"
 Input = {
@@ -27,11 +28,17 @@ WHITESPACE = _{ " " }
 "#]
 struct AssetListParser;
 
-#[derive(Copy, Clone, Display, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Copy, Clone, Display, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[display("{_0:03}-{_1:03}")]
 #[debug("{_0:03}-{_1:03}")]
 pub struct AssetId(u16, u16);
 
+impl serde::Serialize for AssetId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl AssetId {
     pub fn increment(&mut self) {
         self.1 += 1;
@@ -92,18 +99,21 @@ impl Iterator for ListEntryIter {
 }
 
 pub trait Validate {
-    fn validate(&self) -> Result<(), anyhow::Error>;
+    /// Check the list makes sense, e.g. that no range runs backwards
+    ///
+    /// # Errors
+    ///
+    /// Fails with the first problem found.
+    fn validate(&self) -> Result<(), AssetListError>;
 }
 
 impl Validate for Vec<ListEntry> {
-    fn validate(&self) -> Result<(), anyhow::Error> {
+    fn validate(&self) -> Result<(), AssetListError> {
         for item in self {
-            if let ListEntry::Range { from, to } = item
+            if let &ListEntry::Range { from, to } = item
                 && to < from
             {
-                Err(anyhow!(
-                    "The start of a range must be smaller than the end of a range!"
-                ))?;
+                Err(AssetListError::BackwardsRange { from, to })?;
             }
         }
 
@@ -111,12 +121,18 @@ impl Validate for Vec<ListEntry> {
     }
 }
 
-#[allow(
-    clippy::result_large_err,
-    reason = "error is from pest and contains useful info"
-)]
-pub fn parse<S: AsRef<str>>(input: S) -> Result<Vec<ListEntry>, pest::error::Error<Rule>> {
-    let r = AssetListParser::parse(Rule::Input, input.as_ref())?
+/// Parse a comma-separated list of asset IDs and ranges
+///
+/// # Errors
+///
+/// Fails if the input is not a valid list.
+///
+/// # Panics
+///
+/// Panics only if the grammar and parser disagree.
+pub fn parse<S: AsRef<str>>(input: S) -> Result<Vec<ListEntry>, AssetListError> {
+    let r = AssetListParser::parse(Rule::Input, input.as_ref())
+        .map_err(Box::new)?
         .next()
         .unwrap();
     assert_eq!(r.as_rule(), Rule::Input);
@@ -130,6 +146,34 @@ pub fn parse<S: AsRef<str>>(input: S) -> Result<Vec<ListEntry>, pest::error::Err
     Ok(list)
 }
 
+/// Write asset IDs as an asset list, with runs of consecutive IDs as
+/// ranges
+#[must_use]
+pub fn format(asset_ids: &[AssetId]) -> String {
+    let mut runs: Vec<(AssetId, AssetId)> = vec![];
+    for &asset_id in asset_ids {
+        if let Some((_, last)) = runs.last_mut() {
+            let mut next = *last;
+            next.increment();
+            if next == asset_id {
+                *last = asset_id;
+                continue;
+            }
+        }
+        runs.push((asset_id, asset_id));
+    }
+    runs.into_iter()
+        .map(|(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{first}--{last}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 fn parse_range_or_id(p: Pair<'_, Rule>) -> ListEntry {
     match p.as_rule() {
         Rule::AssetId => ListEntry::Id(parse_id(p)),
@@ -0,0 +1,53 @@
+use homebox_label_maker::i18n::Lang;
+
+use crate::ConnectionArgs;
+
+#[derive(clap::Args)]
+pub struct AuditArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// Only audit items in this location (by name or ID) and the
+    /// locations within it
+    #[arg(long)]
+    location: Option<String>,
+}
+
+/// List the items that have no asset ID, and so cannot be given an
+/// asset label
+pub fn run(args: &AuditArgs, lang: Lang) -> anyhow::Result<()> {
+    let client = crate::authenticate(&args.connection)?;
+    let scope = args
+        .location
+        .as_deref()
+        .map(|location| crate::find_location(&client, location))
+        .transpose()?;
+    let location_ids: Option<Vec<&str>> = scope.as_ref().map(|root| {
+        root.descendants()
+            .into_iter()
+            .map(|node| node.id.as_str())
+            .collect()
+    });
+
+    let missing: Vec<_> = client
+        .list_items()?
+        .into_iter()
+        .filter(|item| !item.has_asset_id())
+        .filter(|item| {
+            location_ids.as_ref().is_none_or(|ids| {
+                item.location
+                    .as_ref()
+                    .is_some_and(|location| ids.contains(&location.id.as_str()))
+            })
+        })
+        .collect();
+
+    for item in &missing {
+        match &item.location {
+            Some(location) => println!("{} ({})", item.name, location.name),
+            None => println!("{}", item.name),
+        }
+    }
+    println!("{}", lang.missing_asset_ids(missing.len()));
+    Ok(())
+}
@@ -0,0 +1,133 @@
+//! Writing a mail merge data source for Avery Design & Print or Word,
+//! giving the asset ID, name and location of each label's item with the
+//! path to its label image.
+//!
+//! Neither can read images embedded in a data source, so the CSV refers
+//! to the label images saved in a directory, by the name
+//! [`crate::html::image_file_name`] gives them.
+
+use std::io;
+
+use crate::{
+    error::RenderError,
+    html::{IndexEntry, image_file_name},
+    render::{Sheet, write_csv_row},
+};
+
+/// Options for the mail merge data source
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// The directory the label images are saved in. Word only finds
+    /// merged pictures by their full path, so this should be absolute.
+    pub image_dir: Option<String>,
+    /// The item each label belongs to, in the same order as the labels.
+    /// Labels without one only have their description as the name.
+    pub entries: Vec<IndexEntry>,
+}
+
+/// Write the data source as CSV, with a row for each label. The file
+/// starts with a byte order mark so Word and Excel read it as UTF-8.
+///
+/// # Errors
+///
+/// Fails if there is no image directory to link to, or the output cannot
+/// be written.
+pub fn write(
+    sheet: &Sheet<'_>,
+    options: &Options,
+    out: &mut dyn io::Write,
+) -> Result<(), RenderError> {
+    let Some(dir) = &options.image_dir else {
+        return Err(RenderError::Unsupported(
+            "mail merge can only link to label images, so they must be saved to a directory"
+                .to_string(),
+        ));
+    };
+    let separator = if dir.contains('\\') { '\\' } else { '/' };
+    let dir = dir.trim_end_matches(['/', '\\']);
+
+    out.write_all("\u{feff}".as_bytes())?;
+    write_csv_row(out, &["AssetID", "Name", "Location", "Image"])?;
+    for (idx, label) in sheet.labels.iter().enumerate() {
+        let image = format!("{dir}{separator}{}", image_file_name(label));
+        if let Some(entry) = options.entries.get(idx) {
+            write_csv_row(
+                out,
+                &[
+                    &entry.asset_id.to_string(),
+                    &entry.name,
+                    entry.location.as_deref().unwrap_or_default(),
+                    &image,
+                ],
+            )?;
+        } else {
+            let description = sheet
+                .descriptions
+                .get(idx)
+                .cloned()
+                .unwrap_or_else(|| sheet.lang.numbered_label(idx + 1));
+            write_csv_row(out, &["", &description, "", &image])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{i18n::Lang, layout::Layout};
+
+    #[test]
+    fn rows_follow_the_labels() {
+        let config = Layout::default().build().unwrap();
+        let labels = [
+            bytes::Bytes::from_static(b"first"),
+            bytes::Bytes::from_static(b"second"),
+            bytes::Bytes::from_static(b"third"),
+        ];
+        let sheet = Sheet {
+            config: &config,
+            labels: &labels,
+            descriptions: &[],
+            lang: Lang::En,
+        };
+        let entries = crate::asset_list::parse("000-001--000-002")
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .zip(["Drill", "Saw"])
+            .map(|(asset_id, name)| IndexEntry {
+                asset_id,
+                name: name.to_string(),
+                location: Some("Garage".to_string()),
+                cell: None,
+                tag_colors: vec![],
+            })
+            .collect();
+        let options = Options {
+            image_dir: Some("/labels".to_string()),
+            entries,
+        };
+        let mut out = vec![];
+        write(&sheet, &options, &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let rows: Vec<_> = csv.lines().skip(1).collect();
+        assert_eq!(
+            rows,
+            [
+                format!(
+                    "\"000-001\",\"Drill\",\"Garage\",\"/labels/{}\"",
+                    image_file_name(b"first")
+                ),
+                format!(
+                    "\"000-002\",\"Saw\",\"Garage\",\"/labels/{}\"",
+                    image_file_name(b"second")
+                ),
+                format!(
+                    "\"\",\"Label 3\",\"\",\"/labels/{}\"",
+                    image_file_name(b"third")
+                ),
+            ]
+        );
+    }
+}
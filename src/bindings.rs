@@ -0,0 +1,70 @@
+//! Support shared by the bindings for other languages.
+
+use serde::Deserialize;
+
+use crate::{
+    api::HomeboxClient,
+    asset_list::{self, Validate},
+    error::{Error, RenderError},
+    i18n::Lang,
+    layout::Layout,
+    render::{Registry, Sheet},
+};
+
+/// The options for generating a sheet from another language
+#[derive(Deserialize)]
+pub struct Config {
+    pub server: String,
+    pub username: String,
+    pub password: String,
+    pub assets: String,
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default = "default_format")]
+    pub format: String,
+    pub lang: Option<String>,
+}
+
+fn default_format() -> String {
+    "html".to_string()
+}
+
+impl Config {
+    /// Fetch the labels and render the sheet
+    pub fn generate(&self) -> Result<Vec<u8>, Error> {
+        let config = self.layout.build()?;
+        let registry = Registry::self_contained();
+        let renderer = registry
+            .get(&self.format)
+            .ok_or_else(|| RenderError::UnknownFormat(self.format.clone()))?;
+
+        let list = asset_list::parse(&self.assets)?;
+        list.validate()?;
+
+        let client = HomeboxClient::login(&self.server, &self.username, &self.password)?;
+        let asset_ids: Vec<_> = list.into_iter().flatten().collect();
+        let labels = asset_ids
+            .iter()
+            .map(|&asset_id| {
+                client
+                    .get_asset_label(asset_id)
+                    .map_err(|source| Error::Label { asset_id, source })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let lang = self
+            .lang
+            .as_deref()
+            .and_then(Lang::from_locale)
+            .unwrap_or_default();
+        Ok(renderer.render(&Sheet {
+            config: &config,
+            labels: &labels,
+            descriptions: &asset_ids
+                .iter()
+                .map(|id| lang.asset_label(id))
+                .collect::<Vec<_>>(),
+            lang,
+        })?)
+    }
+}
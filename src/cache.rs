@@ -0,0 +1,284 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process,
+    time::SystemTime,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use clap::Subcommand;
+use homebox_label_maker::asset_list::AssetId;
+use serde::{Deserialize, Serialize};
+
+#[derive(clap::Args)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    command: CacheCommand,
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Show how many labels are cached for each server, their size, and
+    /// when the oldest and newest were downloaded, along with any kept
+    /// sessions
+    Status,
+    /// Delete the cached labels and kept sessions, for every server or
+    /// only one
+    Clear {
+        /// Only delete the labels and session for this server
+        #[arg(long, short)]
+        server: Option<String>,
+    },
+}
+
+/// A cache of label images downloaded from a server, kept in the
+/// user's cache directory
+pub struct LabelCache {
+    dir: PathBuf,
+}
+
+impl LabelCache {
+    /// The directory holding the caches for every server
+    fn root() -> anyhow::Result<PathBuf> {
+        Ok(dirs::cache_dir()
+            .context("Failed to find cache directory")?
+            .join(env!("CARGO_PKG_NAME"))
+            .join("labels"))
+    }
+
+    /// Open the cache for the server with the given base URL
+    pub fn for_server(base_url: &str) -> anyhow::Result<Self> {
+        let dir = Self::root()?.join(server_key(base_url));
+        fs::create_dir_all(&dir).context("Failed to create cache directory")?;
+        Ok(Self { dir })
+    }
+
+    /// Open a cache kept in the given directory
+    #[cfg(test)]
+    pub fn in_dir(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, asset_id: AssetId) -> PathBuf {
+        self.dir.join(format!("{asset_id}.img"))
+    }
+
+    /// Get the cached label for an asset, if there is one
+    pub fn get(&self, asset_id: AssetId) -> Option<bytes::Bytes> {
+        fs::read(self.path(asset_id)).ok().map(bytes::Bytes::from)
+    }
+
+    /// Get the cached label for an asset without reading it into
+    /// memory, so it is only read when it is used and the system can
+    /// let it go again, if there is one
+    pub fn map(&self, asset_id: AssetId) -> Option<bytes::Bytes> {
+        let file = File::open(self.path(asset_id)).ok()?;
+        // SAFETY: cached labels are replaced by renaming a new file over
+        // them, never written in place, so the mapped file does not
+        // change while it is in use
+        match unsafe { memmap2::Mmap::map(&file) } {
+            Ok(map) => Some(bytes::Bytes::from_owner(map)),
+            Err(e) => {
+                tracing::debug!("Failed to map cached label for {asset_id}, reading it: {e}");
+                self.get(asset_id)
+            }
+        }
+    }
+
+    /// Store the label for an asset. The label is written beside the
+    /// cache before being moved in, so labels mapped with
+    /// [`LabelCache::map`] never change.
+    pub fn put(&self, asset_id: AssetId, label: &[u8]) -> anyhow::Result<()> {
+        let partial = self
+            .dir
+            .join(format!("{asset_id}.{}.partial", process::id()));
+        fs::write(&partial, label).context("Failed to write label to cache")?;
+        fs::rename(&partial, self.path(asset_id)).context("Failed to write label to cache")
+    }
+}
+
+/// A session kept with `--stay-logged-in`, to reuse until it expires
+#[derive(Serialize, Deserialize)]
+pub struct CachedToken {
+    pub username: String,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl CachedToken {
+    /// The directory holding the sessions for every server
+    fn root() -> anyhow::Result<PathBuf> {
+        Ok(dirs::cache_dir()
+            .context("Failed to find cache directory")?
+            .join(env!("CARGO_PKG_NAME"))
+            .join("tokens"))
+    }
+
+    fn path(server: &str) -> anyhow::Result<PathBuf> {
+        Ok(Self::root()?.join(format!("{}.toml", server_key(&api_url(server)))))
+    }
+
+    /// Load the session kept for the user on the server, unless it is
+    /// for another user or about to expire
+    pub fn load(server: &str, username: &str) -> Option<Self> {
+        let cached: Self = toml::from_str(&fs::read_to_string(Self::path(server).ok()?).ok()?)
+            .inspect_err(|e| tracing::warn!("Ignoring invalid kept session: {e}"))
+            .ok()?;
+        // Leave some time to finish with it before it expires
+        (cached.username == username && cached.expires_at > Utc::now() + TimeDelta::hours(1))
+            .then_some(cached)
+    }
+
+    /// Keep the session for the server, readable only by the user
+    pub fn save(&self, server: &str) -> anyhow::Result<()> {
+        let path = Self::path(server)?;
+        fs::create_dir_all(Self::root()?).context("Failed to create cache directory")?;
+        fs::write(&path, toml::to_string(self)?).context("Failed to write session")?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+                .context("Failed to restrict access to session")?;
+        }
+        Ok(())
+    }
+
+    /// Delete the session kept for the server, if there is one
+    pub fn forget(server: &str) -> anyhow::Result<()> {
+        let path = Self::path(server)?;
+        if fs::exists(&path).context("Failed to check if session exists")? {
+            fs::remove_file(path).context("Failed to delete session")?;
+        }
+        Ok(())
+    }
+}
+
+/// The base URL of the API, as the client has it, which labels and
+/// sessions are cached by
+fn api_url(server: &str) -> String {
+    format!("{}/api", server.trim_end_matches('/'))
+}
+
+/// A directory name identifying a server, made from its URL
+pub fn server_key(base_url: &str) -> String {
+    base_url
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Show or clear the label cache
+pub fn run(args: &CacheArgs) -> anyhow::Result<()> {
+    let root = LabelCache::root()?;
+    match &args.command {
+        CacheCommand::Status => {
+            if !fs::exists(&root).context("Failed to check if cache exists")? {
+                return Ok(());
+            }
+            let mut servers: Vec<_> = fs::read_dir(&root)
+                .context("Failed to read cache")?
+                .collect::<Result<_, _>>()
+                .context("Failed to read cache")?;
+            servers.sort_by_key(fs::DirEntry::file_name);
+            for server in servers {
+                status(&server.path())?;
+            }
+            sessions()?;
+        }
+        CacheCommand::Clear { server: None } => {
+            for root in [root, CachedToken::root()?] {
+                if fs::exists(&root).context("Failed to check if cache exists")? {
+                    fs::remove_dir_all(&root).context("Failed to clear cache")?;
+                }
+            }
+            tracing::info!("Cleared the cache for every server");
+        }
+        CacheCommand::Clear {
+            server: Some(server),
+        } => {
+            let dir = root.join(server_key(&api_url(server)));
+            let labels = fs::exists(&dir).context("Failed to check if cache exists")?;
+            if labels {
+                fs::remove_dir_all(&dir).context("Failed to clear cache")?;
+            }
+            let session = fs::exists(CachedToken::path(server)?)
+                .context("Failed to check if cache exists")?;
+            CachedToken::forget(server)?;
+            if labels || session {
+                tracing::info!("Cleared the cache for {server}");
+            } else {
+                tracing::warn!("Nothing is cached for {server}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Print the number, size and age of the labels cached in a server's
+/// directory
+fn status(dir: &Path) -> anyhow::Result<()> {
+    let (mut count, mut bytes) = (0, 0);
+    let mut modified: Vec<SystemTime> = vec![];
+    for entry in fs::read_dir(dir).context("Failed to read cache")? {
+        let metadata = entry
+            .and_then(|entry| entry.metadata())
+            .context("Failed to read cache")?;
+        count += 1;
+        bytes += metadata.len();
+        modified.extend(metadata.modified().ok());
+    }
+    let date = |time: Option<&SystemTime>| {
+        time.map(|&time| {
+            DateTime::<Local>::from(time)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+    };
+    #[allow(
+        clippy::cast_precision_loss,
+        reason = "the size is only shown to one decimal place"
+    )]
+    let mib = bytes as f64 / 1024.0 / 1024.0;
+    println!(
+        "{}\t{count} labels\t{mib:.1} MiB\t{} to {}",
+        dir.file_name().unwrap_or_default().to_string_lossy(),
+        date(modified.iter().min()),
+        date(modified.iter().max()),
+    );
+    Ok(())
+}
+
+/// Print the user and expiry of each kept session
+fn sessions() -> anyhow::Result<()> {
+    let root = CachedToken::root()?;
+    if !fs::exists(&root).context("Failed to check if cache exists")? {
+        return Ok(());
+    }
+    let mut paths: Vec<_> = fs::read_dir(&root)
+        .context("Failed to read cache")?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()
+        .context("Failed to read cache")?;
+    paths.sort();
+    for path in paths {
+        let Ok(session) = toml::from_str::<CachedToken>(
+            &fs::read_to_string(&path).context("Failed to read cache")?,
+        ) else {
+            continue;
+        };
+        println!(
+            "{}\tsession for {}\texpires {}",
+            path.file_stem().unwrap_or_default().to_string_lossy(),
+            session.username,
+            session
+                .expires_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M"),
+        );
+    }
+    Ok(())
+}
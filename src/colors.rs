@@ -0,0 +1,102 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use anyhow::{Context, anyhow};
+use homebox_label_maker::{
+    hash,
+    html::{Tint, is_safe_color},
+};
+use serde::Deserialize;
+
+/// The colors chosen for labels, kept in `colors.toml` in the
+/// configuration directory, e.g.
+///
+/// ```toml
+/// [locations]
+/// Garage = "#d33"
+/// Kitchen = "steelblue"
+/// ```
+///
+/// Anything without a color chosen gets one made up from its name.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    /// The color for each location, by name
+    locations: HashMap<String, String>,
+}
+
+impl Colors {
+    fn path() -> anyhow::Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .context("Failed to find configuration directory")?
+            .join(env!("CARGO_PKG_NAME"))
+            .join("colors.toml"))
+    }
+
+    /// Load the chosen colors, if any have been
+    pub fn load() -> anyhow::Result<Self> {
+        let path = Self::path()?;
+        if !fs::exists(&path).context("Failed to check if colors exist")? {
+            return Ok(Self::default());
+        }
+        let colors: Self = toml::from_str(
+            &fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?,
+        )
+        .context("Failed to parse colors")?;
+        for color in colors.locations.values() {
+            if !is_safe_color(color) {
+                Err(anyhow!(
+                    "Invalid color: {color}. Colors must be in hex, e.g. #d33, or a CSS color name"
+                ))?;
+            }
+        }
+        Ok(colors)
+    }
+
+    /// The color for a location, tinting the way given
+    pub fn location(&self, name: &str, tint: Tint) -> String {
+        self.locations
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| made_up(name, tint))
+    }
+}
+
+/// A color made up from a name, the same every time. Backgrounds are
+/// pale so the labels stay readable on them.
+fn made_up(name: &str, tint: Tint) -> String {
+    #[allow(clippy::cast_precision_loss, reason = "the hue is less than 360")]
+    let hue = (hash::stable(name.as_bytes()) % 360) as f64;
+    match tint {
+        Tint::Border => hsl_hex(hue, 0.7, 0.45),
+        Tint::Background => hsl_hex(hue, 0.7, 0.85),
+    }
+}
+
+/// A color given by hue, saturation and lightness in hex, as colors
+/// written into the page must be
+fn hsl_hex(hue: f64, saturation: f64, lightness: f64) -> String {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let second = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (red, green, blue) = match hue {
+        h if h < 60.0 => (chroma, second, 0.0),
+        h if h < 120.0 => (second, chroma, 0.0),
+        h if h < 180.0 => (0.0, chroma, second),
+        h if h < 240.0 => (0.0, second, chroma),
+        h if h < 300.0 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    let lightest = lightness - chroma / 2.0;
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "each channel is between 0 and 255"
+    )]
+    let channel = |value: f64| ((value + lightest) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(red),
+        channel(green),
+        channel(blue)
+    )
+}
@@ -0,0 +1,85 @@
+//! Completing the asset list from the items on the server, for shells
+//! set up with e.g. `source <(COMPLETE=bash homebox-label-maker)`.
+
+use std::{env, ffi::OsStr};
+
+use clap_complete::engine::CompletionCandidate;
+use homebox_label_maker::api::HomeboxClient;
+
+use crate::ConnectionArgs;
+
+/// Complete the last asset ID in the asset list, or the location with
+/// `--location-tree`, from the server given earlier on the command line.
+/// Nothing is completed if the password would have to be asked for.
+pub fn assets(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Some(current) = current.to_str() else {
+        return vec![];
+    };
+    // The completer is only given the current word, so the rest are
+    // read from the command line that the shell passed
+    let words: Vec<_> = env::args().collect();
+    let Some(client) = login(&words) else {
+        return vec![];
+    };
+
+    if words.iter().any(|word| word == "--location-tree") {
+        let Ok(tree) = client.location_tree() else {
+            return vec![];
+        };
+        return tree
+            .iter()
+            .flat_map(|root| root.descendants())
+            .filter(|node| node.name.starts_with(current))
+            .map(|node| CompletionCandidate::new(&node.name))
+            .collect();
+    }
+
+    // Only the ID after the last separator is completed, keeping the
+    // IDs and ranges before it
+    let start = [
+        current.rfind(',').map(|i| i + 1),
+        current.rfind("--").map(|i| i + 2),
+    ]
+    .into_iter()
+    .flatten()
+    .max()
+    .unwrap_or(0);
+    let (before, partial) = current.split_at(start);
+    let Ok(items) = client.list_items() else {
+        return vec![];
+    };
+    items
+        .into_iter()
+        .filter(|item| item.has_asset_id() && item.asset_id.starts_with(partial))
+        .map(|item| {
+            CompletionCandidate::new(format!("{before}{}", item.asset_id))
+                .help(Some(item.name.into()))
+        })
+        .collect()
+}
+
+/// Log in with the connection flags among the words, if the password
+/// is known without asking for it
+fn login(words: &[String]) -> Option<HomeboxClient> {
+    let flag = |short: &str, long: &str| {
+        words.iter().enumerate().find_map(|(i, word)| {
+            if word == short || word == long {
+                words.get(i + 1).cloned()
+            } else {
+                word.strip_prefix(&format!("{long}="))
+                    .map(ToString::to_string)
+            }
+        })
+    };
+    let connection = ConnectionArgs {
+        server: flag("-s", "--server")?,
+        username: flag("-u", "--username"),
+        password: flag("-p", "--password"),
+        password_file: flag("--password-file", "--password-file").map(Into::into),
+        password_stdin: false,
+        password_cmd: None,
+        stay_logged_in: false,
+    };
+    let (username, password) = connection.stored_credentials().ok()?;
+    HomeboxClient::login(&connection.server, &username, &password?).ok()
+}
@@ -0,0 +1,326 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::PathBuf,
+    process,
+};
+
+use anyhow::{Context, anyhow};
+
+use crate::ConnectionArgs;
+
+impl ConnectionArgs {
+    /// Resolve the username and password, falling back to ~/.netrc for
+    /// anything not given explicitly, then prompting for them
+    pub fn credentials(&self) -> anyhow::Result<(String, String)> {
+        let username = self.username()?;
+        if let Some(password) = self.stored_password(&username)? {
+            return Ok((username, password));
+        }
+
+        tracing::debug!("Prompting for password...");
+        let password = rpassword::prompt_password("Enter Homebox Password: ")
+            .context("Failed to get password")?;
+        Ok((username, password))
+    }
+
+    /// Resolve the username and, if it is given or in ~/.netrc, the
+    /// password, without prompting for either
+    pub fn stored_credentials(&self) -> anyhow::Result<(String, Option<String>)> {
+        let username = self
+            .stored_username()?
+            .context("No username given and none found in .netrc")?;
+        let password = self.stored_password(&username)?;
+        Ok((username, password))
+    }
+
+    /// Resolve the username, falling back to ~/.netrc if it is not
+    /// given explicitly, then prompting for it
+    pub fn username(&self) -> anyhow::Result<String> {
+        if let Some(username) = self.stored_username()? {
+            return Ok(username);
+        }
+        // Only ask on a terminal, as otherwise stdin may hold the
+        // password, or nobody is there to answer
+        if !io::stdin().is_terminal() {
+            Err(anyhow!(
+                "No username given and none found in .netrc, and cannot ask for it without a terminal"
+            ))?;
+        }
+
+        tracing::debug!("Prompting for username...");
+        eprint!("Enter Homebox Username: ");
+        io::stderr().flush().context("Failed to get username")?;
+        let mut line = String::new();
+        io::stdin()
+            .lock()
+            .read_line(&mut line)
+            .context("Failed to get username")?;
+        let username = first_line(&line);
+        if username.is_empty() {
+            Err(anyhow!("No username given"))?;
+        }
+        Ok(username)
+    }
+
+    /// Get the username if it is given explicitly or in ~/.netrc
+    fn stored_username(&self) -> anyhow::Result<Option<String>> {
+        if let Some(username) = &self.username {
+            return Ok(Some(username.clone()));
+        }
+        Ok(NetrcEntry::find(&self.server)?.and_then(|entry| entry.login))
+    }
+
+    /// Get the password for the user from whichever source was given,
+    /// falling back to ~/.netrc, without prompting for it
+    fn stored_password(&self, username: &str) -> anyhow::Result<Option<String>> {
+        if let Some(password) = self.explicit_password()? {
+            return Ok(Some(password));
+        }
+
+        if let Some(NetrcEntry {
+            login,
+            password: Some(password),
+        }) = NetrcEntry::find(&self.server)?
+            && login.as_ref().is_none_or(|login| login == username)
+        {
+            tracing::debug!("Using password from .netrc");
+            return Ok(Some(password));
+        }
+        Ok(None)
+    }
+
+    /// Get the password from whichever source was given, if any
+    fn explicit_password(&self) -> anyhow::Result<Option<String>> {
+        if let Some(password) = &self.password {
+            tracing::warn!(
+                "The password has been provided on the command line. Note that this is less secure then providing it when requested."
+            );
+            return Ok(Some(password.clone()));
+        }
+
+        if let Some(path) = &self.password_file {
+            tracing::debug!("Reading password from file...");
+            let contents = fs::read_to_string(path).context("Failed to read password file")?;
+            return Ok(Some(first_line(&contents)));
+        }
+
+        if self.password_stdin {
+            tracing::debug!("Reading password from stdin...");
+            let mut line = String::new();
+            io::stdin()
+                .lock()
+                .read_line(&mut line)
+                .context("Failed to read password from stdin")?;
+            return Ok(Some(first_line(&line)));
+        }
+
+        if let Some(cmd) = &self.password_cmd {
+            tracing::debug!("Running password command...");
+            let output = shell(cmd)
+                .stderr(process::Stdio::inherit())
+                .output()
+                .context("Failed to run password command")?;
+            if !output.status.success() {
+                Err(anyhow!("Password command failed with {}", output.status))?;
+            }
+            let stdout =
+                String::from_utf8(output.stdout).context("Password command output invalid")?;
+            return Ok(Some(first_line(&stdout)));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Take the first line of a password source, without the line ending
+fn first_line(s: &str) -> String {
+    s.lines().next().unwrap_or_default().to_string()
+}
+
+/// Build a command that runs the given string through the platform
+/// shell
+fn shell(cmd: &str) -> process::Command {
+    if cfg!(windows) {
+        let mut command = process::Command::new("cmd");
+        command.args(["/C", cmd]);
+        command
+    } else {
+        let mut command = process::Command::new("sh");
+        command.args(["-c", cmd]);
+        command
+    }
+}
+
+/// The credentials for a host from a netrc file
+#[derive(Default)]
+struct NetrcEntry {
+    login: Option<String>,
+    password: Option<String>,
+}
+
+impl NetrcEntry {
+    /// Find the entry for the host of the server URL, falling back to
+    /// the default entry. The file is read from `$NETRC` if set, or
+    /// `.netrc` (`_netrc` on Windows) in the home directory.
+    fn find(server: &str) -> anyhow::Result<Option<Self>> {
+        let Some(path) = env::var_os("NETRC").map(PathBuf::from).or_else(|| {
+            dirs::home_dir().map(|home| home.join(if cfg!(windows) { "_netrc" } else { ".netrc" }))
+        }) else {
+            return Ok(None);
+        };
+        if !fs::exists(&path).context("Failed to check if netrc exists")? {
+            return Ok(None);
+        }
+
+        let host = reqwest::Url::parse(server)
+            .context("Failed to parse server URL")?
+            .host_str()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        let contents = fs::read_to_string(&path).context("Failed to read netrc")?;
+        tracing::debug!("Looking up {host} in {}", path.display());
+        Ok(Self::parse(&contents, &host))
+    }
+
+    /// Parse the entry for a host from the contents of a netrc file
+    fn parse(contents: &str, host: &str) -> Option<Self> {
+        // Entries are `Some((is_machine, entry))` while reading the
+        // matching machine or the default, and `None` otherwise
+        let mut tokens = netrc_tokens(contents).into_iter();
+        let mut default = None;
+        let mut current: Option<(bool, NetrcEntry)> = None;
+        while let Some(token) = tokens.next() {
+            match token.as_str() {
+                "machine" | "default" => {
+                    match current.take() {
+                        Some((true, entry)) => return Some(entry),
+                        Some((false, entry)) => default = Some(entry),
+                        None => (),
+                    }
+                    if token == "machine" {
+                        let name = tokens.next()?;
+                        current = (name == host).then(|| (true, NetrcEntry::default()));
+                    } else {
+                        current = Some((false, NetrcEntry::default()));
+                    }
+                }
+                "login" | "password" | "account" => {
+                    let value = tokens.next()?;
+                    if let Some((_, entry)) = &mut current {
+                        match token.as_str() {
+                            "login" => entry.login = Some(value),
+                            "password" => entry.password = Some(value),
+                            _ => (),
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        current.map(|(_, entry)| entry).or(default)
+    }
+}
+
+/// Split the contents of a netrc file into words. Words can be quoted to
+/// hold spaces, with a backslash escaping the next character, as curl
+/// allows. Comments run from a `#` to the end of the line, and macro
+/// definitions until the next blank line, and both are left out.
+fn netrc_tokens(contents: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut in_macro = false;
+    for line in contents.lines() {
+        if in_macro {
+            in_macro = !line.trim().is_empty();
+            continue;
+        }
+        let mut chars = line.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+                continue;
+            }
+            if c == '#' {
+                break;
+            }
+            let mut word = String::new();
+            if c == '"' {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => word.extend(chars.next()),
+                        _ => word.push(c),
+                    }
+                }
+            } else {
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                    word.push(c);
+                }
+                if word == "macdef" {
+                    in_macro = true;
+                    break;
+                }
+            }
+            tokens.push(word);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NETRC: &str = r#"# Servers at home
+machine homebox.example.com login alice password "two words"
+machine other.example.com
+    login bob # the old account
+    password "quote\"d"
+
+macdef init
+machine homebox.example.com login mallory
+
+default login guest password guest
+"#;
+
+    #[test]
+    fn netrc_entry_is_found_for_host() {
+        let entry = NetrcEntry::parse(NETRC, "homebox.example.com").unwrap();
+        assert_eq!(entry.login.as_deref(), Some("alice"));
+        assert_eq!(entry.password.as_deref(), Some("two words"));
+
+        let entry = NetrcEntry::parse(NETRC, "other.example.com").unwrap();
+        assert_eq!(entry.login.as_deref(), Some("bob"));
+        assert_eq!(entry.password.as_deref(), Some(r#"quote"d"#));
+    }
+
+    #[test]
+    fn netrc_default_is_used_for_other_hosts() {
+        let entry = NetrcEntry::parse(NETRC, "unknown.example.com").unwrap();
+        assert_eq!(entry.login.as_deref(), Some("guest"));
+        assert_eq!(entry.password.as_deref(), Some("guest"));
+        assert!(NetrcEntry::parse("machine a login b", "c").is_none());
+    }
+
+    #[test]
+    fn password_file_is_read_without_line_ending() {
+        let path = env::temp_dir().join(format!("homebox-password-{}", process::id()));
+        fs::write(&path, "hunter2\r\nignored\n").unwrap();
+        let connection = ConnectionArgs {
+            server: "https://homebox.example.com".to_string(),
+            username: Some("alice".to_string()),
+            password: None,
+            password_file: Some(path.clone()),
+            password_stdin: false,
+            password_cmd: None,
+            stay_logged_in: false,
+        };
+        let password = connection.explicit_password();
+        fs::remove_file(path).unwrap();
+        assert_eq!(password.unwrap().as_deref(), Some("hunter2"));
+        assert_eq!(first_line("hunter2\n"), "hunter2");
+        assert_eq!(first_line(""), "");
+    }
+}
@@ -0,0 +1,260 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::PathBuf,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, anyhow};
+use chrono::Utc;
+
+use homebox_label_maker::{
+    api::HomeboxClient,
+    asset_list::AssetId,
+    i18n::Lang,
+    layout::{Layout, SheetConfig},
+    render::{Registry, Sheet},
+};
+
+use crate::{ConnectionArgs, failure::Failure};
+
+#[derive(clap::Args)]
+pub struct DaemonArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// The directory to write each sheet to before it is printed
+    #[arg(index = 1)]
+    output_dir: PathBuf,
+
+    /// The format to write the sheets in
+    #[arg(
+        long,
+        default_value = "html",
+        value_parser = clap::builder::PossibleValuesParser::new(Registry::self_contained().names()),
+    )]
+    format: String,
+
+    #[command(flatten)]
+    layout: Layout,
+
+    /// How often to check the server for new items, in seconds, or 0 to
+    /// only check when a callback arrives
+    #[arg(long, default_value_t = 60)]
+    interval: u64,
+
+    /// Rather than printing each new item as it appears, print every
+    /// item created since the last sync in one batch at this interval,
+    /// e.g. `24h`, as the `sync` subcommand does. The first batch is
+    /// printed straight away, and callbacks print a batch early.
+    #[arg(long, conflicts_with = "interval", value_parser = parse_period)]
+    every: Option<Duration>,
+
+    /// Also listen for HTTP callbacks on this address, e.g. from a
+    /// webhook, checking for new items as soon as any request arrives
+    #[arg(long)]
+    listen: Option<String>,
+
+    /// The printer to print to, rather than the system default
+    #[arg(long)]
+    printer: Option<String>,
+
+    /// Write the sheets without printing them
+    #[arg(long)]
+    no_print: bool,
+
+    #[cfg(feature = "mqtt")]
+    #[command(flatten)]
+    mqtt: crate::mqtt::MqttArgs,
+}
+
+/// What wakes the daemon
+pub enum Trigger {
+    /// Check the server for new items
+    Check,
+    /// Print labels for these assets straight away
+    #[cfg(feature = "mqtt")]
+    Print(Vec<AssetId>),
+}
+
+/// Parse a period such as `90s`, `30m`, `24h` or `7d`
+fn parse_period(period: &str) -> Result<Duration, String> {
+    let unit = period
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or("the period needs a unit: s, m, h or d")?;
+    let count: u64 = period[..unit]
+        .parse()
+        .map_err(|_| "the period must start with a number")?;
+    let seconds = match &period[unit..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err("the period's unit must be s, m, h or d".to_string()),
+    };
+    if count == 0 {
+        return Err("the period must be longer than zero".to_string());
+    }
+    Ok(Duration::from_secs(count * seconds))
+}
+
+/// Watch the server for new items with asset IDs, printing a label for
+/// each as it appears, or in batches on a schedule. Items that exist
+/// when the daemon starts are not printed unless printing in batches.
+pub fn run(args: &DaemonArgs, lang: Lang) -> anyhow::Result<()> {
+    let config = args.layout.build().context(Failure::Layout)?;
+    fs::create_dir_all(&args.output_dir).context("Failed to create output directory")?;
+
+    let mut client = crate::authenticate(&args.connection)?;
+    let (period, mut seen) = if let Some(every) = args.every {
+        (Some(every), HashSet::new())
+    } else {
+        let seen = asset_ids(&client)?;
+        tracing::info!("Watching for new items, ignoring {} existing", seen.len());
+        let interval = (args.interval > 0).then(|| Duration::from_secs(args.interval));
+        (interval, seen)
+    };
+
+    let (wake, woken) = mpsc::channel();
+    if let Some(listen) = &args.listen {
+        let server = tiny_http::Server::http(listen)
+            .map_err(|e| anyhow!(e))
+            .context("Failed to start callback server")?;
+        tracing::info!("Listening for callbacks on http://{listen}/");
+        let wake = wake.clone();
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                tracing::debug!("Callback received: {} {}", request.method(), request.url());
+                if let Err(e) = request.respond(tiny_http::Response::empty(202)) {
+                    tracing::warn!("Failed to respond to callback: {e}");
+                }
+                if wake.send(Trigger::Check).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    #[cfg(feature = "mqtt")]
+    crate::mqtt::subscribe(&args.mqtt, &wake)?;
+
+    // Batches are printed straight away, then on schedule
+    let mut due = if args.every.is_some() {
+        Some(Instant::now())
+    } else {
+        period.map(|period| Instant::now() + period)
+    };
+    loop {
+        // The period passing triggers a check as well as callbacks
+        let trigger = match due {
+            Some(due) => woken
+                .recv_timeout(due.saturating_duration_since(Instant::now()))
+                .ok(),
+            None => woken.recv().ok(),
+        };
+        if trigger.is_none() {
+            due = due.zip(period).map(|(due, period)| due + period);
+        }
+        let result = match trigger {
+            #[cfg(feature = "mqtt")]
+            Some(Trigger::Print(asset_ids)) => print(args, &client, &config, &asset_ids, lang),
+            Some(Trigger::Check) | None if args.every.is_some() => {
+                sync(args, &client, &config, lang)
+            }
+            Some(Trigger::Check) | None => check(args, &client, &config, &mut seen, lang),
+        };
+
+        let Err(e) = result else {
+            continue;
+        };
+        tracing::warn!("Failed to print labels: {e:#}");
+        // The session expires eventually, so log in again when rejected
+        if crate::session_expired(&e) {
+            match crate::reauthenticate(&args.connection) {
+                Ok(new_client) => client = new_client,
+                Err(e) => tracing::warn!("Failed to log in again: {e:#}"),
+            }
+        }
+    }
+}
+
+/// The asset IDs of every item on the server that has one
+fn asset_ids(client: &HomeboxClient) -> anyhow::Result<HashSet<AssetId>> {
+    let mut asset_ids = HashSet::new();
+    for item in client.list_items().context("Failed to list items")? {
+        if item.has_asset_id() {
+            asset_ids.extend(crate::resolve_assets(&item.asset_id)?);
+        }
+    }
+    Ok(asset_ids)
+}
+
+/// Print labels for any items that have appeared since they were last
+/// seen
+fn check(
+    args: &DaemonArgs,
+    client: &HomeboxClient,
+    config: &SheetConfig,
+    seen: &mut HashSet<AssetId>,
+    lang: Lang,
+) -> anyhow::Result<()> {
+    let mut new: Vec<_> = asset_ids(client)?.difference(seen).copied().collect();
+    if new.is_empty() {
+        return Ok(());
+    }
+    new.sort();
+    tracing::info!("{} new items found", new.len());
+
+    print(args, client, config, &new, lang)?;
+    // Only once printed, so a failed print is retried with the next check
+    seen.extend(new);
+    Ok(())
+}
+
+/// Print labels for every item created since the last sync, then record
+/// the sync
+fn sync(
+    args: &DaemonArgs,
+    client: &HomeboxClient,
+    config: &SheetConfig,
+    lang: Lang,
+) -> anyhow::Result<()> {
+    let (asset_ids, state) = crate::sync::pending(client)?;
+    if !asset_ids.is_empty() {
+        print(args, client, config, &asset_ids, lang)?;
+    }
+    state.save(client.base_url())
+}
+
+/// Write a sheet of labels for the assets, and print it unless asked
+/// not to
+fn print(
+    args: &DaemonArgs,
+    client: &HomeboxClient,
+    config: &SheetConfig,
+    asset_ids: &[AssetId],
+    lang: Lang,
+) -> anyhow::Result<()> {
+    let labels = crate::fetch_labels(client, asset_ids, None)?;
+    let sheet = Sheet {
+        config,
+        labels: &labels,
+        descriptions: &crate::describe_assets(asset_ids, lang),
+        lang,
+    };
+    let path = args.output_dir.join(format!(
+        "labels-{}.{}",
+        Utc::now().format("%Y%m%d-%H%M%S%.3f"),
+        args.format
+    ));
+    let bytes_written =
+        crate::render_to_file(&Registry::self_contained(), &args.format, &sheet, &path)?;
+    println!("{}", lang.wrote(bytes_written, &path));
+
+    if !args.no_print {
+        crate::print::submit(&path, 1, args.printer.as_deref())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use std::net::ToSocketAddrs;
+
+use anyhow::{Context, bail};
+use homebox_label_maker::{api::HomeboxClient, i18n::Lang};
+
+use crate::ConnectionArgs;
+
+#[derive(clap::Args)]
+pub struct VerifyArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+}
+
+/// Check each step of connecting to the server in turn, printing what
+/// works and stopping at the first problem, which is returned with what
+/// to check
+pub fn run(args: &VerifyArgs, lang: Lang) -> anyhow::Result<()> {
+    let server = &args.connection.server;
+    let address = resolve(server).context(
+        "Failed to resolve the server's address. Check the URL, including http:// or https://",
+    )?;
+    println!("✓ {}", lang.server_resolved(&address));
+
+    let status = HomeboxClient::status(server).context(
+        "The server did not respond as Homebox does. Check the URL points to Homebox itself, \
+        not a page in front of it",
+    )?;
+    if !status.health {
+        bail!("The server reports that it is unhealthy. Check its logs.");
+    }
+    println!("✓ {}", lang.api_responds(&status.build.version));
+
+    let client =
+        crate::authenticate(&args.connection).context("Check the username and password")?;
+    println!("✓ {}", lang.logged_in());
+
+    let items = client.list_items().context("Failed to list items")?;
+    let Some(item) = items.iter().find(|item| item.has_asset_id()) else {
+        println!("- {}", lang.no_label_to_check());
+        return Ok(());
+    };
+    let asset_id = crate::resolve_assets(&item.asset_id)?[0];
+    client
+        .get_asset_label(asset_id)
+        .with_context(|| format!("The label maker failed to return the label for {asset_id}"))?;
+    println!("✓ {}", lang.label_downloaded(asset_id));
+    Ok(())
+}
+
+/// The first address the server's host name resolves to
+fn resolve(server: &str) -> anyhow::Result<String> {
+    let url = reqwest::Url::parse(server)?;
+    let host = url.host_str().context("The URL has no host")?;
+    let port = url.port_or_known_default().context("The URL has no port")?;
+    let address = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .context("The host has no addresses")?;
+    Ok(address.to_string())
+}
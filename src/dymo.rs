@@ -0,0 +1,151 @@
+//! Writing labels in the XML format of DYMO Label, which DYMO Connect
+//! also opens, so labels can be printed through DYMO's own software.
+//!
+//! Each file holds a single die-cut label, with the label image
+//! embedded and scaled to fill it.
+
+use std::io;
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+
+use crate::{error::RenderError, image::ImageType, render::Sheet};
+
+/// The number of twips, the unit of DYMO label files, in a millimeter
+const TWIPS_PER_MM: f64 = 1440.0 / 25.4;
+
+/// Options for DYMO label files
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The name of the DYMO label product, as DYMO Connect lists it,
+    /// e.g. `30252 Address` or `11354 Multi-Purpose`
+    pub paper_name: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            paper_name: "30252 Address".to_string(),
+        }
+    }
+}
+
+/// Write the only label of the sheet as a DYMO label file, sized to the
+/// cells of the layout
+///
+/// # Errors
+///
+/// Fails if the sheet has more or fewer than one label, the label is an
+/// image DYMO software cannot show, or the output cannot be written.
+pub fn write(
+    sheet: &Sheet<'_>,
+    options: &Options,
+    out: &mut dyn io::Write,
+) -> Result<(), RenderError> {
+    let [label] = sheet.labels else {
+        return Err(RenderError::Unsupported(format!(
+            "a DYMO label file holds one label, not {}",
+            sheet.labels.len()
+        )));
+    };
+    if matches!(
+        ImageType::detect(label),
+        Some(ImageType::Svg | ImageType::WebP)
+    ) {
+        return Err(RenderError::Unsupported(
+            "DYMO labels can only show PNG, JPEG or GIF images".to_string(),
+        ));
+    }
+
+    // The die is described upright, and the label objects across it
+    // when it is turned to fit the label
+    let (width, height) = sheet.config.cell_size_mm();
+    let twips = |mm: f64| (mm * TWIPS_PER_MM).round();
+    let (short, long) = (twips(width.min(height)), twips(width.max(height)));
+    let orientation = if width > height {
+        "Landscape"
+    } else {
+        "Portrait"
+    };
+    let (across, down) = (twips(width), twips(height));
+    let name = sheet
+        .descriptions
+        .first()
+        .cloned()
+        .unwrap_or_else(|| sheet.lang.numbered_label(1));
+
+    write!(
+        out,
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<DieCutLabel Version="8.0" Units="twips">
+  <PaperOrientation>{orientation}</PaperOrientation>
+  <Id>Custom</Id>
+  <PaperName>{paper}</PaperName>
+  <DrawCommands>
+    <RoundRectangle X="0" Y="0" Width="{short}" Height="{long}" Rx="0" Ry="0" />
+  </DrawCommands>
+  <ObjectInfo>
+    <ImageObject>
+      <Name>{name}</Name>
+      <ForeColor Alpha="255" Red="0" Green="0" Blue="0" />
+      <BackColor Alpha="0" Red="255" Green="255" Blue="255" />
+      <LinkedObjectName></LinkedObjectName>
+      <Rotation>Rotation0</Rotation>
+      <IsMirrored>False</IsMirrored>
+      <IsVariable>False</IsVariable>
+      <Image>{image}</Image>
+      <ScaleMode>Uniform</ScaleMode>
+      <BorderWidth>0</BorderWidth>
+      <BorderColor Alpha="255" Red="0" Green="0" Blue="0" />
+      <HorizontalAlignment>Center</HorizontalAlignment>
+      <VerticalAlignment>Center</VerticalAlignment>
+    </ImageObject>
+    <Bounds X="0" Y="0" Width="{across}" Height="{down}" />
+  </ObjectInfo>
+</DieCutLabel>
+"#,
+        paper = escape_xml(&options.paper_name),
+        name = escape_xml(&name),
+        image = BASE64_STANDARD.encode(label),
+    )?;
+    Ok(())
+}
+
+/// Escape text for the content of an XML element
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{i18n::Lang, layout::Layout};
+
+    #[test]
+    fn only_one_label_fits_in_a_file() {
+        let config = Layout::default().build().unwrap();
+        let labels = vec![bytes::Bytes::from_static(b"\x89PNG\r\n\x1a\n"); 2];
+        let sheet = Sheet {
+            config: &config,
+            labels: &labels,
+            descriptions: &[],
+            lang: Lang::En,
+        };
+        let mut out = vec![];
+        assert!(matches!(
+            write(&sheet, &Options::default(), &mut out),
+            Err(RenderError::Unsupported(_))
+        ));
+        let sheet = Sheet {
+            labels: &labels[..1],
+            ..sheet
+        };
+        write(&sheet, &Options::default(), &mut out).unwrap();
+        assert!(
+            String::from_utf8(out)
+                .unwrap()
+                .contains("<Name>Label 1</Name>")
+        );
+    }
+}
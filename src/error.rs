@@ -0,0 +1,161 @@
+//! The errors returned by the library, so callers can match on the cause
+//! of a failure.
+
+use thiserror::Error;
+
+use crate::asset_list::{AssetId, Rule};
+
+/// Any error from generating a sheet
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    AssetList(#[from] AssetListError),
+    #[cfg(feature = "client")]
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+    #[cfg(feature = "client")]
+    #[error("Failed to get label for asset {asset_id}")]
+    Label {
+        asset_id: AssetId,
+        #[source]
+        source: ApiError,
+    },
+    #[error(transparent)]
+    Layout(#[from] LayoutError),
+    #[error(transparent)]
+    Render(#[from] RenderError),
+}
+
+/// Describe an error and everything that caused it, on one line
+#[cfg(any(feature = "ffi", feature = "python", feature = "wasm"))]
+pub(crate) fn describe(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+/// A problem with a list of asset IDs
+#[derive(Debug, Error)]
+pub enum AssetListError {
+    #[error("Failed to parse asset list")]
+    Parse(#[from] Box<pest::error::Error<Rule>>),
+    #[error("The start of a range must be smaller than the end of a range!")]
+    BackwardsRange { from: AssetId, to: AssetId },
+}
+
+/// A failed request to a Homebox server
+#[cfg(feature = "client")]
+#[derive(Debug, Error)]
+pub enum ApiError {
+    /// The server could not be reached, or its response could not be
+    /// read
+    #[error("Failed to communicate with the server")]
+    Request(#[from] reqwest::Error),
+    /// The server rejected the session token
+    #[error("The server rejected the session token{}", explained(.message.as_deref()))]
+    Unauthorized { message: Option<String> },
+    /// The requested resource does not exist
+    #[error("The requested resource was not found{}", explained(.message.as_deref()))]
+    NotFound { message: Option<String> },
+    /// The server responded with something other than an image, such as
+    /// an error page from a proxy
+    #[error("The server returned {content_type} rather than an image")]
+    NotAnImage { content_type: String },
+    /// The server responded with an unexpected status, such as when it
+    /// fails itself
+    #[error("The server responded with {status}{}", explained(.message.as_deref()))]
+    Status {
+        status: reqwest::StatusCode,
+        message: Option<String>,
+    },
+    /// The server runs a version of Homebox without the feature
+    #[error("The server's version of Homebox cannot provide {0}")]
+    Unsupported(&'static str),
+}
+
+#[cfg(feature = "client")]
+impl ApiError {
+    /// Whether the request might succeed if tried again, as the server
+    /// could not be reached or was briefly unable to respond
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Request(e) => e.is_connect() || e.is_timeout() || e.is_request() || e.is_body(),
+            Self::Status { status, .. } => {
+                status.is_server_error() || *status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            }
+            Self::Unauthorized { .. }
+            | Self::NotFound { .. }
+            | Self::NotAnImage { .. }
+            | Self::Unsupported(_) => false,
+        }
+    }
+}
+
+/// The explanation the server gave for an error, to follow its
+/// description
+#[cfg(feature = "client")]
+fn explained(message: Option<&str>) -> String {
+    message
+        .map(|message| format!(": {message}"))
+        .unwrap_or_default()
+}
+
+/// A failure to log in to a Homebox server
+#[cfg(feature = "client")]
+#[derive(Debug, Error)]
+pub enum AuthError {
+    /// The server rejected the username or password
+    #[error("The server rejected the credentials{}", explained(.message.as_deref()))]
+    Rejected { message: Option<String> },
+    #[error(transparent)]
+    Api(#[from] ApiError),
+}
+
+/// A layout that cannot be placed on the page
+#[derive(Debug, Error)]
+pub enum LayoutError {
+    #[error("Page sizes, margins and spacing cannot be negative!")]
+    NegativeSize,
+    #[error("The grid must have at least one row and column!")]
+    EmptyGrid,
+    #[error("The page margins must leave space for the grid!")]
+    NoSpaceForGrid,
+    #[error("Invalid cell to skip: {0}")]
+    InvalidCell(String),
+}
+
+/// A label whose QR code could not be read back
+#[cfg(feature = "verify")]
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    /// The label is not a valid image
+    #[error("Failed to decode the label image")]
+    Image(#[source] RenderError),
+    /// No QR code could be found or decoded in the label
+    #[error("The label has no readable QR code")]
+    NoCode,
+}
+
+/// A failure to render a sheet
+#[derive(Debug, Error)]
+pub enum RenderError {
+    /// No renderer is registered with the requested name
+    #[error("Unknown output format: {0}")]
+    UnknownFormat(String),
+    /// The labels cannot be rendered as asked, for the reason given,
+    /// such as the format not holding them
+    #[error("Cannot render the labels: {0}")]
+    Unsupported(String),
+    /// The output could not be written
+    #[error("Failed to write the output")]
+    Io(#[from] std::io::Error),
+    /// The renderer itself failed
+    #[error("Failed to render the sheet")]
+    Backend(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
@@ -0,0 +1,52 @@
+use std::process::ExitCode;
+
+use derive_more::{Display, Error};
+
+/// The reasons a run can fail that are reported with a distinct exit
+/// code. These are attached as context to errors so the cause can be
+/// recovered when exiting.
+#[derive(Debug, Display, Error, Clone, Copy)]
+pub enum Failure {
+    #[display("Invalid asset list")]
+    InvalidAssets,
+    #[display("Authentication failed")]
+    Auth,
+    #[display("Asset not found")]
+    AssetNotFound,
+    #[display("Invalid layout")]
+    Layout,
+    #[display("Failed to write output")]
+    Write,
+    #[display("Label verification failed")]
+    Verify,
+    #[display("Some labels were left out")]
+    Incomplete,
+    #[display("The sheet failed a check in strict mode")]
+    Strict,
+}
+
+impl Failure {
+    /// The exit code for this failure. 1 is left for other errors and 2
+    /// for invalid command line arguments, which clap exits with, so
+    /// scripts can tell them apart.
+    pub fn exit_code(self) -> ExitCode {
+        ExitCode::from(match self {
+            Self::InvalidAssets => 10,
+            Self::Auth => 3,
+            Self::AssetNotFound => 4,
+            Self::Layout => 5,
+            Self::Write => 6,
+            Self::Verify => 7,
+            Self::Incomplete => 8,
+            Self::Strict => 9,
+        })
+    }
+
+    /// The exit code for an error, based on the failure it was caused
+    /// by, if any
+    pub fn exit_code_for(error: &anyhow::Error) -> ExitCode {
+        error
+            .downcast_ref::<Self>()
+            .map_or(ExitCode::FAILURE, |failure| failure.exit_code())
+    }
+}
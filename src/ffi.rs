@@ -0,0 +1,91 @@
+//! A C interface for generating label sheets from other languages. See
+//! `include/homebox_label_maker.h` for the declarations.
+
+use std::{
+    ffi::{CStr, CString, c_char, c_int},
+    ptr,
+};
+
+use crate::{bindings::Config, error::describe};
+
+/// Generate a sheet from the JSON configuration, describing any error
+fn generate(config: &str) -> Result<Vec<u8>, String> {
+    let config: Config =
+        serde_json::from_str(config).map_err(|e| format!("Invalid configuration: {e}"))?;
+    config.generate().map_err(|e| describe(&e))
+}
+
+/// Generate a label sheet from a JSON configuration. Returns 0 and sets
+/// `output` and `output_len` on success, or returns 1 and sets `error`
+/// to a description of the failure.
+///
+/// # Safety
+///
+/// `config` must be a valid NUL-terminated string, and `output`,
+/// `output_len` and `error` must be valid for writes. `error` may be
+/// null if the description is not wanted.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hlm_generate_sheet(
+    config: *const c_char,
+    output: *mut *mut u8,
+    output_len: *mut usize,
+    error: *mut *mut c_char,
+) -> c_int {
+    let result = if config.is_null() {
+        Err("No configuration given".to_string())
+    } else {
+        // SAFETY: the caller guarantees config is a valid string
+        unsafe { CStr::from_ptr(config) }
+            .to_str()
+            .map_err(|_| "Configuration is not valid UTF-8".to_string())
+            .and_then(generate)
+    };
+
+    match result {
+        Ok(sheet) => {
+            let sheet = sheet.into_boxed_slice();
+            // SAFETY: the caller guarantees the outputs are writable
+            unsafe {
+                *output_len = sheet.len();
+                *output = Box::into_raw(sheet).cast();
+            }
+            0
+        }
+        Err(e) => {
+            if !error.is_null() {
+                let message = CString::new(e.replace('\0', "")).unwrap_or_default();
+                // SAFETY: the caller guarantees error is writable
+                unsafe { *error = message.into_raw() };
+            }
+            1
+        }
+    }
+}
+
+/// Free a sheet returned by [`hlm_generate_sheet`]
+///
+/// # Safety
+///
+/// `output` and `output_len` must be as returned by
+/// [`hlm_generate_sheet`], and not already freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hlm_free_output(output: *mut u8, output_len: usize) {
+    if !output.is_null() {
+        // SAFETY: the caller guarantees this came from a boxed slice
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(output, output_len)) });
+    }
+}
+
+/// Free an error returned by [`hlm_generate_sheet`]
+///
+/// # Safety
+///
+/// `error` must be as returned by [`hlm_generate_sheet`], and not
+/// already freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hlm_free_error(error: *mut c_char) {
+    if !error.is_null() {
+        // SAFETY: the caller guarantees this came from a CString
+        drop(unsafe { CString::from_raw(error) });
+    }
+}
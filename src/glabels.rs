@@ -0,0 +1,172 @@
+//! Reading label sheets from the template catalogs of gLabels, which
+//! describe virtually every label product, e.g. `avery-us-templates.xml`.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, anyhow};
+use roxmltree::{Document, Node};
+
+/// The extensions of gLabels template files
+pub const EXTENSIONS: &[&str] = &["xml", "glabels"];
+
+/// The paper sizes gLabels templates name, in millimeters
+const PAPER_SIZES: &[(&str, f64, f64)] = &[
+    ("A3", 297.0, 420.0),
+    ("A4", 210.0, 297.0),
+    ("A5", 148.0, 210.0),
+    ("A6", 105.0, 148.0),
+    ("B5", 176.0, 250.0),
+    ("US-Letter", 215.9, 279.4),
+    ("US-Legal", 215.9, 355.6),
+    ("US-Executive", 184.15, 266.7),
+];
+
+/// Read a template from a gLabels catalog, as the layout fields of a
+/// template file. The part number chooses the template when there is
+/// more than one.
+pub fn read(path: &Path, part: Option<&str>) -> anyhow::Result<toml::Table> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let document = Document::parse(&contents).context("Failed to parse gLabels template")?;
+    let templates: Vec<_> = document
+        .descendants()
+        .filter(|node| node.has_tag_name("Template"))
+        .collect();
+
+    let template = match part {
+        Some(part) => find(&templates, part)?,
+        None => match templates.as_slice() {
+            [template] => *template,
+            [] => Err(anyhow!("The file has no gLabels templates"))?,
+            [first, ..] => Err(anyhow!(
+                "The file has {} templates, so choose one by its part number, e.g. {}#{}",
+                templates.len(),
+                path.display(),
+                first.attribute("part").unwrap_or_default()
+            ))?,
+        },
+    };
+    // Equivalent products only refer to the template they share a
+    // layout with
+    let template = match template.attribute("equiv") {
+        Some(equiv) => find(&templates, equiv)?,
+        None => template,
+    };
+    convert(template)
+}
+
+/// Find the template with a part number, ignoring case
+fn find<'a, 'input>(
+    templates: &[Node<'a, 'input>],
+    part: &str,
+) -> anyhow::Result<Node<'a, 'input>> {
+    templates
+        .iter()
+        .find(|template| {
+            template
+                .attribute("part")
+                .is_some_and(|name| name.eq_ignore_ascii_case(part))
+        })
+        .copied()
+        .ok_or_else(|| anyhow!("No gLabels template has part number {part}"))
+}
+
+/// Convert a template to the layout fields giving its geometry
+fn convert(template: Node<'_, '_>) -> anyhow::Result<toml::Table> {
+    let part = template.attribute("part").unwrap_or_default();
+    let (page_width, page_height) = match template.attribute("size") {
+        Some("Other" | "other") | None => (length(template, "width")?, length(template, "height")?),
+        Some(size) => PAPER_SIZES
+            .iter()
+            .find(|(name, ..)| name.eq_ignore_ascii_case(size))
+            .map(|&(_, width, height)| (width, height))
+            .ok_or_else(|| anyhow!("Template {part} is on unknown paper size {size}"))?,
+    };
+
+    let label = template
+        .children()
+        .find(|node| node.tag_name().name().starts_with("Label-"))
+        .ok_or_else(|| anyhow!("Template {part} has no label"))?;
+    let (width, height) = match label.tag_name().name() {
+        "Label-rectangle" | "Label-ellipse" => (length(label, "width")?, length(label, "height")?),
+        "Label-round" | "Label-cd" => {
+            let diameter = 2.0 * length(label, "radius")?;
+            (diameter, diameter)
+        }
+        shape => Err(anyhow!(
+            "Template {part} has unsupported label shape {shape}"
+        ))?,
+    };
+
+    let mut layouts = label.children().filter(|node| node.has_tag_name("Layout"));
+    let layout = layouts
+        .next()
+        .ok_or_else(|| anyhow!("Template {part} has no layout"))?;
+    if layouts.next().is_some() {
+        tracing::warn!("Template {part} has more than one layout, so only the first is used");
+    }
+    let count = |name| -> anyhow::Result<i64> {
+        layout
+            .attribute(name)
+            .ok_or_else(|| anyhow!("Template {part} layout has no {name}"))?
+            .parse()
+            .with_context(|| format!("Template {part} layout has an invalid {name}"))
+    };
+    let (columns, rows) = (count("nx")?, count("ny")?);
+    // Single rows or columns have no step to the next, so no spacing
+    let spacing = |step, count, size| -> anyhow::Result<f64> {
+        if count > 1 {
+            Ok(round(length(layout, step)? - size))
+        } else {
+            Ok(0.0)
+        }
+    };
+
+    let mut fields = toml::Table::new();
+    for (key, value) in [
+        ("page_width_mm", page_width),
+        ("page_height_mm", page_height),
+        ("page_margin_top_mm", length(layout, "y0")?),
+        ("page_margin_left_mm", length(layout, "x0")?),
+        ("grid_row_spacing_mm", spacing("dy", rows, height)?),
+        ("grid_col_spacing_mm", spacing("dx", columns, width)?),
+        ("label_width_mm", width),
+        ("label_height_mm", height),
+    ] {
+        fields.insert(key.to_string(), round(value).into());
+    }
+    fields.insert("grid_rows".to_string(), rows.into());
+    fields.insert("grid_columns".to_string(), columns.into());
+    Ok(fields)
+}
+
+/// Read a length attribute in millimeters. gLabels lengths are in
+/// points unless they give a unit.
+fn length(node: Node<'_, '_>, name: &str) -> anyhow::Result<f64> {
+    let value = node
+        .attribute(name)
+        .ok_or_else(|| anyhow!("{} has no {name}", node.tag_name().name()))?;
+    let split = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid length {value}"))?;
+    let mm_per_unit = match unit.trim() {
+        "" | "pt" => 25.4 / 72.0,
+        "in" => 25.4,
+        "mm" => 1.0,
+        "cm" => 10.0,
+        "pc" => 25.4 / 6.0,
+        unit => Err(anyhow!("Unknown unit {unit} in length {value}"))?,
+    };
+    Ok(number * mm_per_unit)
+}
+
+/// Round a length to a thousandth of a millimeter, to hide the rounding
+/// errors of converting between units
+fn round(mm: f64) -> f64 {
+    (mm * 1000.0).round() / 1000.0
+}
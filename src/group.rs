@@ -0,0 +1,39 @@
+use anyhow::Context;
+
+use homebox_label_maker::{api::HomeboxClient, asset_list::AssetId, i18n::Lang};
+
+/// How to group labels in the output
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum GroupBy {
+    /// The location of each item
+    Location,
+}
+
+/// Sort the assets into groups, returning the name of each group with
+/// its assets, in order of name
+pub fn groups(
+    client: &HomeboxClient,
+    asset_ids: &[AssetId],
+    by: GroupBy,
+    lang: Lang,
+) -> anyhow::Result<Vec<(String, Vec<AssetId>)>> {
+    let items = client.list_items().context("Failed to list items")?;
+
+    let mut groups: Vec<(String, Vec<AssetId>)> = vec![];
+    for &asset_id in asset_ids {
+        let item = items
+            .iter()
+            .find(|item| item.asset_id == asset_id.to_string());
+        let name = match by {
+            GroupBy::Location => item
+                .and_then(|item| item.location.as_ref())
+                .map_or_else(|| lang.no_location().to_string(), |l| l.name.clone()),
+        };
+        match groups.iter_mut().find(|(group, _)| *group == name) {
+            Some((_, ids)) => ids.push(asset_id),
+            None => groups.push((name, vec![asset_id])),
+        }
+    }
+    groups.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(groups)
+}
@@ -0,0 +1,250 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use anyhow::{Context, anyhow};
+use build_html::Html;
+use eframe::egui;
+use homebox_label_maker::{html, i18n::Lang, layout::Layout, render::Sheet};
+
+use crate::ConnectionArgs;
+
+/// The state of the most recent generation
+#[derive(Default)]
+enum Status {
+    #[default]
+    Idle,
+    Running,
+    Done(String),
+    Failed(String),
+}
+
+struct App {
+    server: String,
+    username: String,
+    password: String,
+    assets: String,
+    output: String,
+    layout: Layout,
+    status: Arc<Mutex<Status>>,
+}
+
+/// Launch the desktop front-end
+pub fn run() -> anyhow::Result<()> {
+    eframe::run_native(
+        "Homebox Label Maker",
+        eframe::NativeOptions::default(),
+        Box::new(|_cc| Ok(Box::new(App::new()))),
+    )
+    .map_err(|e| anyhow!("{e}"))
+    .context("Failed to run GUI")
+}
+
+impl App {
+    fn new() -> Self {
+        Self {
+            server: String::new(),
+            username: String::new(),
+            password: String::new(),
+            assets: String::new(),
+            output: "labels.html".to_string(),
+            layout: Layout::default(),
+            status: Arc::default(),
+        }
+    }
+
+    /// Start generating the sheet on a background thread
+    fn generate(&self, ctx: &egui::Context) {
+        let connection = ConnectionArgs {
+            server: self.server.clone(),
+            username: Some(self.username.clone()),
+            password: Some(self.password.clone()),
+            password_file: None,
+            password_stdin: false,
+            password_cmd: None,
+            stay_logged_in: false,
+        };
+        let assets = self.assets.clone();
+        let output = PathBuf::from(&self.output);
+        let layout = self.layout.clone();
+        let status = self.status.clone();
+        let ctx = ctx.clone();
+
+        *status.lock().unwrap() = Status::Running;
+        thread::spawn(move || {
+            let result = (|| -> anyhow::Result<usize> {
+                let config = layout.build()?;
+                let client = crate::authenticate(&connection)?;
+                let asset_ids = crate::resolve_assets(&assets)?;
+                let labels = crate::fetch_labels(&client, &asset_ids, None)?;
+                let lang = Lang::detect();
+                let page = html::generate(
+                    &Sheet {
+                        config: &config,
+                        labels: &labels,
+                        descriptions: &crate::describe_assets(&asset_ids, lang),
+                        lang,
+                    },
+                    &html::Options::default(),
+                );
+                fs::write(&output, page.to_html_string()).context("Failed to write output")?;
+                Ok(labels.len())
+            })();
+
+            *status.lock().unwrap() = match result {
+                Ok(count) => Status::Done(format!("Wrote {count} labels to {}", output.display())),
+                Err(e) => Status::Failed(format!("{e:#}")),
+            };
+            ctx.request_repaint();
+        });
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Homebox");
+        egui::Grid::new("connection").num_columns(2).show(ui, |ui| {
+            ui.label("Server");
+            ui.text_edit_singleline(&mut self.server);
+            ui.end_row();
+            ui.label("Username");
+            ui.text_edit_singleline(&mut self.username);
+            ui.end_row();
+            ui.label("Password");
+            ui.add(egui::TextEdit::singleline(&mut self.password).password(true));
+            ui.end_row();
+        });
+
+        ui.separator();
+        ui.heading("Labels");
+        egui::Grid::new("labels").num_columns(2).show(ui, |ui| {
+            ui.label("Assets");
+            ui.text_edit_singleline(&mut self.assets)
+                .on_hover_text("e.g. 000-000--000-010,000-015");
+            ui.end_row();
+            ui.label("Output");
+            ui.text_edit_singleline(&mut self.output);
+            ui.end_row();
+        });
+
+        ui.separator();
+        ui.heading("Layout");
+        let layout = &mut self.layout;
+        egui::Grid::new("layout").num_columns(2).show(ui, |ui| {
+            for (label, value) in [
+                ("Page width (mm)", &mut layout.page_width_mm),
+                ("Page height (mm)", &mut layout.page_height_mm),
+                ("Top margin (mm)", &mut layout.page_margin_top_mm),
+                ("Left margin (mm)", &mut layout.page_margin_left_mm),
+                ("Bottom margin (mm)", &mut layout.page_margin_bottom_mm),
+                ("Right margin (mm)", &mut layout.page_margin_right_mm),
+                ("Row spacing (mm)", &mut layout.grid_row_spacing_mm),
+                ("Column spacing (mm)", &mut layout.grid_col_spacing_mm),
+            ] {
+                ui.label(label);
+                ui.add(egui::DragValue::new(value).speed(0.1).range(0.0..=1000.0));
+                ui.end_row();
+            }
+            for (label, value) in [
+                ("Rows", &mut layout.grid_rows),
+                ("Columns", &mut layout.grid_columns),
+                ("Skip cells", &mut layout.grid_skip),
+            ] {
+                ui.label(label);
+                ui.add(egui::DragValue::new(value).range(0..=1000));
+                ui.end_row();
+            }
+        });
+
+        ui.separator();
+        let running = matches!(*self.status.lock().unwrap(), Status::Running);
+        if ui
+            .add_enabled(!running, egui::Button::new("Generate"))
+            .clicked()
+        {
+            self.generate(ui.ctx());
+        }
+        match &*self.status.lock().unwrap() {
+            Status::Idle => (),
+            Status::Running => {
+                ui.spinner();
+            }
+            Status::Done(message) => {
+                ui.label(message);
+            }
+            Status::Failed(message) => {
+                ui.colored_label(ui.visuals().error_fg_color, message);
+            }
+        }
+    }
+
+    /// Draw the first page of the sheet, showing which cells will be
+    /// skipped and which will receive labels
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "sizes are small and only used for drawing"
+    )]
+    fn page_preview(&self, ui: &mut egui::Ui) {
+        let Ok(config) = self.layout.build() else {
+            ui.label("The layout is invalid.");
+            return;
+        };
+        let labels = crate::resolve_assets(&self.assets).map_or(0, |ids| ids.len());
+        ui.label(format!(
+            "{labels} labels over {} pages",
+            config.page_count(labels)
+        ));
+
+        let config = config.unrolled(labels);
+        let (page_width, page_height) = config.page_size_mm();
+        let available = ui.available_size();
+        let scale = (available.x / page_width as f32).min(available.y / page_height as f32);
+        let mm = |v: f64| v as f32 * scale;
+        let (response, painter) = ui.allocate_painter(
+            egui::vec2(mm(page_width), mm(page_height)),
+            egui::Sense::hover(),
+        );
+        let page = response.rect;
+        painter.rect_filled(page, 0.0, egui::Color32::WHITE);
+
+        let (cell_width, cell_height) = config.cell_size_mm();
+        let skip = config.grid_skip();
+        let used = config.cells_used(labels);
+        let first_page = skip / config.num_per_page() * config.num_per_page();
+        for idx in 0..config.num_per_page() {
+            let position = first_page + idx;
+            let (x, y) = config.cell_origin_mm(config.cell(position));
+            let cell = egui::Rect::from_min_size(
+                page.min + egui::vec2(mm(x), mm(y)),
+                egui::vec2(mm(cell_width), mm(cell_height)),
+            );
+
+            let fill = if config.is_skipped(position) {
+                egui::Color32::LIGHT_GRAY
+            } else if position < used {
+                egui::Color32::LIGHT_BLUE
+            } else {
+                egui::Color32::WHITE
+            };
+            painter.rect_filled(cell, 0.0, fill);
+            painter.rect_stroke(
+                cell,
+                0.0,
+                egui::Stroke::new(1.0, egui::Color32::GRAY),
+                egui::StrokeKind::Inside,
+            );
+        }
+    }
+}
+
+impl eframe::App for App {
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        egui::Panel::left("controls")
+            .resizable(false)
+            .show(ui, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| self.controls(ui));
+            });
+        egui::CentralPanel::default_margins().show(ui, |ui| self.page_preview(ui));
+    }
+}
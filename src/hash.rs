@@ -0,0 +1,10 @@
+//! Hashing that gives the same result on every run, for names and colors
+//! that must not change between runs.
+
+/// Hash bytes with FNV-1a, which unlike the standard library's hasher is
+/// stable between runs and versions
+pub fn stable(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,188 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, anyhow};
+use chrono::{DateTime, Local, Utc};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+
+use homebox_label_maker::{
+    asset_list::AssetId,
+    i18n::Lang,
+    layout::Layout,
+    render::{Registry, Sheet},
+};
+
+use crate::{ConnectionArgs, failure::Failure};
+
+#[derive(clap::Args)]
+pub struct HistoryArgs {
+    #[command(subcommand)]
+    command: HistoryCommand,
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// List the recorded batches, oldest first
+    List,
+}
+
+#[derive(clap::Args)]
+pub struct ReprintArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// The ID of the batch to print again, as shown by `history list`
+    #[arg(index = 1)]
+    batch: u64,
+
+    /// The file path to output the result to
+    #[arg(index = 2)]
+    output_html: PathBuf,
+
+    /// The format to write the output in. Defaults to the format of the
+    /// output file's extension, or else HTML.
+    #[arg(
+        long,
+        value_parser = clap::builder::PossibleValuesParser::new(Registry::self_contained().names()),
+    )]
+    format: Option<String>,
+
+    /// Submit the output to the system print queue
+    #[arg(long)]
+    print: bool,
+
+    /// The printer to print to, rather than the system default
+    #[arg(long, requires = "print")]
+    printer: Option<String>,
+}
+
+/// A generated sheet, recorded so it can be printed again
+#[derive(Serialize, Deserialize)]
+pub struct Batch {
+    id: u64,
+    timestamp: DateTime<Utc>,
+    server: String,
+    /// The assets, as an asset list
+    assets: String,
+    layout: Layout,
+}
+
+impl Batch {
+    fn dir() -> anyhow::Result<PathBuf> {
+        Ok(dirs::data_dir()
+            .context("Failed to find data directory")?
+            .join(env!("CARGO_PKG_NAME"))
+            .join("history"))
+    }
+
+    /// Every recorded batch, oldest first
+    fn all() -> anyhow::Result<Vec<Self>> {
+        let dir = Self::dir()?;
+        if !fs::exists(&dir).context("Failed to check if history exists")? {
+            return Ok(vec![]);
+        }
+        let mut batches = vec![];
+        for entry in fs::read_dir(dir).context("Failed to read history")? {
+            let path = entry.context("Failed to read history")?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "toml")
+            {
+                let batch = fs::read_to_string(&path).context("Failed to read batch")?;
+                match toml::from_str(&batch) {
+                    Ok(batch) => batches.push(batch),
+                    Err(e) => tracing::warn!("Ignoring invalid batch {}: {e}", path.display()),
+                }
+            }
+        }
+        batches.sort_by_key(|batch: &Self| batch.id);
+        Ok(batches)
+    }
+
+    /// Record a batch of labels printed with the layout, giving it the
+    /// next ID
+    pub fn record(server: &str, asset_ids: &[AssetId], layout: Layout) -> anyhow::Result<()> {
+        let id = Self::all()?.last().map_or(1, |batch| batch.id + 1);
+        let batch = Self {
+            id,
+            timestamp: Utc::now(),
+            server: server.to_string(),
+            assets: asset_ids
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(","),
+            layout,
+        };
+        let dir = Self::dir()?;
+        fs::create_dir_all(&dir).context("Failed to create history directory")?;
+        fs::write(dir.join(format!("{id}.toml")), toml::to_string(&batch)?)
+            .context("Failed to write batch")?;
+        tracing::info!("Recorded as batch {id}");
+        Ok(())
+    }
+}
+
+/// Show the recorded batches
+pub fn run(args: &HistoryArgs) -> anyhow::Result<()> {
+    match args.command {
+        HistoryCommand::List => {
+            for batch in Batch::all()? {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    batch.id,
+                    batch
+                        .timestamp
+                        .with_timezone(&Local)
+                        .format("%Y-%m-%d %H:%M"),
+                    batch.server,
+                    batch.assets
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Generate the sheet for a recorded batch again, with the same assets
+/// and layout
+pub fn reprint(args: &ReprintArgs, lang: Lang) -> anyhow::Result<()> {
+    let format = crate::single_file_format(args.format.as_deref(), &args.output_html)?;
+    crate::ensure_output_free(&args.output_html)?;
+    let batch = Batch::all()?
+        .into_iter()
+        .find(|batch| batch.id == args.batch)
+        .ok_or_else(|| anyhow!("No batch with ID {} in the history!", args.batch))?;
+    if batch.server.trim_end_matches('/') != args.connection.server.trim_end_matches('/') {
+        tracing::warn!(
+            "Batch {} was printed from {}, not {}",
+            batch.id,
+            batch.server,
+            args.connection.server
+        );
+    }
+    let config = batch.layout.build().context(Failure::Layout)?;
+    let asset_ids = crate::parse_assets(&batch.assets)?;
+
+    let client = crate::authenticate(&args.connection)?;
+    let labels = crate::fetch_labels(&client, &asset_ids, None)?;
+    let sheet = Sheet {
+        config: &config,
+        labels: &labels,
+        descriptions: &crate::describe_assets(&asset_ids, lang),
+        lang,
+    };
+    let bytes_written = crate::render_to_file(
+        &Registry::self_contained(),
+        format,
+        &sheet,
+        &args.output_html,
+    )?;
+    crate::print_plan(&config, labels.len(), lang);
+    println!("{}", lang.wrote(bytes_written, &args.output_html));
+
+    if args.print {
+        crate::print::submit(&args.output_html, 1, args.printer.as_deref())?;
+    }
+    Ok(())
+}
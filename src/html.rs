@@ -0,0 +1,890 @@
+use std::{collections::HashMap, convert::Infallible, fmt::Write as _, io};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use build_html::{
+    Html, HtmlChild, HtmlContainer, HtmlElement, HtmlPage, HtmlTag, Table, escape_html,
+};
+
+use crate::{
+    asset_list::AssetId,
+    i18n::Lang,
+    image::{self, ImageType},
+    layout::{Cell, LabelSlot, SheetConfig},
+    render::Sheet,
+};
+
+/// Options for the HTML output, beyond the layout of the sheet
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Open the print dialog as soon as the page is opened
+    pub auto_print: bool,
+    /// Link to the label images in this directory, relative to the page,
+    /// rather than embedding them. Each image must be saved there under
+    /// its [`image_file_name`].
+    pub assets_dir: Option<String>,
+    /// Text printed in the top margin of each page, with `{page}` and
+    /// `{pages}` replaced by the page number and the number of pages
+    pub header: Option<String>,
+    /// Text printed in the bottom margin of each page, with the same
+    /// replacements as the header
+    pub footer: Option<String>,
+    /// The labels to list on a contents page before the labels, if any
+    pub contents: Vec<IndexEntry>,
+    /// Where each label sits in its cell when they are different shapes
+    pub align: Align,
+    /// Details of the document, to find it by when archived
+    pub metadata: Metadata,
+    /// The bookmarks for the outline of a PDF printed from the page
+    pub outline: Outline,
+    /// The items to print on the back of each label, in the same order,
+    /// on a mirrored page after each page for duplex printing. No backs
+    /// are printed if empty.
+    pub backs: Vec<IndexEntry>,
+    /// Tile this many pages across and down each printed sheet, with
+    /// cut lines between them, to print small pages on larger paper
+    pub n_up: Option<(usize, usize)>,
+    /// The color to tint each label with, in the same order, as a CSS
+    /// color. Labels without one are left as they are.
+    pub colors: Vec<Option<String>>,
+    /// Whether the colors tint the border or background of the labels
+    pub tint: Tint,
+    /// The colors to mark each label with a dot of, in the same order,
+    /// such as those of its item's tags
+    pub dots: Vec<Vec<String>>,
+}
+
+/// How a label is tinted with its color
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Tint {
+    /// A thick border around the label
+    #[default]
+    Border,
+    /// The background of the label, showing through its white parts
+    Background,
+}
+
+impl Tint {
+    /// The class of a cell tinted this way
+    fn class(self) -> &'static str {
+        match self {
+            Self::Border => "tint-border",
+            Self::Background => "tint-background",
+        }
+    }
+}
+
+/// The colors CSS knows by name
+const COLOR_NAMES: [&str; 148] = [
+    "aliceblue",
+    "antiquewhite",
+    "aqua",
+    "aquamarine",
+    "azure",
+    "beige",
+    "bisque",
+    "black",
+    "blanchedalmond",
+    "blue",
+    "blueviolet",
+    "brown",
+    "burlywood",
+    "cadetblue",
+    "chartreuse",
+    "chocolate",
+    "coral",
+    "cornflowerblue",
+    "cornsilk",
+    "crimson",
+    "cyan",
+    "darkblue",
+    "darkcyan",
+    "darkgoldenrod",
+    "darkgray",
+    "darkgreen",
+    "darkgrey",
+    "darkkhaki",
+    "darkmagenta",
+    "darkolivegreen",
+    "darkorange",
+    "darkorchid",
+    "darkred",
+    "darksalmon",
+    "darkseagreen",
+    "darkslateblue",
+    "darkslategray",
+    "darkslategrey",
+    "darkturquoise",
+    "darkviolet",
+    "deeppink",
+    "deepskyblue",
+    "dimgray",
+    "dimgrey",
+    "dodgerblue",
+    "firebrick",
+    "floralwhite",
+    "forestgreen",
+    "fuchsia",
+    "gainsboro",
+    "ghostwhite",
+    "gold",
+    "goldenrod",
+    "gray",
+    "green",
+    "greenyellow",
+    "grey",
+    "honeydew",
+    "hotpink",
+    "indianred",
+    "indigo",
+    "ivory",
+    "khaki",
+    "lavender",
+    "lavenderblush",
+    "lawngreen",
+    "lemonchiffon",
+    "lightblue",
+    "lightcoral",
+    "lightcyan",
+    "lightgoldenrodyellow",
+    "lightgray",
+    "lightgreen",
+    "lightgrey",
+    "lightpink",
+    "lightsalmon",
+    "lightseagreen",
+    "lightskyblue",
+    "lightslategray",
+    "lightslategrey",
+    "lightsteelblue",
+    "lightyellow",
+    "lime",
+    "limegreen",
+    "linen",
+    "magenta",
+    "maroon",
+    "mediumaquamarine",
+    "mediumblue",
+    "mediumorchid",
+    "mediumpurple",
+    "mediumseagreen",
+    "mediumslateblue",
+    "mediumspringgreen",
+    "mediumturquoise",
+    "mediumvioletred",
+    "midnightblue",
+    "mintcream",
+    "mistyrose",
+    "moccasin",
+    "navajowhite",
+    "navy",
+    "oldlace",
+    "olive",
+    "olivedrab",
+    "orange",
+    "orangered",
+    "orchid",
+    "palegoldenrod",
+    "palegreen",
+    "paleturquoise",
+    "palevioletred",
+    "papayawhip",
+    "peachpuff",
+    "peru",
+    "pink",
+    "plum",
+    "powderblue",
+    "purple",
+    "rebeccapurple",
+    "red",
+    "rosybrown",
+    "royalblue",
+    "saddlebrown",
+    "salmon",
+    "sandybrown",
+    "seagreen",
+    "seashell",
+    "sienna",
+    "silver",
+    "skyblue",
+    "slateblue",
+    "slategray",
+    "slategrey",
+    "snow",
+    "springgreen",
+    "steelblue",
+    "tan",
+    "teal",
+    "thistle",
+    "tomato",
+    "turquoise",
+    "violet",
+    "wheat",
+    "white",
+    "whitesmoke",
+    "yellow",
+    "yellowgreen",
+];
+
+/// Whether a color can be written into the page's styles as it is,
+/// being in hex, e.g. `#d33` or `#dd3333`, or a CSS color name. Anything
+/// else could carry other declarations, such as ones fetching URLs.
+pub fn is_safe_color(color: &str) -> bool {
+    let hex = color.strip_prefix('#').is_some_and(|hex| {
+        [3, 6].contains(&hex.len()) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    });
+    hex || COLOR_NAMES.contains(&color.to_ascii_lowercase().as_str())
+}
+
+/// The color if it is safe to write into the page, or else none, with a
+/// warning
+fn checked_color(color: &str) -> Option<&str> {
+    if is_safe_color(color) {
+        Some(color)
+    } else {
+        tracing::warn!("Leaving out the invalid color {color:?}");
+        None
+    }
+}
+
+/// The bookmarks to add to the outline of a PDF printed from the page,
+/// as hidden headings that converters such as wkhtmltopdf list in it
+#[derive(Clone, Debug, Default)]
+pub enum Outline {
+    #[default]
+    None,
+    /// A bookmark for each page
+    Pages,
+    /// A bookmark for each group, by the index of the label starting
+    /// it and its name
+    Groups(Vec<(usize, String)>),
+}
+
+/// Details of a document, written in its head. Browsers and converters
+/// such as wkhtmltopdf take the title for the PDF when printing to one.
+#[derive(Clone, Debug, Default)]
+pub struct Metadata {
+    /// The title, rather than the default for the language
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// When the document was created, as a date or timestamp
+    pub created: Option<String>,
+    pub keywords: Vec<String>,
+}
+
+/// Where a label sits in its cell, when it is shrunk to fit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Align {
+    #[default]
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Align {
+    /// The CSS `object-position` placing an image this way
+    fn object_position(self) -> &'static str {
+        match self {
+            Self::Center => "center",
+            Self::Top => "top",
+            Self::Bottom => "bottom",
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::TopLeft => "left top",
+            Self::TopRight => "right top",
+            Self::BottomLeft => "left bottom",
+            Self::BottomRight => "right bottom",
+        }
+    }
+
+    /// The SVG `preserveAspectRatio` placing an image this way
+    fn preserve_aspect_ratio(self) -> &'static str {
+        match self {
+            Self::Center => "xMidYMid",
+            Self::Top => "xMidYMin",
+            Self::Bottom => "xMidYMax",
+            Self::Left => "xMinYMid",
+            Self::Right => "xMaxYMid",
+            Self::TopLeft => "xMinYMin",
+            Self::TopRight => "xMaxYMin",
+            Self::BottomLeft => "xMinYMax",
+            Self::BottomRight => "xMaxYMax",
+        }
+    }
+}
+
+/// The name of the file to save a label image as when linking to it
+/// rather than embedding it, which is the same for identical images
+pub fn image_file_name(label: &[u8]) -> String {
+    let hash = crate::hash::stable(label);
+    let extension = ImageType::detect(label).map_or("png", ImageType::extension);
+    format!("{hash:016x}.{extension}")
+}
+
+/// Generate the HTML itself
+pub fn generate(sheet: &Sheet<'_>, options: &Options) -> HtmlPage {
+    let config = sheet.config.unrolled(sheet.labels.len());
+    let sheet = &Sheet {
+        config: &config,
+        ..*sheet
+    };
+    let shared = shared_images(sheet.labels, options);
+    let mut page = document(sheet.config, sheet.lang, options, &shared);
+    let mut main = HtmlElement::new(HtmlTag::Main);
+    if !options.contents.is_empty() {
+        main.add_child(contents_page(&options.contents, sheet.lang).into());
+    }
+    for_each_sheet(sheet, options, &shared, |page_div| {
+        main.add_child(page_div.into());
+        Ok(())
+    })
+    .unwrap_or_else(|e: Infallible| match e {});
+    page.add_html(main.with_raw(""));
+    page
+}
+
+/// Write the HTML a page at a time, so only one page of labels is
+/// encoded in memory at once
+///
+/// # Errors
+///
+/// Fails if the output cannot be written.
+pub fn write(sheet: &Sheet<'_>, options: &Options, out: &mut dyn io::Write) -> io::Result<()> {
+    let config = sheet.config.unrolled(sheet.labels.len());
+    let sheet = &Sheet {
+        config: &config,
+        ..*sheet
+    };
+    // Write the surrounding document around the pages, as they are
+    // produced
+    let shared = shared_images(sheet.labels, options);
+    let document = document(sheet.config, sheet.lang, options, &shared).to_html_string();
+    let (start, end) = document
+        .rsplit_once(DOCUMENT_END)
+        .unwrap_or((&document, ""));
+    out.write_all(start.as_bytes())?;
+    out.write_all(b"<main>")?;
+    if !options.contents.is_empty() {
+        out.write_all(
+            contents_page(&options.contents, sheet.lang)
+                .to_html_string()
+                .as_bytes(),
+        )?;
+    }
+    for_each_sheet(sheet, options, &shared, |page_div| {
+        out.write_all(page_div.to_html_string().as_bytes())
+    })?;
+    out.write_all(b"</main>")?;
+    out.write_all(DOCUMENT_END.as_bytes())?;
+    out.write_all(end.as_bytes())
+}
+
+/// The end of the body of a document, before which pages are placed
+const DOCUMENT_END: &str = "</body>";
+
+/// The document holding the pages, with the styles and notice
+fn document(
+    config: &SheetConfig,
+    lang: Lang,
+    options: &Options,
+    shared: &HashMap<&[u8], usize>,
+) -> HtmlPage {
+    let metadata = &options.metadata;
+    let mut page = HtmlPage::new()
+        .with_meta([("charset", "utf-8")])
+        .with_title(escape_html(
+            metadata.title.as_deref().unwrap_or(lang.title()),
+        ))
+        .with_style(config.configurable_style())
+        .with_style(include_str!("style.css"));
+    let keywords = metadata.keywords.join(", ");
+    for (name, content) in [
+        ("author", metadata.author.as_deref()),
+        ("dcterms.created", metadata.created.as_deref()),
+        (
+            "keywords",
+            Some(keywords.as_str()).filter(|k| !k.is_empty()),
+        ),
+    ] {
+        if let Some(content) = content {
+            page.add_meta([("name", name), ("content", &escape_html(content))]);
+        }
+    }
+    if !shared.is_empty() {
+        let mut shared: Vec<_> = shared.iter().collect();
+        shared.sort_by_key(|(_, class)| **class);
+        let mut symbols = String::from(r#"<svg class="shared-labels" aria-hidden="true">"#);
+        for (label, class) in shared {
+            let Some((width, height)) = image::dimensions(label) else {
+                continue;
+            };
+            let _ = write!(
+                symbols,
+                r#"<symbol id="shared-label-{class}" viewBox="0 0 {width} {height}" preserveAspectRatio="{}"><image href="{}" width="{width}" height="{height}"/></symbol>"#,
+                options.align.preserve_aspect_ratio(),
+                data_url(label)
+            );
+        }
+        symbols.push_str("</svg>");
+        page.add_raw(symbols);
+    }
+    if let Some((columns, rows)) = options.n_up {
+        // Later rules take precedence, so this replaces the page size
+        #[allow(
+            clippy::cast_precision_loss,
+            reason = "there are far fewer than 2^52 pages across or down a sheet"
+        )]
+        let (width, height) = {
+            let (width, height) = config.page_size_mm();
+            (width * columns as f64, height * rows as f64)
+        };
+        page.add_style(format!(
+            "@page {{ size: {width}mm {height}mm; }}
+            .sheet {{ grid-template-columns: repeat({columns}, auto); }}"
+        ));
+    }
+    if options.align != Align::Center {
+        page.add_style(format!(
+            ".page > div > img {{ object-position: {} }}",
+            options.align.object_position()
+        ));
+    }
+    page.add_paragraph_attr(escape_html(lang.notice()), [("class", "no-print")]);
+    if options.auto_print {
+        page.add_script_literal(r#"window.addEventListener("load", () => window.print());"#);
+        page.add_raw(format!(
+            r#"<button class="no-print" onclick="window.print()">{}</button>"#,
+            escape_html(lang.print())
+        ));
+    }
+    page
+}
+
+/// The labels that appear more than once, such as copies of the same
+/// asset, each with the number of the SVG symbol that embeds the image
+/// once. Linked images are already shared, so none are embedded, and
+/// images whose size cannot be read are embedded with each label.
+fn shared_images<'a>(labels: &'a [bytes::Bytes], options: &Options) -> HashMap<&'a [u8], usize> {
+    if options.assets_dir.is_some() {
+        return HashMap::new();
+    }
+    let mut counts: HashMap<&[u8], usize> = HashMap::new();
+    for label in labels {
+        *counts.entry(label).or_default() += 1;
+    }
+    let mut shared = HashMap::new();
+    for label in labels {
+        if counts[label.as_ref()] > 1
+            && !shared.contains_key(label.as_ref())
+            && image::dimensions(label).is_some()
+        {
+            shared.insert(label.as_ref(), shared.len());
+        }
+    }
+    shared
+}
+
+/// The data URL for a label, with the image embedded
+fn data_url(label: &[u8]) -> String {
+    format!(
+        "data:{};base64,{}",
+        mime_type(label),
+        BASE64_STANDARD.encode(label)
+    )
+}
+
+/// Build the element for each page of labels in turn
+fn for_each_page<E>(
+    sheet: &Sheet<'_>,
+    options: &Options,
+    shared: &HashMap<&[u8], usize>,
+    mut f: impl FnMut(HtmlElement) -> Result<(), E>,
+) -> Result<(), E> {
+    let pages = sheet.config.page_count(sheet.labels.len());
+    // Each page is followed by its back, if printing them
+    let mut finish = |(page, page_div)| {
+        f(page_div)?;
+        if options.backs.is_empty() {
+            Ok(())
+        } else {
+            f(back_page(sheet, &options.backs, page))
+        }
+    };
+    let mut current: Option<(usize, HtmlElement)> = None;
+    for (cell, slot) in sheet.config.assignments(sheet.labels.len()) {
+        if current.as_ref().is_none_or(|(page, _)| *page != cell.page) {
+            if let Some(previous) = current.take() {
+                finish(previous)?;
+            }
+            let mut page_div = HtmlElement::new(HtmlTag::Div).with_attribute("class", "page");
+            for (tag, text) in [
+                (HtmlTag::Header, &options.header),
+                (HtmlTag::Footer, &options.footer),
+            ] {
+                if let Some(text) = text {
+                    let text = text
+                        .replace("{page}", &cell.page.to_string())
+                        .replace("{pages}", &pages.to_string());
+                    page_div.add_child(
+                        HtmlElement::new(tag)
+                            .with_child(escape_html(&text).into())
+                            .into(),
+                    );
+                }
+            }
+            if let Outline::Pages = options.outline {
+                page_div.add_child(bookmark(&sheet.lang.page_bookmark(cell.page)).into());
+            }
+            current = Some((cell.page, page_div));
+        }
+
+        if let (LabelSlot::Label(idx), Outline::Groups(groups), Some((_, page_div))) =
+            (slot, &options.outline, &mut current)
+            && let Some((_, name)) = groups.iter().find(|(start, _)| *start == idx)
+        {
+            page_div.add_child(bookmark(name).into());
+        }
+
+        let mut div = HtmlElement::new(HtmlTag::Div);
+        if let LabelSlot::Label(idx) = slot {
+            if let Some(color) = options
+                .colors
+                .get(idx)
+                .and_then(Option::as_deref)
+                .and_then(checked_color)
+            {
+                div = div
+                    .with_attribute("class", options.tint.class())
+                    .with_attribute("style", escape_html(&format!("--tint: {color}")));
+            }
+            let label = &sheet.labels[idx];
+            let alt = sheet
+                .descriptions
+                .get(idx)
+                .cloned()
+                .unwrap_or_else(|| sheet.lang.numbered_label(idx + 1));
+            // Repeated images are embedded once, as symbols that each
+            // label showing them uses
+            let img: HtmlChild = match (&options.assets_dir, shared.get(label.as_ref())) {
+                (Some(dir), _) => HtmlElement::new(HtmlTag::Image)
+                    .with_attribute(
+                        "src",
+                        escape_html(&format!("{dir}/{}", image_file_name(label))),
+                    )
+                    .with_attribute("alt", escape_html(&alt))
+                    .into(),
+                (None, Some(class)) => format!(
+                    r##"<svg class="shared-label" role="img" aria-label="{}"><use href="#shared-label-{class}" width="100%" height="100%"/></svg>"##,
+                    escape_html(&alt)
+                )
+                .into(),
+                (None, None) => HtmlElement::new(HtmlTag::Image)
+                    .with_attribute("src", data_url(label))
+                    .with_attribute("alt", escape_html(&alt))
+                    .into(),
+            };
+            div.add_child(img);
+            if let Some(colors) = options.dots.get(idx).filter(|colors| !colors.is_empty()) {
+                div.add_child(dots(colors).into());
+            }
+        } else {
+            div.add_child("".into());
+        }
+        if let Some((_, page_div)) = &mut current {
+            page_div.add_child(div.into());
+        }
+    }
+    if let Some(last) = current {
+        finish(last)?;
+    }
+    Ok(())
+}
+
+/// The back of a page, mirrored so each cell is behind its label when
+/// printed on both sides, with the name and location of each item
+fn back_page(sheet: &Sheet<'_>, backs: &[IndexEntry], page: usize) -> HtmlElement {
+    let mut page_div = HtmlElement::new(HtmlTag::Div).with_attribute("class", "page back");
+    for (_, slot) in sheet
+        .config
+        .assignments(sheet.labels.len())
+        .filter(|(cell, _)| cell.page == page)
+    {
+        let mut div = HtmlElement::new(HtmlTag::Div);
+        match slot {
+            LabelSlot::Label(idx) if let Some(entry) = backs.get(idx) => {
+                div.add_child(
+                    HtmlElement::new(HtmlTag::Strong)
+                        .with_child(escape_html(&entry.name).into())
+                        .into(),
+                );
+                if let Some(location) = &entry.location {
+                    div.add_child(
+                        HtmlElement::new(HtmlTag::Span)
+                            .with_child(escape_html(location).into())
+                            .into(),
+                    );
+                }
+            }
+            _ => div.add_child("".into()),
+        }
+        page_div.add_child(div.into());
+    }
+    page_div
+}
+
+/// Call the function with each printed sheet: each page, or with n-up,
+/// a sheet with several pages tiled on it
+fn for_each_sheet<E>(
+    sheet: &Sheet<'_>,
+    options: &Options,
+    shared: &HashMap<&[u8], usize>,
+    mut f: impl FnMut(HtmlElement) -> Result<(), E>,
+) -> Result<(), E> {
+    let Some((columns, rows)) = options.n_up else {
+        return for_each_page(sheet, options, shared, f);
+    };
+    let mut current: Option<HtmlElement> = None;
+    let mut pages = 0;
+    for_each_page(sheet, options, shared, |page_div| {
+        current
+            .get_or_insert_with(|| HtmlElement::new(HtmlTag::Div).with_attribute("class", "sheet"))
+            .add_child(page_div.into());
+        pages += 1;
+        match current.take_if(|_| pages % (columns * rows) == 0) {
+            Some(full) => f(full),
+            None => Ok(()),
+        }
+    })?;
+    current.map_or(Ok(()), f)
+}
+
+/// A row of colored dots in the corner of a label
+fn dots(colors: &[String]) -> HtmlElement {
+    let mut dots = HtmlElement::new(HtmlTag::Div).with_attribute("class", "dots");
+    for color in colors.iter().filter_map(|color| checked_color(color)) {
+        dots.add_child(
+            HtmlElement::new(HtmlTag::Span)
+                .with_attribute("style", escape_html(&format!("--dot: {color}")))
+                .with_child("".into())
+                .into(),
+        );
+    }
+    dots
+}
+
+/// A heading for the outline of a PDF, hidden from the page itself
+fn bookmark(text: &str) -> HtmlElement {
+    HtmlElement::new(HtmlTag::Heading1)
+        .with_attribute("class", "bookmark")
+        .with_child(escape_html(text).into())
+}
+
+/// A row of the index of printed labels
+#[derive(Clone, Debug)]
+pub struct IndexEntry {
+    pub asset_id: AssetId,
+    pub name: String,
+    pub location: Option<String>,
+    /// The cell the label is placed in, if known
+    pub cell: Option<Cell>,
+    /// The colors of the item's labels (tags), for those with one
+    pub tag_colors: Vec<String>,
+}
+
+/// Generate an index of the printed labels, listing the asset ID, name
+/// and location of each, to keep alongside the sheets
+pub fn generate_index(entries: &[IndexEntry], lang: Lang) -> HtmlPage {
+    HtmlPage::new()
+        .with_title(escape_html(lang.index_title()))
+        .with_style(include_str!("style.css"))
+        .with_header(1, escape_html(lang.index_title()))
+        .with_table(index_table(entries, lang))
+}
+
+/// A table listing the asset ID, name and location of each label, and
+/// its cell if known
+fn index_table(entries: &[IndexEntry], lang: Lang) -> Table {
+    let with_cells = entries.iter().any(|entry| entry.cell.is_some());
+    let mut headings = lang.index_headings().to_vec();
+    if with_cells {
+        headings.push(lang.cell_heading());
+    }
+    let mut table = Table::new().with_header_row(headings.into_iter().map(escape_html));
+    for entry in entries {
+        let mut row = vec![
+            entry.asset_id.to_string(),
+            escape_html(&entry.name),
+            entry
+                .location
+                .as_deref()
+                .map(escape_html)
+                .unwrap_or_default(),
+        ];
+        if with_cells {
+            row.push(entry.cell.map(|cell| lang.cell(cell)).unwrap_or_default());
+        }
+        table.add_body_row(row);
+    }
+    table.with_attributes([("class", "index")])
+}
+
+/// A page listing the labels in the sheet and where each is placed, to
+/// print before them
+fn contents_page(entries: &[IndexEntry], lang: Lang) -> HtmlElement {
+    HtmlElement::new(HtmlTag::Div)
+        .with_attribute("class", "contents")
+        .with_child(
+            HtmlElement::new(HtmlTag::Heading1)
+                .with_child(escape_html(lang.contents_title()).into())
+                .into(),
+        )
+        .with_child(index_table(entries, lang).to_html_string().into())
+}
+
+/// The MIME type of a label image, recognised from its contents, or PNG
+/// as usually returned by the server if it is not recognised
+fn mime_type(label: &[u8]) -> &'static str {
+    ImageType::detect(label).map_or("image/png", ImageType::mime_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_labels_are_embedded_once() {
+        let config = crate::layout::Layout::default().build().unwrap();
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(526u32.to_be_bytes());
+        png.extend(200u32.to_be_bytes());
+        let labels = [
+            bytes::Bytes::from(png),
+            bytes::Bytes::from_static(b"GIF89a"),
+        ];
+        let labels = [&labels[..], &labels[..], &labels[..1]].concat();
+        let sheet = Sheet {
+            config: &config,
+            labels: &labels,
+            descriptions: &[],
+            lang: Lang::En,
+        };
+        let options = Options {
+            align: Align::TopLeft,
+            ..Options::default()
+        };
+        let mut out = vec![];
+        write(&sheet, &options, &mut out).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert_eq!(html.matches("data:image/png;base64").count(), 1);
+        assert!(html.contains(r#"viewBox="0 0 526 200" preserveAspectRatio="xMinYMin""#));
+        assert_eq!(html.matches(r##"<use href="#shared-label-0""##).count(), 3);
+        // Images of an unknown size are embedded with each label
+        assert_eq!(html.matches("<img").count(), 2);
+        assert!(html.contains("object-position: left top"));
+    }
+
+    #[test]
+    fn only_plain_colors_are_written() {
+        assert!(is_safe_color("#d33"));
+        assert!(is_safe_color("#DD3333"));
+        assert!(is_safe_color("SteelBlue"));
+        assert!(!is_safe_color("#dd33"));
+        assert!(!is_safe_color(
+            "red; background-image: url(https://example.com)"
+        ));
+        assert!(!is_safe_color("rgb(40 120 200)"));
+
+        let config = crate::layout::Layout::default().build().unwrap();
+        let labels = vec![bytes::Bytes::from_static(b"GIF89a"); 2];
+        let sheet = Sheet {
+            config: &config,
+            labels: &labels,
+            descriptions: &[],
+            lang: Lang::En,
+        };
+        let options = Options {
+            colors: vec![
+                Some("red; background: url(x)".to_string()),
+                Some("#d33".to_string()),
+            ],
+            ..Options::default()
+        };
+        let mut out = vec![];
+        write(&sheet, &options, &mut out).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(!html.contains("url(x)"));
+        assert_eq!(html.matches("--tint: #d33").count(), 1);
+    }
+
+    #[test]
+    fn invalid_tag_colors_are_left_out() {
+        let config = crate::layout::Layout::default().build().unwrap();
+        let labels = [bytes::Bytes::from_static(b"GIF89a")];
+        let sheet = Sheet {
+            config: &config,
+            labels: &labels,
+            descriptions: &[],
+            lang: Lang::En,
+        };
+        let options = Options {
+            dots: vec![vec![
+                "#e53935".to_string(),
+                "red; background: url(x)".to_string(),
+            ]],
+            ..Options::default()
+        };
+        let mut out = vec![];
+        write(&sheet, &options, &mut out).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(html.contains("--dot: #e53935"));
+        assert!(!html.contains("url(x)"));
+    }
+
+    #[test]
+    fn svg_labels_are_embedded_as_images() {
+        // Scripts in an SVG do not run when it is shown as an image
+        let svg = r#"<svg/onload="alert(1)" viewBox="0 0 4 2"><style>*{}</style></svg>"#;
+        let labels = [bytes::Bytes::from_static(svg.as_bytes())];
+        let config = crate::layout::Layout::default().build().unwrap();
+        let sheet = Sheet {
+            config: &config,
+            labels: &labels,
+            descriptions: &["Shelf on top".to_string()],
+            lang: Lang::En,
+        };
+        let mut out = vec![];
+        write(&sheet, &Options::default(), &mut out).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(!html.contains("onload"));
+        assert!(!html.contains("<style>*"));
+        assert!(html.contains(r#"<img src="data:image/svg+xml;base64,"#));
+        assert!(html.contains(r#"alt="Shelf on top""#));
+    }
+
+    #[test]
+    fn index_text_is_escaped() {
+        let entries = [IndexEntry {
+            asset_id: crate::asset_list::parse("000-001")
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .next()
+                .unwrap(),
+            name: "<script>alert(1)</script>".to_string(),
+            location: Some("Tom's \"Shed\"".to_string()),
+            cell: None,
+            tag_colors: vec![],
+        }];
+        let html = generate_index(&entries, Lang::En).to_html_string();
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(html.contains("Tom&#39;s &quot;Shed&quot;"));
+    }
+}
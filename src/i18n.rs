@@ -0,0 +1,412 @@
+use std::{env, path::Path};
+
+use crate::{asset_list::AssetId, layout::Cell};
+
+/// The languages that output and messages can be shown in
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+    Fr,
+    Es,
+}
+
+impl Lang {
+    /// Pick the language from the locale environment variables, falling
+    /// back to English
+    pub fn detect() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|locale| Self::from_locale(&locale))
+            .unwrap_or_default()
+    }
+
+    /// The language for a locale or language code, such as `de_DE.UTF-8`
+    /// or `fr`, if it is supported
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        match locale.get(..2)? {
+            "en" => Some(Self::En),
+            "de" => Some(Self::De),
+            "fr" => Some(Self::Fr),
+            "es" => Some(Self::Es),
+            _ => None,
+        }
+    }
+
+    /// The title of the output document
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::En => "Homebox Labels",
+            Self::De => "Homebox-Etiketten",
+            Self::Fr => "Étiquettes Homebox",
+            Self::Es => "Etiquetas de Homebox",
+        }
+    }
+
+    /// The caption of the button that opens the print dialog
+    pub fn print(self) -> &'static str {
+        match self {
+            Self::En => "Print",
+            Self::De => "Drucken",
+            Self::Fr => "Imprimer",
+            Self::Es => "Imprimir",
+        }
+    }
+
+    /// The printing instructions shown at the top of the output
+    pub fn notice(self) -> &'static str {
+        match self {
+            Self::En => include_str!("i18n/notice.en.txt"),
+            Self::De => include_str!("i18n/notice.de.txt"),
+            Self::Fr => include_str!("i18n/notice.fr.txt"),
+            Self::Es => include_str!("i18n/notice.es.txt"),
+        }
+    }
+
+    /// The title of the index of printed labels
+    pub fn index_title(self) -> &'static str {
+        match self {
+            Self::En => "Label Index",
+            Self::De => "Etikettenverzeichnis",
+            Self::Fr => "Index des étiquettes",
+            Self::Es => "Índice de etiquetas",
+        }
+    }
+
+    /// The column headings of the index of printed labels
+    pub fn index_headings(self) -> [&'static str; 3] {
+        match self {
+            Self::En => ["Asset ID", "Name", "Location"],
+            Self::De => ["Asset-ID", "Name", "Standort"],
+            Self::Fr => ["Identifiant", "Nom", "Emplacement"],
+            Self::Es => ["ID de activo", "Nombre", "Ubicación"],
+        }
+    }
+
+    /// The group for items without a location
+    pub fn no_location(self) -> &'static str {
+        match self {
+            Self::En => "No location",
+            Self::De => "Kein Standort",
+            Self::Fr => "Sans emplacement",
+            Self::Es => "Sin ubicación",
+        }
+    }
+
+    /// The caption for the purchase date on a label
+    pub fn purchased(self) -> &'static str {
+        match self {
+            Self::En => "Purchased",
+            Self::De => "Gekauft",
+            Self::Fr => "Acheté",
+            Self::Es => "Comprado",
+        }
+    }
+
+    /// The title of the contents page printed before the labels
+    pub fn contents_title(self) -> &'static str {
+        match self {
+            Self::En => "Contents",
+            Self::De => "Inhalt",
+            Self::Fr => "Sommaire",
+            Self::Es => "Contenido",
+        }
+    }
+
+    /// The heading of the column giving the cell of each label
+    pub fn cell_heading(self) -> &'static str {
+        match self {
+            Self::En => "Cell",
+            Self::De => "Zelle",
+            Self::Fr => "Cellule",
+            Self::Es => "Celda",
+        }
+    }
+
+    /// Where a label is placed, in a table of labels
+    pub fn cell(self, Cell { page, row, column }: Cell) -> String {
+        match self {
+            Self::En => format!("Page {page}, row {row}, column {column}"),
+            Self::De => format!("Seite {page}, Zeile {row}, Spalte {column}"),
+            Self::Fr => format!("Page {page}, ligne {row}, colonne {column}"),
+            Self::Es => format!("Página {page}, fila {row}, columna {column}"),
+        }
+    }
+
+    /// The caption for the quantity of an item on a label
+    pub fn quantity(self) -> &'static str {
+        match self {
+            Self::En => "Qty",
+            Self::De => "Menge",
+            Self::Fr => "Qté",
+            Self::Es => "Cant.",
+        }
+    }
+
+    /// The caption for the manufacturer on a label
+    pub fn manufacturer(self) -> &'static str {
+        match self {
+            Self::En => "Make",
+            Self::De => "Hersteller",
+            Self::Fr => "Fabricant",
+            Self::Es => "Fabricante",
+        }
+    }
+
+    /// The caption for the model number on a label
+    pub fn model(self) -> &'static str {
+        match self {
+            Self::En => "Model",
+            Self::De => "Modell",
+            Self::Fr => "Modèle",
+            Self::Es => "Modelo",
+        }
+    }
+
+    /// The caption for the serial number on a label
+    pub fn serial(self) -> &'static str {
+        match self {
+            Self::En => "S/N",
+            Self::De => "Seriennr.",
+            Self::Fr => "N° de série",
+            Self::Es => "N.º de serie",
+        }
+    }
+
+    /// The caption for the warranty expiry on a label
+    pub fn warranty(self) -> &'static str {
+        match self {
+            Self::En => "Warranty",
+            Self::De | Self::Fr => "Garantie",
+            Self::Es => "Garantía",
+        }
+    }
+
+    /// Shown in place of the warranty expiry for a lifetime warranty
+    pub fn lifetime(self) -> &'static str {
+        match self {
+            Self::En => "lifetime",
+            Self::De => "lebenslang",
+            Self::Fr => "à vie",
+            Self::Es => "de por vida",
+        }
+    }
+
+    /// The alternative text for the label of an asset
+    pub fn asset_label(self, asset_id: impl std::fmt::Display) -> String {
+        match self {
+            Self::En => format!("Label for asset {asset_id}"),
+            Self::De => format!("Etikett für Asset {asset_id}"),
+            Self::Fr => format!("Étiquette de l'objet {asset_id}"),
+            Self::Es => format!("Etiqueta del activo {asset_id}"),
+        }
+    }
+
+    /// The alternative text for the label of a location
+    pub fn location_label(self, name: &str) -> String {
+        match self {
+            Self::En => format!("Label for location {name}"),
+            Self::De => format!("Etikett für Ort {name}"),
+            Self::Fr => format!("Étiquette de l'emplacement {name}"),
+            Self::Es => format!("Etiqueta de la ubicación {name}"),
+        }
+    }
+
+    /// The alternative text for the label of a tag
+    pub fn tag_label(self, name: &str) -> String {
+        match self {
+            Self::En => format!("Label for tag {name}"),
+            Self::De => format!("Etikett für Schlagwort {name}"),
+            Self::Fr => format!("Étiquette du libellé {name}"),
+            Self::Es => format!("Etiqueta de la etiqueta {name}"),
+        }
+    }
+
+    /// The alternative text for a label with nothing else to describe it,
+    /// counting from one
+    pub fn numbered_label(self, number: usize) -> String {
+        match self {
+            Self::En => format!("Label {number}"),
+            Self::De => format!("Etikett {number}"),
+            Self::Fr => format!("Étiquette {number}"),
+            Self::Es => format!("Etiqueta {number}"),
+        }
+    }
+
+    pub fn labels(self, count: usize) -> String {
+        match self {
+            Self::En => format!("Labels: {count}"),
+            Self::De => format!("Etiketten: {count}"),
+            Self::Fr => format!("Étiquettes : {count}"),
+            Self::Es => format!("Etiquetas: {count}"),
+        }
+    }
+
+    pub fn pages(self, count: usize) -> String {
+        match self {
+            Self::En => format!("Pages: {count}"),
+            Self::De => format!("Seiten: {count}"),
+            Self::Fr => format!("Pages : {count}"),
+            Self::Es => format!("Páginas: {count}"),
+        }
+    }
+
+    pub fn first_cell(
+        self,
+        Cell {
+            page,
+            row,
+            column: col,
+        }: Cell,
+    ) -> String {
+        match self {
+            Self::En => format!("First cell: page {page}, row {row}, column {col}"),
+            Self::De => format!("Erste Zelle: Seite {page}, Zeile {row}, Spalte {col}"),
+            Self::Fr => format!("Première cellule : page {page}, ligne {row}, colonne {col}"),
+            Self::Es => format!("Primera celda: página {page}, fila {row}, columna {col}"),
+        }
+    }
+
+    pub fn last_cell(
+        self,
+        Cell {
+            page,
+            row,
+            column: col,
+        }: Cell,
+    ) -> String {
+        match self {
+            Self::En => format!("Last cell: page {page}, row {row}, column {col}"),
+            Self::De => format!("Letzte Zelle: Seite {page}, Zeile {row}, Spalte {col}"),
+            Self::Fr => format!("Dernière cellule : page {page}, ligne {row}, colonne {col}"),
+            Self::Es => format!("Última celda: página {page}, fila {row}, columna {col}"),
+        }
+    }
+
+    pub fn next_grid_skip(self, skip: usize) -> String {
+        match self {
+            Self::En => format!("Next --grid-skip: {skip}"),
+            Self::De => format!("Nächstes --grid-skip: {skip}"),
+            Self::Fr => format!("Prochain --grid-skip : {skip}"),
+            Self::Es => format!("Siguiente --grid-skip: {skip}"),
+        }
+    }
+
+    pub fn missing_asset_ids(self, count: usize) -> String {
+        match self {
+            Self::En => format!("Items without an asset ID: {count}"),
+            Self::De => format!("Gegenstände ohne Asset-ID: {count}"),
+            Self::Fr => format!("Objets sans identifiant d'inventaire : {count}"),
+            Self::Es => format!("Artículos sin ID de activo: {count}"),
+        }
+    }
+
+    /// The prompt above the list of items to choose from with `pick`
+    pub fn pick_prompt(self) -> &'static str {
+        match self {
+            Self::En => "Choose the items to print:",
+            Self::De => "Gegenstände zum Drucken auswählen:",
+            Self::Fr => "Choisissez les objets à imprimer :",
+            Self::Es => "Elija los artículos que desea imprimir:",
+        }
+    }
+
+    pub fn server_resolved(self, address: &str) -> String {
+        match self {
+            Self::En => format!("The server's address resolves to {address}"),
+            Self::De => format!("Die Adresse des Servers wird zu {address} aufgelöst"),
+            Self::Fr => format!("L'adresse du serveur correspond à {address}"),
+            Self::Es => format!("La dirección del servidor se resuelve a {address}"),
+        }
+    }
+
+    pub fn api_responds(self, version: &str) -> String {
+        match self {
+            Self::En => format!("The API responds, running Homebox {version}"),
+            Self::De => format!("Die API antwortet, mit Homebox {version}"),
+            Self::Fr => format!("L'API répond, avec Homebox {version}"),
+            Self::Es => format!("La API responde, con Homebox {version}"),
+        }
+    }
+
+    pub fn logged_in(self) -> &'static str {
+        match self {
+            Self::En => "The credentials are accepted",
+            Self::De => "Die Anmeldedaten werden akzeptiert",
+            Self::Fr => "Les identifiants sont acceptés",
+            Self::Es => "Las credenciales son aceptadas",
+        }
+    }
+
+    pub fn label_downloaded(self, asset_id: AssetId) -> String {
+        match self {
+            Self::En => format!("The label maker returns the label for {asset_id}"),
+            Self::De => format!("Der Etikettendrucker liefert das Etikett für {asset_id}"),
+            Self::Fr => format!("Le générateur d'étiquettes renvoie l'étiquette de {asset_id}"),
+            Self::Es => format!("El generador de etiquetas devuelve la etiqueta de {asset_id}"),
+        }
+    }
+
+    pub fn no_label_to_check(self) -> &'static str {
+        match self {
+            Self::En => "No items have an asset ID, so the label maker was not checked",
+            Self::De => {
+                "Kein Gegenstand hat eine Asset-ID, daher wurde der Etikettendrucker nicht geprüft"
+            }
+            Self::Fr => {
+                "Aucun objet n'a d'identifiant d'inventaire, le générateur d'étiquettes n'a donc \
+                pas été vérifié"
+            }
+            Self::Es => {
+                "Ningún artículo tiene ID de activo, por lo que no se comprobó el generador de \
+                etiquetas"
+            }
+        }
+    }
+
+    pub fn wrote(self, bytes: usize, path: &Path) -> String {
+        let path = path.display();
+        match self {
+            Self::En => format!("Wrote {bytes} bytes to {path}"),
+            Self::De => format!("{bytes} Bytes in {path} geschrieben"),
+            Self::Fr => format!("{bytes} octets écrits dans {path}"),
+            Self::Es => format!("Se escribieron {bytes} bytes en {path}"),
+        }
+    }
+
+    pub fn labels_failed(self, count: usize) -> String {
+        match self {
+            Self::En => format!("Failed to get {count} labels, which were left out:"),
+            Self::De => format!("{count} Etiketten konnten nicht abgerufen werden und fehlen:"),
+            Self::Fr => {
+                format!("Impossible d'obtenir {count} étiquettes, qui ont été omises :")
+            }
+            Self::Es => format!("No se pudieron obtener {count} etiquetas, que se omitieron:"),
+        }
+    }
+
+    pub fn retry_with(self, assets: &str) -> String {
+        match self {
+            Self::En => format!("To retry only these, use the asset list: {assets}"),
+            Self::De => {
+                format!("Um nur diese erneut zu versuchen, verwende die Asset-Liste: {assets}")
+            }
+            Self::Fr => {
+                format!("Pour réessayer uniquement celles-ci, utilisez la liste : {assets}")
+            }
+            Self::Es => format!("Para reintentar solo estas, usa la lista de activos: {assets}"),
+        }
+    }
+
+    pub fn page_bookmark(self, page: usize) -> String {
+        match self {
+            Self::En | Self::Fr => format!("Page {page}"),
+            Self::De => format!("Seite {page}"),
+            Self::Es => format!("Página {page}"),
+        }
+    }
+}
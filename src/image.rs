@@ -0,0 +1,175 @@
+//! Recognising the format of label images, since servers and proxies do
+//! not always return PNGs, or say what they have returned.
+
+/// A format of image that can be embedded as a label
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageType {
+    Png,
+    Jpeg,
+    Gif,
+    WebP,
+    Svg,
+}
+
+impl ImageType {
+    /// Recognise the format of an image from its first bytes, if it is an
+    /// image at all
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if data.starts_with(b"\xff\xd8\xff") {
+            Some(Self::Jpeg)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(Self::Gif)
+        } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+            Some(Self::WebP)
+        } else if is_svg(data) {
+            Some(Self::Svg)
+        } else {
+            None
+        }
+    }
+
+    /// The usual file extension for images in this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Gif => "gif",
+            Self::WebP => "webp",
+            Self::Svg => "svg",
+        }
+    }
+
+    /// The MIME type of images in this format
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Gif => "image/gif",
+            Self::WebP => "image/webp",
+            Self::Svg => "image/svg+xml",
+        }
+    }
+}
+
+/// The (width, height) of an image, in pixels for PNGs and in the units
+/// of its view box, or its width and height, for SVGs. Only the ratio
+/// of the two is comparable between formats.
+pub fn dimensions(data: &[u8]) -> Option<(f64, f64)> {
+    let (width, height) = match ImageType::detect(data)? {
+        ImageType::Png if data.get(12..16)? == b"IHDR" => (
+            f64::from(u32::from_be_bytes(data.get(16..20)?.try_into().ok()?)),
+            f64::from(u32::from_be_bytes(data.get(20..24)?.try_into().ok()?)),
+        ),
+        ImageType::Svg => {
+            let text = std::str::from_utf8(data).ok()?;
+            let root = &text[text.find("<svg")?..];
+            let root = &root[..root.find('>')?];
+            match attribute(root, "viewBox") {
+                Some(view_box) => {
+                    let sizes: Vec<f64> = view_box
+                        .split([' ', ','])
+                        .filter(|part| !part.is_empty())
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .ok()?;
+                    (*sizes.get(2)?, *sizes.get(3)?)
+                }
+                None => (
+                    leading_number(attribute(root, "width")?)?,
+                    leading_number(attribute(root, "height")?)?,
+                ),
+            }
+        }
+        _ => return None,
+    };
+    (width > 0.0 && height > 0.0).then_some((width, height))
+}
+
+/// The value of an attribute of an XML tag, e.g. `<svg width="40mm"`
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {name}="))? + name.len() + 2;
+    let quote = tag[start..]
+        .chars()
+        .next()
+        .filter(|&c| c == '"' || c == '\'')?;
+    let value = &tag[start + 1..];
+    Some(&value[..value.find(quote)?])
+}
+
+/// The number at the start of a length, e.g. `40` of `40mm`
+fn leading_number(length: &str) -> Option<f64> {
+    let end = length
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(length.len());
+    length[..end].parse().ok()
+}
+
+/// Whether the data is an SVG document, allowing for a byte order mark,
+/// whitespace, an XML declaration and comments before the root element
+fn is_svg(data: &[u8]) -> bool {
+    let mut rest = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    loop {
+        rest = rest.trim_ascii_start();
+        if rest.starts_with(b"<svg") {
+            return true;
+        }
+        let end = if rest.starts_with(b"<?") {
+            b"?>".as_slice()
+        } else if rest.starts_with(b"<!--") {
+            b"-->".as_slice()
+        } else if rest.starts_with(b"<!DOCTYPE") {
+            b">".as_slice()
+        } else {
+            return false;
+        };
+        let Some(idx) = rest.windows(end.len()).position(|window| window == end) else {
+            return false;
+        };
+        rest = &rest[idx + end.len()..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_are_detected() {
+        assert_eq!(
+            ImageType::detect(b"\x89PNG\r\n\x1a\n\0\0"),
+            Some(ImageType::Png)
+        );
+        assert_eq!(
+            ImageType::detect(b"\xff\xd8\xff\xe0"),
+            Some(ImageType::Jpeg)
+        );
+        assert_eq!(
+            ImageType::detect(b"RIFF\0\0\0\0WEBPVP8 "),
+            Some(ImageType::WebP)
+        );
+        assert_eq!(
+            ImageType::detect(b"\xef\xbb\xbf<?xml version=\"1.0\"?>\n<!-- label -->\n<svg/>"),
+            Some(ImageType::Svg)
+        );
+        assert_eq!(ImageType::detect(b"<!DOCTYPE html><html>"), None);
+    }
+
+    #[test]
+    fn dimensions_are_read_from_pngs_and_svgs() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(526u32.to_be_bytes());
+        png.extend(200u32.to_be_bytes());
+        assert_eq!(dimensions(&png), Some((526.0, 200.0)));
+        assert_eq!(
+            dimensions(br#"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 38 21.5">"#),
+            Some((38.0, 21.5))
+        );
+        assert_eq!(
+            dimensions(br"<svg width='526px' height='200px'>"),
+            Some((526.0, 200.0))
+        );
+        assert_eq!(dimensions(b"<svg>"), None);
+    }
+}
@@ -0,0 +1,76 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use build_html::Html;
+
+use homebox_label_maker::{
+    api::HomeboxClient,
+    asset_list::AssetId,
+    html::{self, IndexEntry},
+    i18n::Lang,
+    layout::{LabelSlot, SheetConfig},
+};
+
+use crate::failure::Failure;
+
+/// The name and location of the item each label belongs to, and the
+/// cell it is placed in if the layout is given
+pub fn entries(
+    client: &HomeboxClient,
+    asset_ids: &[AssetId],
+    config: Option<&SheetConfig>,
+) -> anyhow::Result<Vec<IndexEntry>> {
+    let items = client.list_items().context("Failed to list items")?;
+    let cells: Vec<_> = config
+        .map(|config| {
+            config
+                .assignments(asset_ids.len())
+                .filter(|(_, slot)| matches!(slot, LabelSlot::Label(_)))
+                .map(|(cell, _)| cell)
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(asset_ids
+        .iter()
+        .enumerate()
+        .map(|(idx, &asset_id)| {
+            let item = items
+                .iter()
+                .find(|item| item.asset_id == asset_id.to_string());
+            IndexEntry {
+                asset_id,
+                name: item.map(|item| item.name.clone()).unwrap_or_default(),
+                location: item
+                    .and_then(|item| item.location.as_ref())
+                    .map(|location| location.name.clone()),
+                cell: cells.get(idx).copied(),
+                tag_colors: item
+                    .map(|item| {
+                        item.labels
+                            .iter()
+                            .map(|label| label.color.clone())
+                            // Tags without a color have an empty one
+                            .filter(|color| !color.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+/// Write an index of the printed labels, with the name and location of
+/// the item each belongs to
+pub fn write(
+    client: &HomeboxClient,
+    asset_ids: &[AssetId],
+    path: &Path,
+    lang: Lang,
+) -> anyhow::Result<()> {
+    let entries = entries(client, asset_ids, None)?;
+
+    tracing::info!("Writing index of {} labels...", entries.len());
+    fs::write(path, html::generate_index(&entries, lang).to_html_string())
+        .context("Failed to write index")
+        .context(Failure::Write)
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use homebox_label_maker::asset_list::{self, AssetId};
+
+use crate::cache::LabelCache;
+
+/// A record of the labels downloaded so far in a run, kept next to the
+/// output until the run completes so that an interrupted run can be
+/// resumed
+pub struct Journal {
+    /// The journal file and its path, unless the output cannot be
+    /// resumed
+    file: Option<(PathBuf, File)>,
+    fetched: HashSet<AssetId>,
+    cache: LabelCache,
+}
+
+impl Journal {
+    /// Open the journal for an output. When resuming, labels recorded
+    /// by the previous run are kept, otherwise the journal starts empty.
+    pub fn open(output: &Path, cache: LabelCache, resume: bool) -> anyhow::Result<Self> {
+        let mut path = output.as_os_str().to_owned();
+        path.push(".journal");
+        let path = PathBuf::from(path);
+
+        let mut fetched = HashSet::new();
+        let mut partial = false;
+        if fs::exists(&path).context("Failed to check if journal exists")? {
+            if resume {
+                let contents = fs::read_to_string(&path).context("Failed to read journal")?;
+                // The last line is cut off if the run was interrupted
+                // while writing it, and is left unread as it is not a
+                // whole asset ID
+                partial = !contents.is_empty() && !contents.ends_with('\n');
+                for line in contents.lines() {
+                    if let Ok(list) = asset_list::parse(line) {
+                        fetched.extend(list.into_iter().flatten());
+                    }
+                }
+                tracing::info!("Resuming with {} labels already fetched", fetched.len());
+            } else {
+                tracing::warn!(
+                    "A previous run for this output was interrupted. Starting over, use --resume to continue it instead."
+                );
+            }
+        } else if resume {
+            tracing::warn!("No interrupted run found to resume, starting from the beginning.");
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(resume)
+            .write(true)
+            .truncate(!resume)
+            .open(&path)
+            .context("Failed to open journal")?;
+        if partial {
+            // End the cut off line, so the next asset ID recorded is not
+            // run into it
+            writeln!(file).context("Failed to write to journal")?;
+        }
+
+        Ok(Self {
+            file: Some((path, file)),
+            fetched,
+            cache,
+        })
+    }
+
+    /// Start a journal that is only kept in memory, for output that
+    /// cannot be resumed, such as to stdout
+    pub fn in_memory(cache: LabelCache) -> Self {
+        Self {
+            file: None,
+            fetched: HashSet::new(),
+            cache,
+        }
+    }
+
+    /// Get the label for an asset if it was fetched earlier in this run,
+    /// read from the cache only when it is used
+    pub fn get(&self, asset_id: AssetId) -> Option<bytes::Bytes> {
+        if self.fetched.contains(&asset_id) {
+            self.cache.map(asset_id)
+        } else {
+            None
+        }
+    }
+
+    /// Record that a label has been fetched, storing it in the cache.
+    /// The stored label is returned to use in place of the one given, so
+    /// that labels for large runs are kept on disk rather than in memory.
+    pub fn record(
+        &mut self,
+        asset_id: AssetId,
+        label: bytes::Bytes,
+    ) -> anyhow::Result<bytes::Bytes> {
+        self.cache.put(asset_id, &label)?;
+        if let Some((_, file)) = &mut self.file {
+            writeln!(file, "{asset_id}").context("Failed to write to journal")?;
+        }
+        self.fetched.insert(asset_id);
+        Ok(self.cache.map(asset_id).unwrap_or(label))
+    }
+
+    /// Remove the journal once the run has completed
+    pub fn finish(self) -> anyhow::Result<()> {
+        let Some((path, file)) = self.file else {
+            return Ok(());
+        };
+        drop(file);
+        fs::remove_file(&path).context("Failed to remove journal")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(id: &str) -> AssetId {
+        asset_list::parse(id)
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .next()
+            .unwrap()
+    }
+
+    /// A directory for the output and cache of one test
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("homebox-journal-{name}-{}", std::process::id()));
+        fs::create_dir_all(dir.join("cache")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn resumed_run_skips_fetched_labels() {
+        let dir = test_dir("resume");
+        let output = dir.join("labels.html");
+        let cache = || LabelCache::in_dir(dir.join("cache"));
+
+        let mut journal = Journal::open(&output, cache(), false).unwrap();
+        journal.record(asset("000-001"), "one".into()).unwrap();
+        journal.record(asset("000-002"), "two".into()).unwrap();
+        // The run is interrupted, leaving the journal behind
+        drop(journal);
+
+        let journal = Journal::open(&output, cache(), true).unwrap();
+        assert_eq!(journal.get(asset("000-001")).as_deref(), Some(&b"one"[..]));
+        assert_eq!(journal.get(asset("000-002")).as_deref(), Some(&b"two"[..]));
+        assert_eq!(journal.get(asset("000-003")), None);
+        journal.finish().unwrap();
+        assert!(!fs::exists(dir.join("labels.html.journal")).unwrap());
+
+        // Without --resume, the run starts over
+        let mut journal = Journal::open(&output, cache(), false).unwrap();
+        journal.record(asset("000-001"), "one".into()).unwrap();
+        drop(journal);
+        let journal = Journal::open(&output, cache(), false).unwrap();
+        assert_eq!(journal.get(asset("000-001")), None);
+        drop(journal);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cut_off_line_is_not_trusted() {
+        let dir = test_dir("truncated");
+        let output = dir.join("labels.html");
+        let cache = || LabelCache::in_dir(dir.join("cache"));
+        for id in ["000-001", "000-012", "000-123"] {
+            cache().put(asset(id), id.as_bytes()).unwrap();
+        }
+        // Cut off while writing 000-012
+        fs::write(dir.join("labels.html.journal"), "000-001\n000-12").unwrap();
+
+        let mut journal = Journal::open(&output, cache(), true).unwrap();
+        assert!(journal.get(asset("000-001")).is_some());
+        assert_eq!(journal.get(asset("000-012")), None);
+        // The labels recorded after resuming are kept by the next resume
+        journal.record(asset("000-123"), "000-123".into()).unwrap();
+        drop(journal);
+
+        assert_eq!(
+            fs::read_to_string(dir.join("labels.html.journal")).unwrap(),
+            "000-001\n000-12\n000-123\n"
+        );
+        let journal = Journal::open(&output, cache(), true).unwrap();
+        assert!(journal.get(asset("000-123")).is_some());
+        assert_eq!(journal.get(asset("000-012")), None);
+        drop(journal);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,362 @@
+//! Rendering labels locally as SVG, rather than downloading them from the
+//! server.
+
+use std::fmt::Write;
+
+use qrcode::{EcLevel, QrCode};
+
+use crate::{asset_list::AssetId, error::RenderError};
+
+/// The details of an item shown on its label
+#[derive(Clone, Debug)]
+pub struct LabelData {
+    /// Shown before the asset ID, e.g. to identify which server the item
+    /// is from
+    pub prefix: Option<String>,
+    pub asset_id: AssetId,
+    pub name: String,
+    pub location: Option<String>,
+    /// Further lines shown below the location, as pairs of a name and a
+    /// value, such as custom fields
+    pub details: Vec<(String, String)>,
+}
+
+/// How labels are rendered locally
+#[derive(Clone, Debug)]
+pub struct LabelOptions {
+    /// The text encoded in the QR code, with `{asset_id}` replaced by the
+    /// asset ID, e.g. `https://homebox.example.com/a/{asset_id}`. Use
+    /// `{asset_id}` alone to encode the raw ID.
+    pub qr_template: String,
+    /// The ratio of width to height to render labels at, within their
+    /// cell. Labels fill the cell if not given.
+    pub aspect_ratio: Option<f64>,
+    /// Render for reading at a distance or with poor sight: a larger
+    /// title, every line in bold, and a QR code of fewer, larger modules
+    pub large_print: bool,
+    /// How much of the QR code can be damaged and still read, rather
+    /// than the default of medium, or low for large print
+    pub error_correction: Option<ErrorCorrection>,
+    /// The width of each module of the QR code in millimeters, rather
+    /// than filling the height of the label, to keep small codes
+    /// scannable
+    pub qr_module_mm: Option<f64>,
+    /// The width of the blank margin around the QR code, in modules
+    pub qr_quiet_zone: usize,
+    /// The font size in millimeters of the asset ID printed beneath the
+    /// QR code, so the label can still be read if the code is damaged.
+    /// The ID is only beside the code if not given.
+    pub id_size_mm: Option<f64>,
+}
+
+/// How much of a QR code can be damaged, scuffed or covered and still
+/// read. Higher levels need more modules, so each is smaller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ErrorCorrection {
+    /// About 7%
+    L,
+    /// About 15%
+    M,
+    /// About 25%
+    Q,
+    /// About 30%
+    H,
+}
+
+impl From<ErrorCorrection> for EcLevel {
+    fn from(level: ErrorCorrection) -> Self {
+        match level {
+            ErrorCorrection::L => Self::L,
+            ErrorCorrection::M => Self::M,
+            ErrorCorrection::Q => Self::Q,
+            ErrorCorrection::H => Self::H,
+        }
+    }
+}
+
+impl LabelOptions {
+    /// The options matching the labels rendered by a Homebox server at
+    /// the given URL, with QR codes linking to the item
+    pub fn for_server(server: &str) -> Self {
+        Self {
+            qr_template: format!("{}/a/{{asset_id}}", server.trim_end_matches('/')),
+            aspect_ratio: None,
+            large_print: false,
+            error_correction: None,
+            qr_module_mm: None,
+            qr_quiet_zone: 1,
+            id_size_mm: None,
+        }
+    }
+
+    /// The text encoded in the QR code for an asset
+    pub fn qr_text(&self, asset_id: AssetId) -> String {
+        self.qr_template
+            .replace("{asset_id}", &asset_id.to_string())
+    }
+}
+
+/// Render a label for an item as an SVG document, sized to fit a cell of
+/// the given (width, height) in millimeters
+///
+/// # Errors
+///
+/// Fails if the QR code text is too long to encode.
+pub fn render(
+    data: &LabelData,
+    options: &LabelOptions,
+    size_mm: (f64, f64),
+) -> Result<bytes::Bytes, RenderError> {
+    let title = match &data.prefix {
+        Some(prefix) => format!("{prefix} {}", data.asset_id),
+        None => data.asset_id.to_string(),
+    };
+    let lines: Vec<_> = [Some(data.name.clone()), data.location.clone()]
+        .into_iter()
+        .flatten()
+        .chain(
+            data.details
+                .iter()
+                .map(|(name, value)| format!("{name}: {value}")),
+        )
+        .collect();
+    render_sticker(
+        &options.qr_text(data.asset_id),
+        &title,
+        &lines,
+        Some(&data.asset_id.to_string()),
+        options,
+        size_mm,
+    )
+}
+
+/// Render a label with a QR code encoding any text, next to a bold title
+/// and further lines of text, as an SVG document sized to fit a cell of
+/// the given (width, height) in millimeters. The caption is printed
+/// beneath the code if the options give it a size. The QR code template
+/// in the options is not used.
+///
+/// # Errors
+///
+/// Fails if the QR code text is too long to encode, or the code does not
+/// fit at the module size given.
+pub fn render_sticker(
+    qr_text: &str,
+    title: &str,
+    lines: &[String],
+    caption: Option<&str>,
+    options: &LabelOptions,
+    (mut width, mut height): (f64, f64),
+) -> Result<bytes::Bytes, RenderError> {
+    if let Some(ratio) = options.aspect_ratio {
+        if width / height > ratio {
+            width = height * ratio;
+        } else {
+            height = width / ratio;
+        }
+    }
+
+    // The lowest error correction needs the fewest modules, so each is
+    // drawn larger in large print
+    let ec_level = options.error_correction.unwrap_or(if options.large_print {
+        ErrorCorrection::L
+    } else {
+        ErrorCorrection::M
+    });
+    let qr = QrCode::with_error_correction_level(qr_text, EcLevel::from(ec_level))
+        .map_err(|e| RenderError::Backend(Box::new(e)))?;
+
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}mm" height="{height}mm" viewBox="0 0 {width} {height}">"#
+    );
+
+    // The QR code fills the height of the label, with its quiet zone,
+    // above the caption and leaving the rest of the width for text,
+    // unless its modules are given a size
+    let caption = caption.zip(options.id_size_mm);
+    let code_height = height - caption.map_or(0.0, |(_, size)| 1.2 * size);
+    let modules = qr.width();
+    #[allow(
+        clippy::cast_precision_loss,
+        reason = "QR codes are at most 177 modules wide"
+    )]
+    let span = (modules + 2 * options.qr_quiet_zone) as f64;
+    let (qr_size, scale) = match options.qr_module_mm {
+        Some(module) if module * span > code_height.min(width) => {
+            return Err(RenderError::Unsupported(format!(
+                "a QR code {:.1}mm wide, of {modules} modules of {module}mm and the quiet \
+                zone, does not fit on a {width:.1}x{height:.1}mm label",
+                module * span
+            )));
+        }
+        Some(module) => (module * span, module),
+        None => {
+            let qr_size = code_height.min(width / 2.0);
+            (qr_size, qr_size / span)
+        }
+    };
+    write_qr_path(&mut svg, &qr, scale, options.qr_quiet_zone);
+    if let Some((caption, size)) = caption {
+        let _ = write!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle" font-family="sans-serif" font-size="{size}">{}</text>"#,
+            qr_size / 2.0,
+            qr_size + size,
+            escape(caption)
+        );
+    }
+
+    let weight = if options.large_print {
+        "bold"
+    } else {
+        "normal"
+    };
+    let lines: Vec<_> = [(title, "bold")]
+        .into_iter()
+        .chain(lines.iter().map(|line| (line.as_str(), weight)))
+        .collect();
+
+    // Shrink the text to fit when there are more lines than usual
+    #[allow(clippy::cast_precision_loss, reason = "there are only a few lines")]
+    let mut font_size = (height / 5.0).min(height / (1.2 * lines.len() as f64 + 0.2));
+    let mut title_size = font_size;
+    if options.large_print {
+        // The title takes as much of the space beside the QR code as it
+        // fits in, and the other lines share what is left below it
+        #[allow(clippy::cast_precision_loss, reason = "titles are short")]
+        let title_width = 0.65 * title.chars().count().max(1) as f64 + 0.5;
+        title_size = (height / 2.5).min((width - qr_size) / title_width);
+        #[allow(clippy::cast_precision_loss, reason = "there are only a few lines")]
+        let rest = (height - 1.2 * title_size) / (1.2 * (lines.len() - 1) as f64 + 0.2);
+        font_size = (height / 4.0).min(rest).min(title_size);
+    }
+    let x = qr_size + font_size / 2.0;
+    for (line, (text, weight)) in lines.into_iter().enumerate() {
+        let (y, size) = if line == 0 {
+            (title_size, title_size)
+        } else {
+            #[allow(clippy::cast_precision_loss, reason = "there are only a few lines")]
+            let y = title_size + 1.2 * font_size * line as f64;
+            (y, font_size)
+        };
+        let _ = write!(
+            svg,
+            r#"<text x="{x}" y="{y}" font-family="sans-serif" font-size="{size}" font-weight="{weight}">{}</text>"#,
+            escape(text)
+        );
+    }
+
+    svg.push_str("</svg>");
+    Ok(svg.into())
+}
+
+/// Draw a QR code as a path, scaled to modules of the given size and
+/// offset by its quiet zone
+fn write_qr_path(svg: &mut String, qr: &QrCode, scale: f64, quiet_zone: usize) {
+    let _ = write!(
+        svg,
+        r#"<path transform="scale({scale}) translate({quiet_zone} {quiet_zone})" shape-rendering="crispEdges" d=""#
+    );
+    // Each run of dark modules in a row is drawn as one rectangle, so
+    // neighbouring modules have no seams between them at any size
+    let modules = qr.width();
+    let colors = qr.to_colors();
+    for (y, row) in colors.chunks(modules).enumerate() {
+        let mut x = 0;
+        while x < modules {
+            if row[x] == qrcode::Color::Dark {
+                let run = row[x..]
+                    .iter()
+                    .take_while(|&&color| color == qrcode::Color::Dark)
+                    .count();
+                let _ = write!(svg, "M{x} {y}h{run}v1h-{run}z");
+                x += run;
+            } else {
+                x += 1;
+            }
+        }
+    }
+    svg.push_str(r#""/>"#);
+}
+
+/// Render a label holding only a line of text, centred, as an SVG
+/// document sized to fill a cell of the given (width, height) in
+/// millimeters. Used to divide groups of labels.
+pub fn render_divider(text: &str, (width, height): (f64, f64)) -> bytes::Bytes {
+    let font_size = height / 4.0;
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}mm" height="{height}mm" viewBox="0 0 {width} {height}"><text x="{}" y="{}" text-anchor="middle" dominant-baseline="middle" font-family="sans-serif" font-size="{font_size}" font-weight="bold">{}</text></svg>"#,
+        width / 2.0,
+        height / 2.0,
+        escape(text)
+    )
+    .into()
+}
+
+/// Escape text for use in XML
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> LabelData {
+        LabelData {
+            prefix: None,
+            asset_id: crate::asset_list::parse("000-042")
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .next()
+                .unwrap(),
+            name: "Nuts & <Bolts>".to_string(),
+            location: None,
+            details: vec![],
+        }
+    }
+
+    #[test]
+    fn qr_text_defaults_to_item_page() {
+        let options = LabelOptions::for_server("https://homebox.example.com/");
+        assert_eq!(
+            options.qr_text(data().asset_id),
+            "https://homebox.example.com/a/000-042"
+        );
+    }
+
+    #[test]
+    fn qr_template_can_encode_raw_id() {
+        let options = LabelOptions {
+            qr_template: "{asset_id}".to_string(),
+            aspect_ratio: None,
+            large_print: false,
+            error_correction: None,
+            qr_module_mm: None,
+            qr_quiet_zone: 1,
+            id_size_mm: None,
+        };
+        assert_eq!(options.qr_text(data().asset_id), "000-042");
+    }
+
+    #[test]
+    fn text_is_escaped() {
+        let options = LabelOptions::for_server("https://homebox.example.com");
+        let svg = render(&data(), &options, (40.0, 20.0)).unwrap();
+        let svg = std::str::from_utf8(&svg).unwrap();
+        assert!(svg.contains(">Nuts &amp; &lt;Bolts&gt;</text>"));
+    }
+}
@@ -0,0 +1,30 @@
+use anyhow::Context;
+
+use homebox_label_maker::{api::HomeboxClient, asset_list::AssetId};
+
+/// Record on each printed item that it now has a label, by setting a
+/// custom field to the date and batch it was printed in
+pub fn mark(
+    client: &HomeboxClient,
+    asset_ids: &[AssetId],
+    field: &str,
+    batch: &str,
+) -> anyhow::Result<()> {
+    let value = format!("{} ({batch})", chrono::Local::now().format("%Y-%m-%d"));
+    let items = client
+        .list_items()
+        .context("Failed to list items to mark as labeled")?;
+
+    for asset_id in asset_ids {
+        let asset_id = asset_id.to_string();
+        let Some(item) = items.iter().find(|item| item.asset_id == asset_id) else {
+            tracing::warn!("No item has asset ID {asset_id}, so it cannot be marked as labeled");
+            continue;
+        };
+        tracing::info!("Marking {} as labeled", item.name);
+        client
+            .set_item_field(&item.id, field, &value)
+            .with_context(|| format!("Failed to mark {} as labeled", item.name))?;
+    }
+    Ok(())
+}
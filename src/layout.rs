@@ -0,0 +1,878 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::LayoutError;
+
+/// The default layout, used for the command line defaults
+const DEFAULT: Layout = Layout {
+    page_width_mm: 210.0,
+    page_height_mm: 297.0,
+    page_margin_top_mm: 10.0,
+    page_margin_left_mm: 5.0,
+    page_margin_bottom_mm: 10.0,
+    page_margin_right_mm: 5.0,
+    grid_rows: 13,
+    grid_columns: 5,
+    grid_row_spacing_mm: 0.0,
+    grid_col_spacing_mm: 2.5,
+    grid_skip: 0,
+    label_width_mm: None,
+    label_height_mm: None,
+    auto_grid: false,
+    skip_cells: None,
+    roll_pitch_mm: None,
+};
+
+/// A cell of the grid. All positions are 1-based.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub page: usize,
+    pub row: usize,
+    pub column: usize,
+}
+
+/// What is placed in a cell
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelSlot {
+    /// The cell is skipped, as it has been used on a previous run
+    Skipped,
+    /// The cell holds the label at this index in the list of labels
+    Label(usize),
+}
+
+/// The options for how labels are arranged in a grid on each page, as
+/// given on the command line or in a template. Build it into a
+/// [`SheetConfig`] to use it.
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::Args))]
+#[serde(default)]
+pub struct Layout {
+    /// The width of the page, in millimeters
+    #[cfg_attr(feature = "cli", arg(long, default_value_t = DEFAULT.page_width_mm))]
+    pub page_width_mm: f64,
+
+    /// The height of the page, in millimeters
+    #[cfg_attr(feature = "cli", arg(long, default_value_t = DEFAULT.page_height_mm))]
+    pub page_height_mm: f64,
+
+    /// The margin at the top of the page before the first row, in
+    /// millimeters
+    #[cfg_attr(feature = "cli", arg(long, default_value_t = DEFAULT.page_margin_top_mm))]
+    pub page_margin_top_mm: f64,
+
+    /// The margin to the left of the page, before the first column, in
+    /// millimeters
+    #[cfg_attr(feature = "cli", arg(long, default_value_t = DEFAULT.page_margin_left_mm))]
+    pub page_margin_left_mm: f64,
+
+    /// The margin at the bottom of the page after the last row, in
+    /// millimeters
+    #[cfg_attr(feature = "cli", arg(long, default_value_t = DEFAULT.page_margin_bottom_mm))]
+    pub page_margin_bottom_mm: f64,
+
+    /// The margin to the right of the page, after the last column, in
+    /// millimeters
+    #[cfg_attr(feature = "cli", arg(long, default_value_t = DEFAULT.page_margin_right_mm))]
+    pub page_margin_right_mm: f64,
+
+    /// The number of rows in the grid
+    #[cfg_attr(feature = "cli", arg(long, default_value_t = DEFAULT.grid_rows))]
+    pub grid_rows: usize,
+
+    /// The number of columns in the grid
+    #[cfg_attr(feature = "cli", arg(long, default_value_t = DEFAULT.grid_columns))]
+    pub grid_columns: usize,
+
+    /// The spacing between each grid row, in millimeters
+    #[cfg_attr(feature = "cli", arg(long, default_value_t = DEFAULT.grid_row_spacing_mm))]
+    pub grid_row_spacing_mm: f64,
+
+    /// The spacing between each grid column, in millimeters
+    #[cfg_attr(feature = "cli", arg(long, default_value_t = DEFAULT.grid_col_spacing_mm))]
+    pub grid_col_spacing_mm: f64,
+
+    /// Skip the first n elements of the grid to make better use of
+    /// partially used sheets
+    #[cfg_attr(feature = "cli", arg(long, short = 'S', default_value_t = DEFAULT.grid_skip))]
+    pub grid_skip: usize,
+
+    /// The width of each label, in millimeters, as given on the label
+    /// sheet's datasheet. When given, the right margin is whatever is
+    /// left over, rather than the labels sharing the width between the
+    /// margins.
+    #[cfg_attr(feature = "cli", arg(long))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_width_mm: Option<f64>,
+
+    /// The height of each label, in millimeters, as given on the label
+    /// sheet's datasheet. When given, the bottom margin is whatever is
+    /// left over, rather than the labels sharing the height between the
+    /// margins.
+    #[cfg_attr(feature = "cli", arg(long))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_height_mm: Option<f64>,
+
+    /// Fit as many rows and columns of labels of the given size as
+    /// there is space for between the margins, rather than using the
+    /// grid size given
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, requires = "label_width_mm", requires = "label_height_mm")
+    )]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub auto_grid: bool,
+
+    /// Leave these cells empty as well, e.g. where labels have already
+    /// been peeled off. Cells are numbered from 1 across each row of the
+    /// first page, e.g. `3,7,22`, or can be on a later page, e.g. `2:5`
+    /// for cell 5 of page 2.
+    #[cfg_attr(feature = "cli", arg(long))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_cells: Option<String>,
+
+    /// Print on a continuous roll of labels rather than sheets, with a
+    /// label every this many millimeters along the roll, from the top of
+    /// one to the top of the next. The labels are in a single column on
+    /// one strip as long as needed, so the page height, rows and columns
+    /// are ignored, and the row spacing is the gap between labels.
+    #[cfg_attr(
+        feature = "cli",
+        arg(long, conflicts_with_all = ["auto_grid", "label_height_mm"])
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roll_pitch_mm: Option<f64>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        DEFAULT
+    }
+}
+
+impl Layout {
+    /// Validate the layout, producing the configuration for a sheet
+    ///
+    /// # Errors
+    ///
+    /// Fails if the grid cannot be laid out on the page.
+    pub fn build(&self) -> Result<SheetConfig, LayoutError> {
+        SheetBuilder {
+            layout: self.clone(),
+        }
+        .build()
+    }
+}
+
+/// Builds a [`SheetConfig`], starting from the default layout
+#[derive(Clone, Default)]
+pub struct SheetBuilder {
+    layout: Layout,
+}
+
+impl SheetBuilder {
+    /// Set the size of the page
+    #[must_use]
+    pub fn page_size_mm(mut self, width: f64, height: f64) -> Self {
+        self.layout.page_width_mm = width;
+        self.layout.page_height_mm = height;
+        self
+    }
+
+    /// Set the margins around the grid, in the same order as CSS
+    #[must_use]
+    pub fn margins_mm(mut self, top: f64, right: f64, bottom: f64, left: f64) -> Self {
+        self.layout.page_margin_top_mm = top;
+        self.layout.page_margin_right_mm = right;
+        self.layout.page_margin_bottom_mm = bottom;
+        self.layout.page_margin_left_mm = left;
+        self
+    }
+
+    /// Set the number of rows and columns of labels on each page
+    #[must_use]
+    pub fn grid(mut self, rows: usize, columns: usize) -> Self {
+        self.layout.grid_rows = rows;
+        self.layout.grid_columns = columns;
+        self
+    }
+
+    /// Set the spacing between rows and between columns
+    #[must_use]
+    pub fn spacing_mm(mut self, row: f64, column: f64) -> Self {
+        self.layout.grid_row_spacing_mm = row;
+        self.layout.grid_col_spacing_mm = column;
+        self
+    }
+
+    /// Set the number of cells to leave empty at the start of the first
+    /// page
+    #[must_use]
+    pub fn skip(mut self, cells: usize) -> Self {
+        self.layout.grid_skip = cells;
+        self
+    }
+
+    /// Set the size of each label, leaving whatever space is left over
+    /// at the right and bottom of the page, rather than sharing the
+    /// space between the margins
+    #[must_use]
+    pub fn label_size_mm(mut self, width: f64, height: f64) -> Self {
+        self.layout.label_width_mm = Some(width);
+        self.layout.label_height_mm = Some(height);
+        self
+    }
+
+    /// Fit as many rows and columns of labels as there is space for
+    /// between the margins, once the label size is set
+    #[must_use]
+    pub fn auto_grid(mut self) -> Self {
+        self.layout.auto_grid = true;
+        self
+    }
+
+    /// Set the cells to leave empty, in the form `3,7,2:5`, numbered from
+    /// 1 across each row, and on the first page unless the page is given
+    #[must_use]
+    pub fn skip_cells(mut self, cells: &str) -> Self {
+        self.layout.skip_cells = Some(cells.to_string());
+        self
+    }
+
+    /// Print on a continuous roll in a single column, with a label every
+    /// `pitch` millimeters, rather than on sheets
+    #[must_use]
+    pub fn roll_mm(mut self, pitch: f64) -> Self {
+        self.layout.roll_pitch_mm = Some(pitch);
+        self
+    }
+
+    /// Check the grid can be laid out on the page
+    ///
+    /// # Errors
+    ///
+    /// Fails if any size is negative, the grid is empty, or there is no
+    /// space left for the labels.
+    #[allow(
+        clippy::cast_precision_loss,
+        reason = "grid dimensions are far too small to lose precision"
+    )]
+    pub fn build(self) -> Result<SheetConfig, LayoutError> {
+        let Layout {
+            page_width_mm,
+            page_height_mm,
+            page_margin_top_mm,
+            page_margin_left_mm,
+            mut page_margin_bottom_mm,
+            mut page_margin_right_mm,
+            mut grid_rows,
+            mut grid_columns,
+            grid_row_spacing_mm,
+            grid_col_spacing_mm,
+            grid_skip,
+            label_width_mm,
+            label_height_mm,
+            auto_grid,
+            skip_cells,
+            roll_pitch_mm,
+        } = self.layout;
+
+        let labels = [label_width_mm, label_height_mm, roll_pitch_mm];
+        if !labels.into_iter().flatten().all(|size| size > 0.0) {
+            Err(LayoutError::NegativeSize)?;
+        }
+        // The length of a roll depends on the labels, so it is laid out
+        // as a single row until then
+        if roll_pitch_mm.is_some() {
+            (grid_rows, grid_columns) = (1, 1);
+        }
+        if let (true, Some(width), Some(height)) = (auto_grid, label_width_mm, label_height_mm) {
+            grid_columns = fit(
+                page_width_mm - page_margin_left_mm - page_margin_right_mm,
+                width,
+                grid_col_spacing_mm,
+            );
+            grid_rows = fit(
+                page_height_mm - page_margin_top_mm - page_margin_bottom_mm,
+                height,
+                grid_row_spacing_mm,
+            );
+            if grid_columns == 0 || grid_rows == 0 {
+                Err(LayoutError::NoSpaceForGrid)?;
+            }
+        }
+        // Labels of a given size leave the rest of the page after the
+        // grid as the margin
+        if let Some(width) = label_width_mm {
+            let columns = grid_columns as f64;
+            page_margin_right_mm = page_width_mm
+                - page_margin_left_mm
+                - width * columns
+                - grid_col_spacing_mm * (columns - 1.0);
+            if page_margin_right_mm < 0.0 {
+                Err(LayoutError::NoSpaceForGrid)?;
+            }
+        }
+        if let (Some(height), None) = (label_height_mm, roll_pitch_mm) {
+            let rows = grid_rows as f64;
+            page_margin_bottom_mm = page_height_mm
+                - page_margin_top_mm
+                - height * rows
+                - grid_row_spacing_mm * (rows - 1.0);
+            if page_margin_bottom_mm < 0.0 {
+                Err(LayoutError::NoSpaceForGrid)?;
+            }
+        }
+
+        let sizes = [
+            page_width_mm,
+            page_height_mm,
+            page_margin_top_mm,
+            page_margin_left_mm,
+            page_margin_bottom_mm,
+            page_margin_right_mm,
+            grid_row_spacing_mm,
+            grid_col_spacing_mm,
+        ];
+        // Written so that NaN is rejected too
+        if !sizes.iter().all(|&size| size >= 0.0) {
+            Err(LayoutError::NegativeSize)?;
+        }
+        if grid_rows == 0 || grid_columns == 0 {
+            Err(LayoutError::EmptyGrid)?;
+        }
+
+        let mut config = SheetConfig {
+            page_width_mm,
+            page_height_mm,
+            page_margin_top_mm,
+            page_margin_left_mm,
+            page_margin_bottom_mm,
+            page_margin_right_mm,
+            grid_rows,
+            grid_columns,
+            grid_row_spacing_mm,
+            grid_col_spacing_mm,
+            grid_skip,
+            skip_cells: vec![],
+            roll_pitch_mm,
+        };
+        if let Some(cells) = skip_cells {
+            config.skip_cells = parse_cells(&cells, config.num_per_page())?;
+        }
+        let (width, height) = config.cell_size_mm();
+        if !(width > 0.0 && height > 0.0) {
+            Err(LayoutError::NoSpaceForGrid)?;
+        }
+        Ok(config)
+    }
+}
+
+/// The number of labels of the given size, with the given spacing
+/// between them, that fit in the space, all in millimeters
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "the count is floored, and negative counts become zero"
+)]
+fn fit(space: f64, size: f64, spacing: f64) -> usize {
+    ((space + spacing) / (size + spacing)).floor().max(0.0) as usize
+}
+
+/// Parse a list of cells such as `3,7,2:5` into their indices, counting
+/// across rows and then down the pages, in order
+fn parse_cells(cells: &str, num_per_page: usize) -> Result<Vec<usize>, LayoutError> {
+    let mut indices = vec![];
+    for cell in cells
+        .split(',')
+        .map(str::trim)
+        .filter(|cell| !cell.is_empty())
+    {
+        let invalid = || LayoutError::InvalidCell(cell.to_string());
+        let (page, within_page): (usize, _) = match cell.split_once(':') {
+            Some((page, within_page)) => (page.parse().map_err(|_| invalid())?, within_page),
+            None => (1, cell),
+        };
+        let within_page: usize = within_page.parse().map_err(|_| invalid())?;
+        if within_page == 0 || within_page > num_per_page {
+            Err(invalid())?;
+        }
+        let index = page
+            .checked_sub(1)
+            .and_then(|page| page.checked_mul(num_per_page))
+            .and_then(|start| start.checked_add(within_page - 1))
+            .ok_or_else(invalid)?;
+        indices.push(index);
+    }
+    indices.sort_unstable();
+    indices.dedup();
+    Ok(indices)
+}
+
+/// A validated description of how labels are arranged on each page,
+/// created with [`SheetConfig::builder`] or [`Layout::build`]
+#[derive(Clone, Debug)]
+pub struct SheetConfig {
+    page_width_mm: f64,
+    page_height_mm: f64,
+    page_margin_top_mm: f64,
+    page_margin_left_mm: f64,
+    page_margin_bottom_mm: f64,
+    page_margin_right_mm: f64,
+    grid_rows: usize,
+    grid_columns: usize,
+    grid_row_spacing_mm: f64,
+    grid_col_spacing_mm: f64,
+    grid_skip: usize,
+    /// Further cells to leave empty, by index, in order
+    skip_cells: Vec<usize>,
+    /// The distance from each label to the next on a roll, which is one
+    /// page as long as needed
+    roll_pitch_mm: Option<f64>,
+}
+
+impl SheetConfig {
+    /// Start building a configuration from the default layout
+    pub fn builder() -> SheetBuilder {
+        SheetBuilder::default()
+    }
+
+    /// The (width, height) of the page, in millimeters
+    pub fn page_size_mm(&self) -> (f64, f64) {
+        (self.page_width_mm, self.page_height_mm)
+    }
+
+    /// The number of cells left empty at the start of the first page
+    pub fn grid_skip(&self) -> usize {
+        self.grid_skip
+    }
+
+    /// A copy of this configuration leaving a different number of cells
+    /// empty
+    #[must_use]
+    pub fn with_skip(mut self, cells: usize) -> Self {
+        self.grid_skip = cells;
+        self
+    }
+
+    /// The number of labels that fit on a single page, which is any
+    /// number on a roll
+    pub fn num_per_page(&self) -> usize {
+        if self.roll_pitch_mm.is_some() {
+            usize::MAX
+        } else {
+            self.grid_rows * self.grid_columns
+        }
+    }
+
+    /// Whether labels are printed on a roll rather than sheets
+    pub fn is_roll(&self) -> bool {
+        self.roll_pitch_mm.is_some()
+    }
+
+    /// The configuration to lay out the given number of labels with. A
+    /// roll becomes a single page just long enough for them, and sheets
+    /// are unchanged.
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        reason = "grid dimensions are far too small to lose precision"
+    )]
+    pub fn unrolled(&self, labels: usize) -> Self {
+        let mut config = self.clone();
+        if let Some(pitch) = config.roll_pitch_mm.take() {
+            config.grid_rows = self.cells_used(labels).max(1);
+            config.page_height_mm = self.page_margin_top_mm + pitch * config.grid_rows as f64
+                - self.grid_row_spacing_mm
+                + self.page_margin_bottom_mm;
+        }
+        config
+    }
+
+    /// The number of pages needed to hold the given number of labels
+    /// after the skipped cells
+    pub fn page_count(&self, labels: usize) -> usize {
+        self.cells_used(labels).div_ceil(self.num_per_page())
+    }
+
+    /// The number of cells up to and including the last of the given
+    /// number of labels, or the cells skipped at the start if there are
+    /// no labels
+    pub fn cells_used(&self, labels: usize) -> usize {
+        self.assignments(labels).count()
+    }
+
+    /// The number of cells used on the last page after the given number
+    /// of labels, to skip on the next run. A roll is cut after printing,
+    /// so always starts afresh.
+    pub fn next_grid_skip(&self, labels: usize) -> usize {
+        if self.is_roll() {
+            0
+        } else {
+            self.cells_used(labels) % self.num_per_page()
+        }
+    }
+
+    /// Whether the cell at the given index is left empty
+    pub fn is_skipped(&self, index: usize) -> bool {
+        index < self.grid_skip || self.skip_cells.binary_search(&index).is_ok()
+    }
+
+    /// A copy of this configuration starting at the given page, so that
+    /// it is the first page, with the cells skipped on it and the pages
+    /// after it
+    #[must_use]
+    pub fn from_page(mut self, page: usize) -> Self {
+        let offset = page.saturating_sub(1).saturating_mul(self.num_per_page());
+        self.grid_skip = self.grid_skip.saturating_sub(offset);
+        self.skip_cells = self
+            .skip_cells
+            .iter()
+            .filter_map(|index| index.checked_sub(offset))
+            .collect();
+        self
+    }
+
+    /// The cell at the given index, counting across rows and then down
+    /// the pages
+    pub fn cell(&self, index: usize) -> Cell {
+        let num_per_page = self.num_per_page();
+        let within_page = index % num_per_page;
+        Cell {
+            page: index / num_per_page + 1,
+            row: within_page / self.grid_columns + 1,
+            column: within_page % self.grid_columns + 1,
+        }
+    }
+
+    /// Assign the given number of labels to cells, around the skipped
+    /// cells, in the order they are filled
+    pub fn assignments(&self, labels: usize) -> impl Iterator<Item = (Cell, LabelSlot)> {
+        let mut next = 0;
+        (0..).map_while(move |index| {
+            // The cells skipped at the start are kept even without labels,
+            // but not skipped cells after the last label
+            if next == labels && index >= self.grid_skip {
+                return None;
+            }
+            let slot = if self.is_skipped(index) {
+                LabelSlot::Skipped
+            } else {
+                next += 1;
+                LabelSlot::Label(next - 1)
+            };
+            Some((self.cell(index), slot))
+        })
+    }
+
+    /// The (width, height) of each cell, in millimeters
+    #[allow(
+        clippy::cast_precision_loss,
+        reason = "grid dimensions are far too small to lose precision"
+    )]
+    pub fn cell_size_mm(&self) -> (f64, f64) {
+        let columns = self.grid_columns as f64;
+        let rows = self.grid_rows as f64;
+        (
+            (self.page_width_mm
+                - self.page_margin_left_mm
+                - self.page_margin_right_mm
+                - self.grid_col_spacing_mm * (columns - 1.0))
+                / columns,
+            match self.roll_pitch_mm {
+                Some(pitch) => pitch - self.grid_row_spacing_mm,
+                None => {
+                    (self.page_height_mm
+                        - self.page_margin_top_mm
+                        - self.page_margin_bottom_mm
+                        - self.grid_row_spacing_mm * (rows - 1.0))
+                        / rows
+                }
+            },
+        )
+    }
+
+    /// The (x, y) of the top left corner of a cell from the top left of
+    /// its page, in millimeters
+    #[allow(
+        clippy::cast_precision_loss,
+        reason = "grid dimensions are far too small to lose precision"
+    )]
+    pub fn cell_origin_mm(&self, cell: Cell) -> (f64, f64) {
+        let (width, height) = self.cell_size_mm();
+        (
+            self.page_margin_left_mm
+                + (cell.column - 1) as f64 * (width + self.grid_col_spacing_mm),
+            self.page_margin_top_mm + (cell.row - 1) as f64 * (height + self.grid_row_spacing_mm),
+        )
+    }
+
+    /// Generate the CSS that sizes the printed page and positions the
+    /// grid on each page. The layout is given as custom properties at the
+    /// top, so it can be adjusted by editing the output.
+    pub fn configurable_style(&self) -> String {
+        format!(
+            r"
+        /*
+         * The layout of the sheet. Edit these values to adjust the
+         * alignment of a printed sheet without generating it again:
+         *
+         * --page-width, --page-height: the size of the paper
+         * --margin-*: the space between the edge of the paper and the grid
+         * --grid-columns, --grid-rows: the number of labels across and down
+         * --column-gap, --row-gap: the space between labels
+         *
+         * Browsers do not allow these in the @page size below, so change
+         * it to match if the page size is changed.
+         */
+        :root {{
+            --page-width: {}mm;
+            --page-height: {}mm;
+            --margin-top: {}mm;
+            --margin-right: {}mm;
+            --margin-bottom: {}mm;
+            --margin-left: {}mm;
+            --grid-columns: {};
+            --grid-rows: {};
+            --column-gap: {}mm;
+            --row-gap: {}mm;
+        }}
+        @page {{
+            size: {}mm {}mm;
+            margin: 0;
+        }}
+        .page {{
+            width: calc(var(--page-width) - var(--margin-left) - var(--margin-right));
+            height: calc(var(--page-height) - var(--margin-top) - var(--margin-bottom));
+            padding-top: var(--margin-top);
+            padding-right: var(--margin-right);
+            padding-bottom: var(--margin-bottom);
+            padding-left: var(--margin-left);
+            grid-template-columns: repeat(var(--grid-columns), 1fr);
+            grid-template-rows: repeat(var(--grid-rows), 1fr);
+            column-gap: var(--column-gap);
+            row-gap: var(--row-gap);
+        }}
+    ",
+            self.page_width_mm,
+            self.page_height_mm,
+            self.page_margin_top_mm,
+            self.page_margin_right_mm,
+            self.page_margin_bottom_mm,
+            self.page_margin_left_mm,
+            self.grid_columns,
+            self.grid_rows,
+            self.grid_col_spacing_mm,
+            self.grid_row_spacing_mm,
+            self.page_width_mm,
+            self.page_height_mm,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A small layout that is easy to reason about: a 100x100mm page
+    /// with 10mm margins, holding a 2x3 grid
+    fn builder() -> SheetBuilder {
+        SheetConfig::builder()
+            .page_size_mm(100.0, 100.0)
+            .margins_mm(10.0, 10.0, 10.0, 10.0)
+            .grid(2, 3)
+            .spacing_mm(0.0, 0.0)
+    }
+
+    fn config() -> SheetConfig {
+        builder().build().unwrap()
+    }
+
+    fn cell(page: usize, row: usize, column: usize) -> Cell {
+        Cell { page, row, column }
+    }
+
+    #[test]
+    fn default_is_valid() {
+        Layout::default().build().unwrap();
+        SheetConfig::builder().build().unwrap();
+    }
+
+    #[test]
+    fn empty_grid_is_invalid() {
+        assert!(matches!(
+            builder().grid(2, 0).build(),
+            Err(LayoutError::EmptyGrid)
+        ));
+    }
+
+    #[test]
+    fn negative_sizes_are_invalid() {
+        assert!(matches!(
+            builder().margins_mm(10.0, -1.0, 10.0, 10.0).build(),
+            Err(LayoutError::NegativeSize)
+        ));
+        assert!(matches!(
+            builder().spacing_mm(f64::NAN, 0.0).build(),
+            Err(LayoutError::NegativeSize)
+        ));
+    }
+
+    #[test]
+    fn margins_filling_page_are_invalid() {
+        assert!(matches!(
+            builder().margins_mm(10.0, 50.0, 10.0, 50.0).build(),
+            Err(LayoutError::NoSpaceForGrid)
+        ));
+    }
+
+    #[test]
+    fn spacing_filling_page_is_invalid() {
+        assert!(matches!(
+            builder().spacing_mm(0.0, 40.0).build(),
+            Err(LayoutError::NoSpaceForGrid)
+        ));
+    }
+
+    #[test]
+    fn label_size_sets_cell_size() {
+        let config = builder().label_size_mm(25.0, 30.0).build().unwrap();
+        assert_eq!(config.cell_size_mm(), (25.0, 30.0));
+        assert!(matches!(
+            builder().label_size_mm(35.0, 30.0).build(),
+            Err(LayoutError::NoSpaceForGrid)
+        ));
+    }
+
+    #[test]
+    fn auto_grid_fits_labels() {
+        let config = builder()
+            .spacing_mm(2.0, 2.0)
+            .label_size_mm(25.0, 30.0)
+            .auto_grid()
+            .build()
+            .unwrap();
+        assert_eq!(config.num_per_page(), 3 * 2);
+        assert_eq!(config.cell_size_mm(), (25.0, 30.0));
+    }
+
+    #[test]
+    fn skipped_cells_are_left_empty() {
+        let config = builder().skip(1).skip_cells("3, 2:2").build().unwrap();
+        let labels: Vec<_> = config
+            .assignments(5)
+            .filter(|(_, slot)| *slot != LabelSlot::Skipped)
+            .map(|(cell, _)| cell)
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                cell(1, 1, 2),
+                cell(1, 2, 1),
+                cell(1, 2, 2),
+                cell(1, 2, 3),
+                cell(2, 1, 1),
+            ]
+        );
+        assert_eq!(config.page_count(5), 2);
+        assert_eq!(config.page_count(6), 2);
+        assert!(matches!(
+            builder().skip_cells("7").build(),
+            Err(LayoutError::InvalidCell(_))
+        ));
+    }
+
+    #[test]
+    fn roll_is_one_page_as_long_as_needed() {
+        let config = builder()
+            .spacing_mm(2.0, 0.0)
+            .roll_mm(22.0)
+            .build()
+            .unwrap();
+        assert_eq!(config.cell_size_mm(), (80.0, 20.0));
+        assert_eq!(config.page_count(100), 1);
+        assert_eq!(config.cell(99), cell(1, 100, 1));
+
+        let unrolled = config.unrolled(3);
+        assert_eq!(unrolled.num_per_page(), 3);
+        assert_eq!(unrolled.page_size_mm(), (100.0, 10.0 + 64.0 + 10.0));
+        assert_eq!(unrolled.cell_size_mm(), (80.0, 20.0));
+    }
+
+    #[test]
+    fn page_count_for_exactly_full_pages() {
+        let config = config();
+        assert_eq!(config.page_count(0), 0);
+        assert_eq!(config.page_count(1), 1);
+        assert_eq!(config.page_count(6), 1);
+        assert_eq!(config.page_count(7), 2);
+        assert_eq!(config.page_count(12), 2);
+    }
+
+    #[test]
+    fn page_count_includes_skipped_cells() {
+        let config = builder().skip(5).build().unwrap();
+        assert_eq!(config.page_count(1), 1);
+        assert_eq!(config.page_count(2), 2);
+    }
+
+    #[test]
+    fn cells_fill_rows_then_pages() {
+        let config = config();
+        assert_eq!(config.cell(0), cell(1, 1, 1));
+        assert_eq!(config.cell(2), cell(1, 1, 3));
+        assert_eq!(config.cell(3), cell(1, 2, 1));
+        assert_eq!(config.cell(5), cell(1, 2, 3));
+        assert_eq!(config.cell(6), cell(2, 1, 1));
+    }
+
+    #[test]
+    fn assignments_start_after_skipped_cells() {
+        let config = builder().skip(2).build().unwrap();
+        let assignments: Vec<_> = config.assignments(2).collect();
+        assert_eq!(
+            assignments,
+            [
+                (cell(1, 1, 1), LabelSlot::Skipped),
+                (cell(1, 1, 2), LabelSlot::Skipped),
+                (cell(1, 1, 3), LabelSlot::Label(0)),
+                (cell(1, 2, 1), LabelSlot::Label(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn assignments_span_pages() {
+        let config = builder().skip(4).build().unwrap();
+        let last = config.assignments(5).last().unwrap();
+        assert_eq!(last, (cell(2, 1, 3), LabelSlot::Label(4)));
+    }
+
+    #[test]
+    fn skip_beyond_a_page_leaves_blank_pages() {
+        let config = builder().skip(7).build().unwrap();
+        let (first_label, _) = config
+            .assignments(1)
+            .find(|(_, slot)| *slot == LabelSlot::Label(0))
+            .unwrap();
+        assert_eq!(first_label, cell(2, 1, 2));
+        assert_eq!(config.page_count(1), 2);
+    }
+
+    #[test]
+    fn cell_size_without_spacing() {
+        assert_eq!(config().cell_size_mm(), (80.0 / 3.0, 40.0));
+    }
+
+    #[test]
+    fn cell_size_and_origin_with_spacing() {
+        let config = builder().spacing_mm(10.0, 4.0).build().unwrap();
+        assert_eq!(config.cell_size_mm(), (24.0, 35.0));
+        assert_eq!(config.cell_origin_mm(cell(1, 1, 1)), (10.0, 10.0));
+        assert_eq!(config.cell_origin_mm(cell(3, 2, 3)), (66.0, 55.0));
+    }
+
+    #[test]
+    fn printed_page_matches_page_size() {
+        let style = builder().page_size_mm(62.0, 100.0).build().unwrap();
+        assert!(style.configurable_style().contains("size: 62mm 100mm;"));
+    }
+}
@@ -0,0 +1,66 @@
+//! Create custom label sheets from a Homebox instance.
+//!
+//! This crate provides the pieces used by the `homebox-label-maker`
+//! command line tool, so that other programs can generate label sheets
+//! themselves:
+//!
+//! - [`api`]: a client for the Homebox API, for fetching items and labels
+//! - [`asset_list`]: parsing lists of asset IDs such as
+//!   `000-001--000-010,000-015`
+//! - [`layout`]: describing how labels are arranged on a page, with a
+//!   [`layout::SheetConfig`] checked when it is built
+//! - [`error`]: the errors returned, to match on the cause of a failure
+//! - [`image`]: recognising the format of label images
+//! - [`render`]: rendering a sheet of labels to an output format, such
+//!   as [`html`]
+//! - `label`: rendering labels locally with a QR code, behind the
+//!   `local` feature
+//! - `avery`: a mail merge data source for Avery Design & Print or
+//!   Word, behind the `avery` feature
+//! - `dymo`: DYMO label files, behind the `dymo` feature
+//! - `ptouch`: a database for Brother P-touch Editor linking to the
+//!   label images, behind the `ptouch` feature
+//! - `optimize`: making the PNG labels from the server smaller, behind
+//!   the `optimize` feature
+//! - `verify`: reading back the QR codes on PNG labels, behind the
+//!   `verify` feature
+//!
+//! The command line interface is behind the default `cli` feature.
+//! Bindings for use from JavaScript when built for wasm32 are behind the
+//! `wasm` feature, a C interface is behind the `ffi` feature, and a
+//! Python module is behind the `python` feature.
+
+#![warn(clippy::pedantic)]
+#![allow(clippy::must_use_candidate)]
+
+#[cfg(feature = "client")]
+pub mod api;
+pub mod asset_list;
+#[cfg(feature = "avery")]
+pub mod avery;
+#[cfg(any(feature = "ffi", feature = "python"))]
+mod bindings;
+#[cfg(feature = "dymo")]
+pub mod dymo;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod hash;
+#[cfg(feature = "html")]
+pub mod html;
+pub mod i18n;
+pub mod image;
+#[cfg(feature = "local")]
+pub mod label;
+pub mod layout;
+#[cfg(feature = "optimize")]
+pub mod optimize;
+#[cfg(feature = "ptouch")]
+pub mod ptouch;
+#[cfg(feature = "python")]
+mod python;
+pub mod render;
+#[cfg(feature = "verify")]
+pub mod verify;
+#[cfg(feature = "wasm")]
+pub mod wasm;
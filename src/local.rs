@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+
+use homebox_label_maker::{
+    api::{HomeboxClient, Item},
+    asset_list::AssetId,
+    i18n::Lang,
+    image,
+    label::{self, LabelData, LabelOptions},
+};
+
+use crate::failure::Failure;
+
+/// What to show on each label beyond the asset ID, name and location
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "each bool is an independent command line flag"
+)]
+pub struct Content<'a> {
+    /// Shown before the asset ID, to identify the server the item is
+    /// from
+    pub prefix: Option<&'a str>,
+    /// The custom fields to show, if the item has them
+    pub fields: &'a [String],
+    /// Whether to show the purchase date and warranty expiry
+    pub warranty: bool,
+    /// Whether to show the manufacturer, model and serial number
+    pub service: bool,
+    /// Whether to show the quantity of the item
+    pub quantity: bool,
+    /// Whether to number the labels of assets listed more than once,
+    /// e.g. `2/4`, as one for each unit of the item
+    pub units: bool,
+    pub lang: Lang,
+}
+
+impl Content<'_> {
+    /// Whether any of the content is only included in the full item
+    fn needs_full_item(&self) -> bool {
+        !self.fields.is_empty() || self.warranty || self.service
+    }
+
+    /// The extra lines to show for an item
+    fn details(&self, item: &Item) -> Vec<(String, String)> {
+        let mut details = vec![];
+        for name in self.fields {
+            if let Some(value) = item.field(name) {
+                details.push((name.clone(), value));
+            }
+        }
+        if self.service {
+            for (caption, value) in [
+                (self.lang.manufacturer(), &item.manufacturer),
+                (self.lang.model(), &item.model_number),
+                (self.lang.serial(), &item.serial_number),
+            ] {
+                if !value.is_empty() {
+                    details.push((caption.to_string(), value.clone()));
+                }
+            }
+        }
+        if self.warranty {
+            if let Some(date) = item.purchase_date() {
+                details.push((self.lang.purchased().to_string(), date.to_string()));
+            }
+            if item.lifetime_warranty {
+                details.push((
+                    self.lang.warranty().to_string(),
+                    self.lang.lifetime().to_string(),
+                ));
+            } else if let Some(date) = item.warranty_expiry() {
+                details.push((self.lang.warranty().to_string(), date.to_string()));
+            }
+        }
+        details
+    }
+}
+
+/// Render labels for every asset in the list locally, sized to fill a
+/// cell, using the item details from the server
+pub fn render_labels(
+    client: &HomeboxClient,
+    asset_ids: &[AssetId],
+    options: &LabelOptions,
+    content: &Content<'_>,
+    cell_size_mm: (f64, f64),
+) -> anyhow::Result<Vec<bytes::Bytes>> {
+    let items = client.list_items().context("Failed to list items")?;
+
+    let mut labels = vec![];
+    let mut units: HashMap<AssetId, (usize, usize)> = HashMap::new();
+    if content.units {
+        for &asset_id in asset_ids {
+            units.entry(asset_id).or_default().1 += 1;
+        }
+    }
+    for &asset_id in asset_ids {
+        let Some(item) = items
+            .iter()
+            .find(|item| item.asset_id == asset_id.to_string())
+        else {
+            Err(Failure::AssetNotFound)
+                .with_context(|| format!("No item has asset ID {asset_id}"))?
+        };
+
+        // Only fetch the full item when it is needed, as it is a request
+        // per item
+        let mut details = if content.needs_full_item() {
+            let full = client
+                .get_item(&item.id)
+                .with_context(|| format!("Failed to get details of {}", item.name))?;
+            content.details(&full)
+        } else {
+            vec![]
+        };
+        if content.quantity {
+            details.push((
+                content.lang.quantity().to_string(),
+                item.quantity.to_string(),
+            ));
+        }
+        let mut name = item.name.clone();
+        if let Some((unit, count)) = units.get_mut(&asset_id)
+            && *count > 1
+        {
+            *unit += 1;
+            name = format!("{name} ({unit}/{count})");
+        }
+
+        tracing::info!("Rendering label for asset ID: {asset_id}");
+        let data = LabelData {
+            prefix: content.prefix.map(str::to_string),
+            asset_id,
+            name,
+            location: item.location.as_ref().map(|l| l.name.clone()),
+            details,
+        };
+        labels.push(label::render(&data, options, cell_size_mm)?);
+    }
+    Ok(labels)
+}
+
+/// The ratio of width to height of the labels rendered by the server.
+///
+/// Homebox takes its label settings from its environment and does not
+/// offer them through the API, so they are inferred from the size of the
+/// label the server renders for an asset, whether PNG or SVG.
+pub fn server_aspect_ratio(client: &HomeboxClient, asset_id: AssetId) -> anyhow::Result<f64> {
+    let data = client
+        .get_asset_label(asset_id)
+        .context("Failed to get a label from the server to match")?;
+    let (width, height) = image::dimensions(&data)
+        .context("The server's label is not a PNG or an SVG with a size")?;
+    tracing::debug!("The server renders labels at {width}x{height}");
+    Ok(width / height)
+}
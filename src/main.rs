@@ -1,265 +1,1777 @@
 #![warn(clippy::pedantic)]
 
-use std::{fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs,
+    hash::{BuildHasher, Hasher, RandomState},
+    io,
+    path::{self, Path, PathBuf},
+    process::ExitCode,
+    thread,
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, anyhow};
-use base64::{Engine, prelude::BASE64_STANDARD};
-use build_html::{Html, HtmlContainer, HtmlElement, HtmlPage, HtmlTag};
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
 use clap_verbosity_flag::Verbosity;
-
-use crate::{
-    api::{LoginReq, LoginRes},
-    asset_list::Validate,
+use homebox_label_maker::{
+    api::{HomeboxClient, LocationNode},
+    asset_list::{self, AssetId, Validate},
+    avery, dymo,
+    error::{ApiError, RenderError},
+    html::{self, IndexEntry},
+    i18n::Lang,
+    image,
+    label::{self, LabelOptions},
+    layout::{LabelSlot, Layout, SheetConfig},
+    optimize, ptouch,
+    render::{
+        AveryRenderer, DymoRenderer, HtmlRenderer, PtouchRenderer, Registry, Renderer, Sheet,
+    },
+    verify,
 };
 
-mod api;
-mod asset_list;
+use crate::failure::Failure;
+
+mod audit;
+mod cache;
+mod colors;
+mod complete;
+mod credentials;
+mod daemon;
+mod diagnose;
+mod failure;
+mod glabels;
+mod group;
+#[cfg(feature = "gui")]
+mod gui;
+mod history;
+mod index;
+mod journal;
+mod labeled;
+mod local;
+mod manifest;
+mod merge;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "pick")]
+mod pick;
+mod preview;
+mod print;
+mod profile;
+mod report;
+mod serve;
+mod sheet_state;
+mod sync;
+mod tags;
+mod template;
+mod watch;
 
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
-    /// The URL of the Homebox server
-    #[arg(long, short)]
-    server: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// The username for the Homebox server
-    #[arg(long, short)]
-    username: String,
+    #[command(flatten)]
+    generate: Option<GenerateArgs>,
 
-    /// The password for the Homebox server. It is discouraged to
-    /// provide the password through the command line - by omitting it,
-    /// it will be requested on execution.
-    #[arg(long, short)]
-    password: Option<String>,
+    /// The language to use for the output and messages. Defaults to the
+    /// language of the system locale.
+    #[arg(long, global = true)]
+    lang: Option<Lang>,
+
+    /// The format to write logs in
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    #[command(flatten)]
+    verbose: Verbosity,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the generated sheet on localhost, allowing the layout to
+    /// be tuned from the browser in real time
+    Preview(Box<preview::PreviewArgs>),
+
+    /// Print labels for every item created since the last sync with the
+    /// server, e.g. from a weekly scheduled job. The first sync prints
+    /// every item.
+    Sync(Box<sync::SyncArgs>),
+
+    /// Print labels from several servers onto shared sheets, rendering
+    /// them locally with a prefix identifying each server
+    Merge(Box<merge::MergeArgs>),
+
+    /// Print a sticker for each label (tag), with a QR code linking to
+    /// the items it is applied to, e.g. for storage areas by category
+    Tags(Box<tags::TagsArgs>),
+
+    /// Keep running, printing a label for each new item with an asset
+    /// ID as soon as it appears on the server
+    Daemon(Box<daemon::DaemonArgs>),
+
+    /// Serve an HTTP API generating sheets on request, for other
+    /// services to use
+    Serve(Box<serve::ServeArgs>),
+
+    /// Show the sheets generated before
+    History(history::HistoryArgs),
+
+    /// Generate a sheet from the history again, e.g. after a printer
+    /// jam
+    Reprint(Box<history::ReprintArgs>),
+
+    /// List the items that have no asset ID, so they can be fixed
+    /// before printing
+    Audit(audit::AuditArgs),
+
+    /// List the templates for common label sheets, or show the layout
+    /// of one
+    Templates(template::TemplatesArgs),
+
+    /// Show or clear the labels cached from each server
+    Cache(cache::CacheArgs),
+
+    /// Check that the server can be reached, the credentials work and
+    /// labels can be downloaded, explaining the first problem found
+    Verify(diagnose::VerifyArgs),
+
+    /// Choose items to print from a list that can be searched by
+    /// typing, and print the asset list for them, to generate labels
+    /// with `homebox-label-maker ... "$(homebox-label-maker pick ...)"`
+    #[cfg(feature = "pick")]
+    Pick(pick::PickArgs),
+
+    /// Open the desktop interface
+    #[cfg(feature = "gui")]
+    Gui,
+}
+
+#[derive(clap::Args)]
+#[allow(
+    clippy::struct_excessive_bools,
+    reason = "each bool is an independent command line flag"
+)]
+struct GenerateArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
 
     /// The assets to generate labels for. This can be given as an
     /// individual, a range (using -- to join the start and end
-    /// elements), or a list of both, e.g. 000-000--000-010,000-015
-    #[arg(index = 1)]
+    /// elements), or a list of both, e.g. 000-000--000-010,000-015.
+    /// Prefix with @ to read the list from a file, e.g. @assets.txt
+    // clap leaves the group of a struct with nested flattens empty, so
+    // join it explicitly to let `Option<GenerateArgs>` detect presence
+    #[arg(
+        index = 1,
+        group = "GenerateArgs",
+        add = clap_complete::ArgValueCompleter::new(complete::assets),
+    )]
     assets: String,
 
-    /// The file path to output the result to.
+    /// The file path to output the result to, or `-` to write it to
+    /// stdout
     #[arg(index = 2)]
     output_html: PathBuf,
 
-    /// The width of the page, in millimeters
-    #[arg(long, default_value_t = 210.0)]
-    page_width_mm: f64,
+    /// The format to write the output in. Defaults to the format of the
+    /// output file's extension, or else HTML. `ptouch` writes a CSV
+    /// database to merge into a Brother P-touch Editor layout, and
+    /// `avery` a CSV of each label's item for Avery Design & Print or
+    /// Word mail merge.
+    #[arg(
+        long,
+        value_parser = clap::builder::PossibleValuesParser::new(Registry::default().names()),
+    )]
+    format: Option<String>,
+
+    #[command(flatten)]
+    layout: Layout,
 
-    /// The height of the page, in millimeters
-    #[arg(long, default_value_t = 297.0)]
-    page_height_mm: f64,
+    /// A TOML file providing layout options, using the same names as
+    /// the layout flags, e.g. `grid_rows = 13`, or the name of a
+    /// built-in template or one saved in the configuration directory,
+    /// as shown by `templates list`. Built-in templates can also be given
+    /// by product code alone, e.g. `3474`. A gLabels template file can also be
+    /// given, followed by `#` and the part number if it has several, e.g.
+    /// `avery-us-templates.xml#5160`. Values in the template take
+    /// precedence over flags.
+    #[arg(long, short)]
+    template: Option<PathBuf>,
 
-    /// The margin at the top of the page before the first row, in
-    /// millimeters
-    #[arg(long, default_value_t = 10.0)]
-    page_margin_top_mm: f64,
+    /// Keep running, regenerating the output whenever the template or
+    /// assets file changes
+    #[arg(long)]
+    watch: bool,
 
-    /// The margin to the left of the page, before the first column, in
-    /// millimeters
-    #[arg(long, default_value_t = 5.0)]
-    page_margin_left_mm: f64,
+    /// Open the print dialog as soon as the HTML output is opened, and
+    /// add a button to open it again
+    #[arg(long)]
+    auto_print: bool,
 
-    /// The margin at the bottom of the page after the last row, in
-    /// millimeters
-    #[arg(long, default_value_t = 10.0)]
-    page_margin_bottom_mm: f64,
+    /// Text to print in the top margin of each page. `{page}` and
+    /// `{pages}` are replaced by the page number and the number of
+    /// pages, `{batch}` by the name of the output file, `{date}` by
+    /// today's date and `{server}` by the server.
+    #[arg(long, value_name = "TEXT")]
+    header: Option<String>,
 
-    /// The margin to the right of the page, after the last column, in
-    /// millimeters
-    #[arg(long, default_value_t = 5.0)]
-    page_margin_right_mm: f64,
+    /// Text to print in the bottom margin of each page, with the same
+    /// replacements as the header
+    #[arg(long, value_name = "TEXT")]
+    footer: Option<String>,
 
-    /// The number of rows in the grid
-    #[arg(long, default_value_t = 13)]
-    grid_rows: usize,
+    /// Where to place each label in its cell when the label and cell
+    /// are different shapes, rather than in the centre
+    #[arg(long, value_enum, default_value_t)]
+    align: html::Align,
 
-    /// The number of columns in the grid
-    #[arg(long, default_value_t = 5)]
-    grid_columns: usize,
+    /// Tile this many pages across and down each printed sheet, e.g.
+    /// `2x2` to print four A6 pages on A4, with cut lines between them
+    #[arg(long, value_name = "COLUMNSxROWS", value_parser = parse_n_up, conflicts_with = "split_pages")]
+    n_up: Option<(usize, usize)>,
 
-    /// The spacing between each grid row, in millimeters
-    #[arg(long, default_value_t = 0.0)]
-    grid_row_spacing_mm: f64,
+    /// Add a bookmark for each page, or each group with `--group-by`, to
+    /// the outline of a PDF printed from the output
+    #[arg(long)]
+    bookmarks: bool,
 
-    /// The spacing between each grid column, in millimeters
-    #[arg(long, default_value_t = 2.5)]
-    grid_col_spacing_mm: f64,
+    /// The title of the document, which is also taken for the PDF when
+    /// it is printed to one. Defaults to the title for the language
+    /// followed by the assets.
+    #[arg(long)]
+    title: Option<String>,
 
-    /// Skip the first n elements of the grid to make better use of
-    /// partially used sheets
-    #[arg(long, short = 'S', default_value_t = 0)]
-    grid_skip: usize,
+    /// Save the label images in this directory, relative to the output
+    /// file, and link to them rather than embedding them in the HTML.
+    /// Required when exporting for P-touch Editor or mail merge, which
+    /// can only link to images.
+    #[arg(long, value_name = "DIR", value_parser = relative_path)]
+    assets_dir: Option<PathBuf>,
 
-    #[command(flatten)]
-    verbose: Verbosity,
+    /// The DYMO label product to print on when exporting DYMO label
+    /// files, as DYMO Connect names it
+    #[arg(long, value_name = "NAME", default_value = "30252 Address")]
+    dymo_paper: String,
+
+    /// Write each page to its own file, numbered after the output file,
+    /// e.g. `labels-001.html`, `labels-002.html`, ...
+    #[arg(long)]
+    split_pages: bool,
+
+    /// Open the output in the system default application once it has
+    /// been generated
+    #[arg(long)]
+    open: bool,
+
+    /// Authenticate and validate the asset list, then print a plan of
+    /// the pages that would be produced without downloading labels or
+    /// writing any output
+    #[arg(long, conflicts_with_all = ["watch", "open", "print"])]
+    dry_run: bool,
+
+    /// Fail before writing anything if the sheet looks wrong, e.g. the
+    /// labels do not fit the cells or printable area, or any labels
+    /// could not be got, rather than only warning
+    #[arg(long)]
+    strict: bool,
+
+    /// Submit the output to the system print queue using `lp` (or
+    /// `lpr`) once it has been generated. The print system must be able
+    /// to handle the output format.
+    #[arg(long)]
+    print: bool,
+
+    /// The number of copies to print
+    #[arg(long, requires = "print", default_value_t = 1)]
+    copies: usize,
+
+    /// The printer to print to, rather than the system default
+    #[arg(long, requires = "print")]
+    printer: Option<String>,
+
+    /// The printer profile to correct the layout for, from
+    /// `printers.toml` in the configuration directory, with the margins
+    /// the printer cannot print on and how far off it prints
+    #[arg(long, value_name = "NAME")]
+    printer_profile: Option<String>,
+
+    /// A file recording how many cells of the current sheet have been
+    /// used. The grid skip is taken from it, and it is advanced after
+    /// each run, so partially used sheets are picked up automatically.
+    #[arg(long, conflicts_with_all = ["grid_skip", "watch"])]
+    sheet_state: Option<PathBuf>,
+
+    /// Treat the assets as the name or ID of a location, and generate a
+    /// label for it and every location within it
+    #[arg(long, conflicts_with_all = ["watch", "resume", "report"])]
+    location_tree: bool,
+
+    /// Once the output has been written, set a custom field on each
+    /// item (named "Labeled" unless given) to the date and output file,
+    /// so Homebox records which items have labels
+    #[arg(long, num_args = 0..=1, default_missing_value = "Labeled", conflicts_with = "location_tree")]
+    mark_labeled: Option<String>,
+
+    /// Render the labels locally with a QR code, the asset ID, name and
+    /// location of each item, rather than downloading them from the
+    /// server
+    #[arg(long, conflicts_with = "location_tree")]
+    local: bool,
+
+    /// The text encoded in the QR code of locally rendered labels, with
+    /// `{asset_id}` replaced by the asset ID, e.g.
+    /// `https://inventory.example.com/a/{asset_id}`. Use `{asset_id}`
+    /// alone to encode the raw ID. Defaults to the item's page on the
+    /// server.
+    #[arg(long, requires = "local")]
+    qr_url: Option<String>,
+
+    /// Render labels locally in the same proportions as the labels the
+    /// server produces, rather than filling each cell. Homebox does not
+    /// share its label settings, so the proportions are measured from the
+    /// server's label for the first asset
+    #[arg(long, requires = "local")]
+    match_server: bool,
+
+    /// Render labels locally in large, bold print with a QR code of
+    /// larger modules, for reading at a distance or with poor sight
+    #[arg(long, requires = "local")]
+    large_print: bool,
+
+    /// How much of the QR code on locally rendered labels can be damaged
+    /// and still read. Higher levels suit labels that get scuffed, but
+    /// make the code denser. Defaults to `m`, or `l` for large print.
+    #[arg(long, value_name = "LEVEL", requires = "local")]
+    error_correction: Option<label::ErrorCorrection>,
+
+    /// The width of each module of the QR code in millimeters, rather
+    /// than filling the height of the label, to keep codes on small
+    /// labels scannable
+    #[arg(long, value_name = "MM", requires = "local")]
+    qr_module_mm: Option<f64>,
+
+    /// The width of the blank margin around the QR code, in modules.
+    /// Scanners need at least one, and read best with four.
+    #[arg(long, value_name = "MODULES", default_value_t = 1, requires = "local")]
+    qr_quiet_zone: usize,
+
+    /// Print the asset ID beneath the QR code of locally rendered labels,
+    /// as the server does, so they can still be read if the code is
+    /// damaged. The font size in millimeters can be given, or is 2.5.
+    #[arg(
+        long,
+        value_name = "SIZE_MM",
+        num_args = 0..=1,
+        default_missing_value = "2.5",
+        requires = "local"
+    )]
+    id_under_code: Option<f64>,
+
+    /// A custom field to show on locally rendered labels, if the item
+    /// has it. Can be given more than once.
+    #[arg(long = "field", value_name = "NAME", requires = "local")]
+    fields: Vec<String>,
+
+    /// Show the purchase date and warranty expiry on locally rendered
+    /// labels
+    #[arg(long, requires = "local")]
+    warranty: bool,
+
+    /// Show the manufacturer, model and serial number on locally
+    /// rendered labels, so they double as service stickers
+    #[arg(long, requires = "local")]
+    service: bool,
+
+    /// Show the quantity of each item on locally rendered labels
+    #[arg(long, requires = "local")]
+    quantity: bool,
+
+    /// Print a label for each unit of items with a quantity above one,
+    /// numbered e.g. `(2/4)` after the name on locally rendered labels
+    #[arg(long)]
+    per_unit: bool,
+
+    /// Recompress the labels downloaded from the server before embedding
+    /// them, to make the output smaller
+    #[arg(long, conflicts_with = "local")]
+    optimize_images: bool,
+
+    /// Read back the QR code on each label downloaded from the server,
+    /// and stop before writing the output if any does not match its
+    /// asset. Labels that are not PNGs cannot be checked.
+    #[arg(long, conflicts_with_all = ["local", "prefer_svg"])]
+    verify: bool,
+
+    /// Ask the server for SVG labels, if it can produce them, so QR
+    /// codes print sharply at any size. SVG labels cannot be verified or
+    /// written to DYMO files.
+    #[arg(long, conflicts_with = "local")]
+    prefer_svg: bool,
+
+    /// Also shrink the labels to the size of their cell at this
+    /// resolution, in dots per inch, when optimizing them
+    #[arg(long, value_name = "DPI", requires = "optimize_images")]
+    max_dpi: Option<u32>,
+
+    /// Sort the labels into groups, placing a label with the name of
+    /// each group before its labels
+    #[arg(long, conflicts_with_all = ["location_tree", "report"])]
+    group_by: Option<group::GroupBy>,
+
+    /// Also write an index to this file, listing the asset ID, name and
+    /// location of every label, to keep alongside the printed sheets
+    #[arg(long, conflicts_with = "location_tree")]
+    index: Option<PathBuf>,
+
+    /// Print a contents page before the labels, listing the name of each
+    /// asset and the cell its label is placed in
+    #[arg(long, conflicts_with_all = ["location_tree", "group_by", "split_pages", "watch"])]
+    toc: bool,
+
+    /// Follow each page with its back, mirrored, giving the name and
+    /// location of the item behind each label, to print on both sides
+    /// of the sheet
+    #[arg(
+        long,
+        conflicts_with_all = ["location_tree", "group_by", "split_pages", "watch", "n_up"],
+    )]
+    duplex_backs: bool,
+
+    /// Tint each label with a color for its item's location, so labels
+    /// from the same place match at a glance. Colors are taken from
+    /// `colors.toml` in the configuration directory, and otherwise made
+    /// up from the location's name.
+    #[arg(
+        long,
+        value_name = "TINT",
+        num_args = 0..=1,
+        default_missing_value = "border",
+        conflicts_with_all = ["location_tree", "group_by", "split_pages", "watch"],
+    )]
+    color_by_location: Option<html::Tint>,
+
+    /// Mark each label with a dot in the color of each of its item's
+    /// labels (tags) that has a color set in Homebox
+    #[arg(long, conflicts_with_all = ["location_tree", "group_by", "split_pages", "watch"])]
+    tag_colors: bool,
+
+    /// Also write a manifest to this file, giving the page, row and
+    /// column of each asset's label, to identify the labels on a
+    /// partly used sheet later. Written as CSV if the file name ends in
+    /// `.csv`, and otherwise as JSON.
+    #[arg(long, conflicts_with_all = ["location_tree", "group_by"])]
+    manifest: Option<PathBuf>,
+
+    /// Assign asset IDs to any items without one, using the next free
+    /// IDs, and add their labels to the run
+    #[arg(long, conflicts_with = "location_tree")]
+    assign_missing_ids: bool,
+
+    /// Continue an interrupted run for the same output, reusing the
+    /// labels it had already downloaded
+    #[arg(long)]
+    resume: bool,
+
+    /// Emit a machine-readable report of the run, to stdout unless a
+    /// report file is given. A report file is needed when the output is
+    /// written to stdout.
+    #[arg(long)]
+    report: Option<report::Format>,
+
+    /// The file path to write the report to
+    #[arg(long, requires = "report")]
+    report_file: Option<PathBuf>,
+
+    /// Leave out anything that changes between runs, so the same inputs
+    /// always produce identical files. `{date}` is taken from
+    /// `SOURCE_DATE_EPOCH` if set, and is otherwise left empty, and the
+    /// report leaves out the duration of the run.
+    #[arg(long)]
+    deterministic: bool,
 }
 
-fn main() -> anyhow::Result<()> {
+impl GenerateArgs {
+    /// The renderers to choose the output format from, with the output
+    /// options applied
+    fn registry(
+        &self,
+        entries: &[IndexEntry],
+        groups: &[(usize, String)],
+        lang: Lang,
+    ) -> anyhow::Result<Registry> {
+        let entries_if = |wanted: bool| if wanted { entries.to_vec() } else { vec![] };
+        let colors = match self.color_by_location {
+            Some(tint) => {
+                let colors = colors::Colors::load()?;
+                entries
+                    .iter()
+                    .map(|entry| {
+                        let location = entry.location.as_ref()?;
+                        Some(colors.location(location, tint))
+                    })
+                    .collect()
+            }
+            None => vec![],
+        };
+        let mut registry = Registry::default();
+        registry.register(HtmlRenderer {
+            options: html::Options {
+                auto_print: self.auto_print,
+                assets_dir: self.assets_dir.as_ref().map(|dir| {
+                    dir.components()
+                        .map(|part| part.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/")
+                }),
+                header: self.header.as_deref().map(|text| self.fill_margin(text)),
+                footer: self.footer.as_deref().map(|text| self.fill_margin(text)),
+                contents: entries_if(self.toc),
+                backs: entries_if(self.duplex_backs),
+                align: self.align,
+                metadata: self.metadata(lang),
+                n_up: self.n_up,
+                colors,
+                tint: self.color_by_location.unwrap_or_default(),
+                dots: if self.tag_colors {
+                    entries
+                        .iter()
+                        .map(|entry| entry.tag_colors.clone())
+                        .collect()
+                } else {
+                    vec![]
+                },
+                outline: match (self.bookmarks, groups.is_empty()) {
+                    (false, _) => html::Outline::None,
+                    (true, true) => html::Outline::Pages,
+                    (true, false) => html::Outline::Groups(groups.to_vec()),
+                },
+            },
+        });
+        registry.register(DymoRenderer {
+            options: dymo::Options {
+                paper_name: self.dymo_paper.clone(),
+            },
+        });
+        // Databases are opened from anywhere, so they link to the
+        // images by their full path
+        let image_dir = self.assets_dir.as_ref().and_then(|dir| {
+            let dir = path::absolute(self.output_html.with_file_name(dir)).ok()?;
+            Some(dir.to_string_lossy().into_owned())
+        });
+        registry.register(PtouchRenderer {
+            options: ptouch::Options {
+                image_dir: image_dir.clone(),
+            },
+        });
+        registry.register(AveryRenderer {
+            options: avery::Options {
+                image_dir,
+                entries: entries.to_vec(),
+            },
+        });
+        Ok(registry)
+    }
+
+    /// The details written in the head of the document, so archived
+    /// sheets can be searched for by their batch of assets
+    fn metadata(&self, lang: Lang) -> html::Metadata {
+        html::Metadata {
+            title: Some(
+                self.title
+                    .clone()
+                    .unwrap_or_else(|| format!("{} {}", lang.title(), self.assets)),
+            ),
+            author: self.connection.username.clone(),
+            created: Some(self.date()).filter(|date| !date.is_empty()),
+            keywords: vec![format!("batch={}", self.assets)],
+        }
+    }
+
+    /// Replace the details of the run in header or footer text, leaving
+    /// the page numbers for the renderer
+    fn fill_margin(&self, text: &str) -> String {
+        let batch = self.output_html.file_stem().unwrap_or_default();
+        text.replace("{batch}", &batch.to_string_lossy())
+            .replace("{date}", &self.date())
+            .replace("{server}", &self.connection.server)
+    }
+
+    /// Today's date, or the date from `SOURCE_DATE_EPOCH` if the output
+    /// must be deterministic
+    fn date(&self) -> String {
+        if !self.deterministic {
+            return chrono::Local::now().format("%Y-%m-%d").to_string();
+        }
+        std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|epoch| epoch.parse().ok())
+            .and_then(|epoch| chrono::DateTime::from_timestamp(epoch, 0))
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    }
+
+    /// Whether the output is written to stdout rather than a file
+    fn to_stdout(&self) -> bool {
+        self.output_html == Path::new("-")
+    }
+
+    /// The file the given page is written to: the output file itself,
+    /// unless each page is written to its own file
+    fn output_path(&self, page: usize) -> PathBuf {
+        if !self.split_pages {
+            return self.output_html.clone();
+        }
+        numbered(&self.output_html, page)
+    }
+
+    /// The layout from the flags, with the template applied if given
+    fn layout(&self) -> anyhow::Result<SheetConfig> {
+        let mut config = self.printed_layout()?.build().context(Failure::Layout)?;
+        if let Some(path) = &self.sheet_state {
+            let state = sheet_state::SheetState::load(path)?;
+            let skip = state.used_cells % config.num_per_page();
+            config = config.with_skip(skip);
+            tracing::debug!("Skipping {} cells from sheet state", config.grid_skip());
+        }
+        Ok(config)
+    }
+
+    /// The layout given on the command line, with the template applied
+    fn base_layout(&self) -> anyhow::Result<Layout> {
+        match &self.template {
+            Some(name) => template::Template::find(name)?.apply(&self.layout),
+            None => Ok(self.layout.clone()),
+        }
+        .context(Failure::Layout)
+    }
+
+    /// The layout given on the command line, corrected for the printer
+    /// if a profile is given
+    fn printed_layout(&self) -> anyhow::Result<Layout> {
+        let layout = self.base_layout()?;
+        Ok(match &self.printer_profile {
+            Some(name) => profile::PrinterProfile::load(name)
+                .and_then(|profile| profile.apply(&layout))
+                .context(Failure::Layout)?,
+            None => layout,
+        })
+    }
+}
+
+/// How to connect to a server, given on the command line or in the jobs
+/// file for `merge`
+#[derive(clap::Args, serde::Deserialize)]
+struct ConnectionArgs {
+    /// The URL of the Homebox server
+    #[arg(long, short)]
+    server: String,
+
+    /// The username for the Homebox server. If omitted, it is read from
+    /// the entry for the server in ~/.netrc, or else requested on
+    /// execution
+    #[arg(long, short)]
+    username: Option<String>,
+
+    /// The password for the Homebox server. It is discouraged to
+    /// provide the password through the command line - by omitting it,
+    /// it will be requested on execution.
+    #[arg(long, short, group = "password_source")]
+    password: Option<String>,
+
+    /// Read the password from the first line of a file
+    #[arg(long, group = "password_source")]
+    password_file: Option<PathBuf>,
+
+    /// Read the password from the first line of stdin
+    #[arg(long, group = "password_source")]
+    #[serde(default)]
+    password_stdin: bool,
+
+    /// Run a command and use its output as the password, e.g.
+    /// "pass show homebox"
+    #[arg(long, group = "password_source")]
+    password_cmd: Option<String>,
+
+    /// Ask the server for a long-lived session, and keep its token in
+    /// the cache directory to reuse until it expires, rather than
+    /// logging in every time
+    #[arg(long)]
+    #[serde(default)]
+    stay_logged_in: bool,
+}
+
+fn main() -> ExitCode {
+    clap_complete::CompleteEnv::with_factory(Args::command).complete();
     let args = Args::parse();
-    tracing_subscriber::fmt()
-        .with_max_level(args.verbose)
-        .init();
+    // Logs are kept off stdout, which may hold the output
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(io::stderr)
+        .with_max_level(args.verbose);
+    match args.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+
+    let lang = args.lang.unwrap_or_else(Lang::detect);
+
+    let result = match args.command {
+        Some(Command::Preview(preview_args)) => preview::run(&preview_args, lang),
+        Some(Command::Sync(sync_args)) => sync::run(&sync_args, lang),
+        Some(Command::Merge(merge_args)) => merge::run(&merge_args, lang),
+        Some(Command::Tags(tags_args)) => tags::run(&tags_args, lang),
+        Some(Command::Audit(audit_args)) => audit::run(&audit_args, lang),
+        Some(Command::Templates(templates_args)) => template::run(&templates_args),
+        Some(Command::Cache(cache_args)) => cache::run(&cache_args),
+        Some(Command::Verify(verify_args)) => diagnose::run(&verify_args, lang),
+        Some(Command::Daemon(daemon_args)) => daemon::run(&daemon_args, lang),
+        Some(Command::Serve(serve_args)) => serve::run(&serve_args, lang),
+        Some(Command::History(history_args)) => history::run(&history_args),
+        Some(Command::Reprint(reprint_args)) => history::reprint(&reprint_args, lang),
+        #[cfg(feature = "pick")]
+        Some(Command::Pick(pick_args)) => pick::run(&pick_args, lang),
+        #[cfg(feature = "gui")]
+        Some(Command::Gui) => gui::run(),
+        None => generate(
+            &args
+                .generate
+                .expect("clap requires generate args without a subcommand"),
+            lang,
+        ),
+    };
 
-    let client = reqwest::blocking::Client::new();
-    let base_url = format!("{}/api", args.server);
-    tracing::debug!("Base API URL: {base_url}");
+    if let Err(e) = result {
+        eprintln!("Error: {e:?}");
+        return Failure::exit_code_for(&e);
+    }
+    ExitCode::SUCCESS
+}
+
+/// Parse a number of pages across and down a sheet, such as `2x2`
+fn parse_n_up(n_up: &str) -> Result<(usize, usize), String> {
+    let (columns, rows) = n_up
+        .split_once('x')
+        .ok_or("give the pages across and down, e.g. 2x2")?;
+    let parse = |count: &str| match count.parse() {
+        Ok(0) | Err(_) => Err("the pages across and down must be whole numbers above zero"),
+        Ok(count) => Ok(count),
+    };
+    Ok((parse(columns)?, parse(rows)?))
+}
 
-    if fs::exists(&args.output_html).context("Failed to check is output exists already")? {
+/// Check the output can be written as asked without overwriting
+/// anything, before fetching the labels
+fn check_output(args: &GenerateArgs) -> anyhow::Result<()> {
+    if args.to_stdout() && (args.split_pages || args.open || args.print || args.watch) {
         Err(anyhow!(
-            "Cannot overwrite output file! Please delete it first or change output destination."
-        ))?;
+            "Cannot split, open, print or watch the output when writing it to stdout"
+        ))
+        .context(Failure::Write)?;
     }
+    if args.to_stdout() && args.report.is_some() && args.report_file.is_none() {
+        Err(anyhow!(
+            "The report would be mixed into the output on stdout, so give --report-file to write it to"
+        ))
+        .context(Failure::Write)?;
+    }
+    match output_format(args.format.as_deref(), &args.output_html) {
+        "ptouch" | "avery" if args.assets_dir.is_none() => Err(anyhow!(
+            "Mail merge databases link to the label images, so give --assets-dir to save them in"
+        ))
+        .context(Failure::Write)?,
+        // Labels rendered locally are SVG, which DYMO software cannot show
+        "dymo" if args.local || args.prefer_svg => Err(anyhow!(
+            "DYMO labels can only show PNG, JPEG or GIF images, so cannot hold SVG labels from \
+            --local or --prefer-svg"
+        ))
+        .context(Failure::Write)?,
+        // The items are matched to the labels by their place in the list
+        "avery" if args.location_tree || args.group_by.is_some() => Err(anyhow!(
+            "Cannot export a mail merge of locations or grouped labels"
+        ))
+        .context(Failure::Write)?,
+        _ => {}
+    }
+    ensure_output_free(&args.output_path(1))?;
+    Ok(())
+}
+
+/// Generate a label sheet and write it to the output file
+fn generate(args: &GenerateArgs, lang: Lang) -> anyhow::Result<()> {
+    let started = (!args.deterministic).then(Instant::now);
+    check_output(args)?;
+    let config = args.layout()?;
+    check_layout(args, &config)?;
+
+    let template = args
+        .template
+        .as_deref()
+        .map(template::Template::find)
+        .transpose()?;
+    let watched = args
+        .watch
+        .then(|| {
+            watch::Watched::new(
+                template.as_ref().and_then(template::Template::path),
+                &args.assets,
+            )
+        })
+        .transpose()?;
 
     // 1. Authenticate
-    if args.password.is_some() {
-        tracing::warn!(
-            "The password has been provided on the command line. Note that this is less secure then providing it when requested."
-        );
+    let mut client = authenticate(&args.connection)?;
+    client.set_prefer_svg(args.prefer_svg);
+    if args.location_tree || !args.local || args.match_server {
+        client.require_label_maker()?;
     }
-    let password = args
-        .password
-        .or_else(|| {
-            tracing::debug!("Prompting for password...");
-            rpassword::prompt_password("Enter Homebox Password: ").ok()
-        })
-        .context("Failed to get password")?;
-
-    tracing::info!("Authenticating...");
-    let LoginRes { token, .. } = client
-        .post(format!("{base_url}/v1/users/login"))
-        .form(&LoginReq {
-            username: args.username,
-            password,
-            stay_logged_in: false,
-        })
-        .send()
-        .context("Failed to authenticate")?
-        .json::<LoginRes>()
-        .context("Failed to parse authentication response")?;
-    tracing::debug!("Token acquired: {token}");
-
-    // 2. Get label images
-    let list = asset_list::parse(args.assets).context("Failed to parse asset list")?;
-    tracing::debug!("Assets: {list:?}");
-    list.validate().context("Failed to validate asset list")?;
+    if args.location_tree {
+        return generate_location_tree(args, &client, &config, lang);
+    }
+    let mut asset_ids = run_assets(args, &client)?;
 
-    let mut labels = vec![];
-    for entry in list {
-        for asset_id in entry {
-            tracing::info!("Getting label for asset ID: {asset_id}");
-            let label_bytes = client
-                .get(format!(
-                    "{base_url}/v1/labelmaker/asset/{asset_id}?print=false"
-                ))
-                .header("Authorization", &token)
-                .send()
-                .context("Failed to get asset label")?
-                .error_for_status()
-                .context("Failed to get asset label (are all the provided asset IDs valid?)")?
-                .bytes()
-                .context("Failed to parse image")?;
-            labels.push(label_bytes);
+    if args.dry_run {
+        match args.report {
+            Some(format) => report::Report::new(&config, &asset_ids, started)
+                .write(format, args.report_file.as_deref())?,
+            None => print_plan(&config, asset_ids.len(), lang),
         }
+        return Ok(());
     }
 
+    // 2. Get label images, leaving out any that fail to download
+    let cache = cache::LabelCache::for_server(client.base_url())?;
+    let mut journal = if args.to_stdout() {
+        journal::Journal::in_memory(cache)
+    } else {
+        journal::Journal::open(&args.output_html, cache, args.resume)?
+    };
+    let failed = if args.local {
+        vec![]
+    } else {
+        prefetch_labels(args, &client, &mut asset_ids, &mut journal, lang)?
+    };
+    let (labels, descriptions, groups) =
+        get_labels(args, &client, &asset_ids, Some(&mut journal), lang)?;
+    warn_or_fail(args.strict, check_aspect_ratio(&config, &labels))?;
+
     // 3. Build page(s)
-    let num_per_page = args.grid_rows * args.grid_columns;
+    let entries = if args.toc
+        || args.duplex_backs
+        || args.color_by_location.is_some()
+        || args.tag_colors
+        || output_format(args.format.as_deref(), &args.output_html) == "avery"
+    {
+        index::entries(&client, &asset_ids, Some(&config))?
+    } else {
+        vec![]
+    };
+    let (config, written) = write_sheet(args, &labels, &descriptions, &groups, &entries, lang)?;
+    journal.finish()?;
+
+    if let Some(format) = args.report {
+        report::Report::new(&config, &asset_ids, started)
+            .write(format, args.report_file.as_deref())?;
+    }
+    if let Some(path) = &args.index {
+        index::write(&client, &asset_ids, path, lang)?;
+    }
+    if let Some(path) = &args.manifest {
+        manifest::write(path, &config, &asset_ids)?;
+    }
+    deliver(args, &config, labels.len(), &written, lang)?;
+
+    let mut layout = args.base_layout()?;
+    layout.grid_skip = config.grid_skip();
+    if let Err(e) = history::Batch::record(&args.connection.server, &asset_ids, layout) {
+        tracing::warn!("Failed to record the batch in the history: {e:#}");
+    }
+
+    if let Some(field) = &args.mark_labeled {
+        let batch = args.output_html.file_name().unwrap_or_default();
+        labeled::mark(&client, &asset_ids, field, &batch.to_string_lossy())?;
+    }
+    if !failed.is_empty() {
+        report_failures(&failed, lang);
+    }
+
+    if let Some(watched) = watched {
+        watch_output(
+            args,
+            &client,
+            &watched,
+            (labels, descriptions, groups),
+            lang,
+        )?;
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Failure::Incomplete)
+            .with_context(|| format!("Failed to get {} of the labels", failed.len()))
+    }
+}
+
+/// Regenerate the output whenever the template or asset list changes,
+/// until interrupted
+fn watch_output(
+    args: &GenerateArgs,
+    client: &HomeboxClient,
+    watched: &watch::Watched,
+    mut labels: Labels,
+    lang: Lang,
+) -> anyhow::Result<()> {
+    tracing::info!("Watching for changes...");
+    loop {
+        let change = watched.wait_for_change()?;
+        if change.assets {
+            match resolve_assets(&args.assets)
+                .and_then(|asset_ids| get_labels(args, client, &asset_ids, None, lang))
+            {
+                Ok(new_labels) => labels = new_labels,
+                Err(e) => {
+                    tracing::error!("Failed to refresh labels: {e:?}");
+                    continue;
+                }
+            }
+        }
+        let (images, descriptions, groups) = &labels;
+        if let Err(e) = write_sheet(args, images, descriptions, groups, &[], lang) {
+            tracing::error!("Failed to regenerate output: {e:?}");
+        }
+    }
+}
+
+/// Download the label for every asset into the journal, carrying on
+/// past any that fail. Those that failed are left out of the assets and
+/// returned with the reason, unless none are left or in strict mode.
+fn prefetch_labels(
+    args: &GenerateArgs,
+    client: &HomeboxClient,
+    asset_ids: &mut Vec<AssetId>,
+    journal: &mut journal::Journal,
+    lang: Lang,
+) -> anyhow::Result<Vec<(AssetId, anyhow::Error)>> {
+    let mut failed = vec![];
+    for &asset_id in &*asset_ids {
+        if let Err(e) = fetch_labels(client, &[asset_id], Some(journal)) {
+            tracing::warn!("{e:#}");
+            failed.push((asset_id, e));
+        }
+    }
+    asset_ids.retain(|asset_id| failed.iter().all(|(failed, _)| failed != asset_id));
+    // With nothing left to output, fail as the first label did
+    if asset_ids.is_empty() && !failed.is_empty() {
+        return Err(failed.swap_remove(0).1);
+    }
+    if args.strict && !failed.is_empty() {
+        report_failures(&failed, lang);
+        return Err(Failure::Strict).context("Refusing to write a sheet with labels left out");
+    }
+    Ok(failed)
+}
+
+/// Print the labels that were left out and why, with the asset list to
+/// retry just those
+fn report_failures(failed: &[(AssetId, anyhow::Error)], lang: Lang) {
+    eprintln!("{}", lang.labels_failed(failed.len()));
+    for (asset_id, e) in failed {
+        eprintln!("- {asset_id}: {e:#}");
+    }
+    let asset_ids: Vec<_> = failed.iter().map(|&(asset_id, _)| asset_id).collect();
+    eprintln!("{}", lang.retry_with(&asset_list::format(&asset_ids)));
+}
+
+/// Generate a label for the location named by the assets argument and
+/// for every location within it
+fn generate_location_tree(
+    args: &GenerateArgs,
+    client: &HomeboxClient,
+    config: &SheetConfig,
+    lang: Lang,
+) -> anyhow::Result<()> {
+    let root = find_location(client, &args.assets)?;
+    let locations = root.descendants();
+
+    if args.dry_run {
+        print_plan(config, locations.len(), lang);
+        return Ok(());
+    }
+
+    let mut labels = vec![];
+    let mut descriptions = vec![];
+    for location in locations {
+        tracing::info!("Getting label for location: {}", location.name);
+        labels.push(
+            client
+                .get_location_label(&location.id)
+                .with_context(|| format!("Failed to get label for location {}", location.name))?,
+        );
+        descriptions.push(lang.location_label(&location.name));
+    }
+
+    warn_or_fail(args.strict, check_aspect_ratio(config, &labels))?;
+    let (config, written) = write_sheet(args, &labels, &descriptions, &[], &[], lang)?;
+    deliver(args, &config, labels.len(), &written, lang)
+}
+
+/// Give every item without an asset ID the next free ID, returning the
+/// IDs that were assigned
+fn assign_missing_ids(client: &HomeboxClient) -> anyhow::Result<Vec<AssetId>> {
+    let missing: Vec<_> = client
+        .list_items()
+        .context("Failed to list items")?
+        .into_iter()
+        .filter(|item| !item.has_asset_id())
+        .map(|item| item.id)
+        .collect();
+    if missing.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let updated = client
+        .ensure_asset_ids()
+        .context("Failed to assign asset IDs")?;
+    tracing::info!("Assigned asset IDs to {updated} items");
+
+    let mut assigned = vec![];
+    for item in client.list_items().context("Failed to list items")? {
+        if missing.contains(&item.id) && item.has_asset_id() {
+            tracing::info!("{} is now asset {}", item.name, item.asset_id);
+            assigned.extend(
+                asset_list::parse(&item.asset_id)
+                    .context("The server returned an invalid asset ID")?
+                    .into_iter()
+                    .flatten(),
+            );
+        }
+    }
+    Ok(assigned)
+}
+
+/// Find a location by name or ID anywhere in the location tree
+fn find_location(client: &HomeboxClient, name_or_id: &str) -> anyhow::Result<LocationNode> {
+    client
+        .location_tree()
+        .context("Failed to get location tree")?
+        .iter()
+        .find_map(|node| node.find(name_or_id))
+        .cloned()
+        .ok_or_else(|| anyhow!("No location is named {name_or_id}"))
+        .context(Failure::InvalidAssets)
+}
+
+/// How many times wider or narrower labels can be than their cells
+/// before they are warned about
+const MAX_ASPECT_MISMATCH: f64 = 1.25;
+
+/// Check if the labels from the server are a very different shape to
+/// their cells, as they would be shrunk to fit, leaving large gaps
+fn check_aspect_ratio(config: &SheetConfig, labels: &[bytes::Bytes]) -> Option<String> {
+    let (width, height) = labels.iter().find_map(|label| image::dimensions(label))?;
+    let (cell_width, cell_height) = config.cell_size_mm();
+    let label_ratio = width / height;
+    let cell_ratio = cell_width / cell_height;
+    ((label_ratio / cell_ratio).max(cell_ratio / label_ratio) > MAX_ASPECT_MISMATCH).then(|| {
+        format!(
+            "Labels are {label_ratio:.2} times as wide as they are tall, but cells are \
+            {cell_ratio:.2} times, so labels will be shrunk to fit. Check the page size, \
+            margins and grid."
+        )
+    })
+}
+
+/// The space left beyond the margins when fitting the grid to the
+/// labels, in millimeters, above which it is likely to be a mistake
+const MAX_LEFTOVER_MM: f64 = 1.0;
+
+/// Check if fitting the grid to the labels leaves much more space at
+/// the right or bottom of the page than the margins given, as the
+/// margins or spacing are likely to be wrong
+fn check_leftover(layout: &Layout, config: &SheetConfig) -> Vec<String> {
+    let mut problems = vec![];
+    if !layout.auto_grid {
+        return problems;
+    }
+    let last = config.cell(config.num_per_page() - 1);
+    let (x, y) = config.cell_origin_mm(last);
+    let (width, height) = config.cell_size_mm();
+    let (page_width, page_height) = config.page_size_mm();
+    let right = page_width - x - width - layout.page_margin_right_mm;
+    let bottom = page_height - y - height - layout.page_margin_bottom_mm;
+    if right > MAX_LEFTOVER_MM {
+        problems.push(format!(
+            "{} columns of labels leave {right:.1}mm unused at the right of the page. \
+            Check the margins and spacing.",
+            last.column
+        ));
+    }
+    if bottom > MAX_LEFTOVER_MM {
+        problems.push(format!(
+            "{} rows of labels leave {bottom:.1}mm unused at the bottom of the page. \
+            Check the margins and spacing.",
+            last.row
+        ));
+    }
+    problems
+}
+
+/// Check the layout for likely mistakes, and that the labels fit within
+/// the area the printer can print on
+fn check_layout(args: &GenerateArgs, config: &SheetConfig) -> anyhow::Result<()> {
+    warn_or_fail(args.strict, check_leftover(&args.printed_layout()?, config))?;
+    if let Some(name) = &args.printer_profile {
+        let profile = profile::PrinterProfile::load(name).context(Failure::Layout)?;
+        if let Some(problem) = profile.check(config) {
+            Err(anyhow!(problem)).context(Failure::Layout)?;
+        }
+    }
+    Ok(())
+}
+
+/// Warn about each problem with the sheet, or fail on the first one in
+/// strict mode
+fn warn_or_fail(strict: bool, problems: impl IntoIterator<Item = String>) -> anyhow::Result<()> {
+    for problem in problems {
+        if strict {
+            return Err(Failure::Strict).context(problem);
+        }
+        tracing::warn!("{problem}");
+    }
+    Ok(())
+}
+
+/// Render the labels with the current layout and write the output file,
+/// or a file for each page, returning the layout used and each file
+/// written with its size in bytes
+fn write_sheet(
+    args: &GenerateArgs,
+    labels: &[bytes::Bytes],
+    descriptions: &[String],
+    groups: &[(usize, String)],
+    entries: &[IndexEntry],
+    lang: Lang,
+) -> anyhow::Result<(SheetConfig, Vec<(PathBuf, usize)>)> {
+    let config = args.layout()?;
+    if let Some(dir) = &args.assets_dir {
+        write_assets(&args.output_html.with_file_name(dir), labels)?;
+    }
+    let format = output_format(args.format.as_deref(), &args.output_html);
+    if !args.split_pages {
+        let registry = args.registry(entries, groups, lang)?;
+        let sheet = Sheet {
+            config: &config,
+            labels,
+            descriptions,
+            lang,
+        };
+        // Formats holding fewer labels than the sheet are written to
+        // several files
+        if let Some(max_labels) = registry.get(format).and_then(Renderer::max_labels)
+            && labels.len() > max_labels
+        {
+            let written = write_files_of(args, format, &sheet, entries, max_labels)?;
+            return Ok((config, written));
+        }
+        let bytes_written = render_to_file(&registry, format, &sheet, &args.output_html)?;
+        return Ok((config, vec![(args.output_html.clone(), bytes_written)]));
+    }
+
+    // Each page is written as the first page, with the cells skipped on
+    // it. Each file holds one page, so there is nothing to bookmark, and
+    // only the items on the page.
+    let mut written = vec![];
+    let (mut remaining, mut remaining_descriptions, mut remaining_entries) =
+        (labels, descriptions, entries);
+    for page in 1..=config.page_count(labels.len()) {
+        let page_config = config.clone().from_page(page);
+        let count = page_config
+            .assignments(remaining.len())
+            .filter(|(cell, slot)| cell.page == 1 && *slot != LabelSlot::Skipped)
+            .count();
+        let (page_labels, rest) = remaining.split_at(count);
+        let (page_descriptions, rest_descriptions) =
+            remaining_descriptions.split_at(count.min(remaining_descriptions.len()));
+        let (page_entries, rest_entries) =
+            remaining_entries.split_at(count.min(remaining_entries.len()));
+        let registry = args.registry(page_entries, &[], lang)?;
+        let path = args.output_path(page);
+        let sheet = Sheet {
+            config: &page_config,
+            labels: page_labels,
+            descriptions: page_descriptions,
+            lang,
+        };
+        written.push((
+            path.clone(),
+            render_to_file(&registry, format, &sheet, &path)?,
+        ));
+        (remaining, remaining_descriptions, remaining_entries) =
+            (rest, rest_descriptions, rest_entries);
+    }
+    Ok((config, written))
+}
+
+/// The path with a number added after the file name, e.g.
+/// `labels-002.html`
+fn numbered(path: &Path, number: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default();
+    let mut name = format!("{}-{number:03}", stem.to_string_lossy());
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+/// Write the labels to numbered files of as many labels as the format
+/// can hold, whatever the layout, each with the items of its own labels,
+/// returning each file written with its size in bytes
+fn write_files_of(
+    args: &GenerateArgs,
+    format: &str,
+    sheet: &Sheet<'_>,
+    entries: &[IndexEntry],
+    max_labels: usize,
+) -> anyhow::Result<Vec<(PathBuf, usize)>> {
+    let mut written = vec![];
+    for (idx, labels) in sheet.labels.chunks(max_labels).enumerate() {
+        let start = idx * max_labels;
+        let descriptions = sheet
+            .descriptions
+            .get(start..)
+            .map(|rest| &rest[..labels.len().min(rest.len())])
+            .unwrap_or_default();
+        let entries = entries
+            .get(start..)
+            .map(|rest| &rest[..labels.len().min(rest.len())])
+            .unwrap_or_default();
+        let registry = args.registry(entries, &[], sheet.lang)?;
+        let path = numbered(&args.output_html, idx + 1);
+        ensure_output_free(&path)?;
+        let file = Sheet {
+            labels,
+            descriptions,
+            ..*sheet
+        };
+        written.push((
+            path.clone(),
+            render_to_file(&registry, format, &file, &path)?,
+        ));
+    }
+    Ok(written)
+}
+
+/// Save each distinct label image in the directory, to be linked to from
+/// the output rather than embedded in it
+fn write_assets(dir: &Path, labels: &[bytes::Bytes]) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)
+        .context("Failed to create assets directory")
+        .context(Failure::Write)?;
+    for label in labels {
+        let path = dir.join(html::image_file_name(label));
+        if !path.exists() {
+            fs::write(&path, label).context(Failure::Write)?;
+        }
+    }
+    Ok(())
+}
+
+/// Accept only a relative path, for files saved next to the output
+fn relative_path(path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(path);
+    if path.is_relative() {
+        Ok(path)
+    } else {
+        Err("must be relative to the output file".to_string())
+    }
+}
+
+/// Render the labels in the given format and write them to the output
+/// file, returning the number of bytes written
+fn render_to_file(
+    registry: &Registry,
+    format: &str,
+    sheet: &Sheet<'_>,
+    path: &Path,
+) -> anyhow::Result<usize> {
+    let renderer = registry
+        .get(format)
+        .expect("clap only accepts registered formats");
     tracing::info!(
         "Producing {} pages...",
-        (args.grid_skip + labels.len()) / num_per_page + 1
+        sheet.config.page_count(sheet.labels.len())
     );
 
-    let configurable_style = format!(
-        r"
-        .page {{
-            --pad-top: {}mm;
-            --pad-left: {}mm;
-            --pad-bottom: {}mm;
-            --pad-right: {}mm;
-            width: calc({}mm - var(--pad-left) - var(--pad-right));
-            height: calc({}mm - var(--pad-top) - var(--pad-bottom));
-            padding-top: var(--pad-top);
-            padding-left: var(--pad-left);
-            padding-bottom: var(--pad-bottom);
-            padding-right: var(--pad-right);
-            grid-template-columns: repeat({}, 1fr);
-            grid-template-rows: repeat({}, 1fr);
-            row-gap: {}mm;
-            column-gap: {}mm;
-        }}
-    ",
-        args.page_margin_top_mm,
-        args.page_margin_left_mm,
-        args.page_margin_bottom_mm,
-        args.page_margin_right_mm,
-        args.page_width_mm,
-        args.page_height_mm,
-        args.grid_columns,
-        args.grid_rows,
-        args.grid_row_spacing_mm,
-        args.grid_col_spacing_mm
-    );
+    if path == Path::new("-") {
+        let mut stdout = Counted {
+            inner: io::stdout().lock(),
+            count: 0,
+        };
+        match renderer.render_to(sheet, &mut stdout) {
+            Err(RenderError::Io(e)) => return Err(e).context(Failure::Write),
+            result => result?,
+        }
+        io::Write::flush(&mut stdout).context(Failure::Write)?;
+        return Ok(stdout.count);
+    }
+
+    // Write the output as it is rendered, rather than holding it all in
+    // memory
+    let mut file = io::BufWriter::new(fs::File::create(path).context(Failure::Write)?);
+    match renderer.render_to(sheet, &mut file) {
+        Err(RenderError::Io(e)) => return Err(e).context(Failure::Write),
+        result => result?,
+    }
+    let file = file.into_inner().context(Failure::Write)?;
+    let len = file.metadata().context(Failure::Write)?.len();
+    Ok(usize::try_from(len).unwrap_or(usize::MAX))
+}
+
+/// The format given, or else the format for the extension of the output
+/// path, defaulting to HTML
+fn output_format<'a>(format: Option<&'a str>, path: &Path) -> &'a str {
+    format
+        .or_else(|| {
+            let extension = path.extension()?.to_str()?;
+            Registry::default()
+                .for_extension(extension)
+                .map(Renderer::name)
+        })
+        .unwrap_or("html")
+}
+
+/// The format of the output from a command that writes every label to
+/// one file, checking it can be written that way before any labels are
+/// fetched
+fn single_file_format<'a>(format: Option<&'a str>, path: &Path) -> anyhow::Result<&'a str> {
+    let format = output_format(format, path);
+    if Registry::self_contained().get(format).is_none() {
+        Err(anyhow!(
+            "Only generate can write {format} output, as it saves the label images or splits \
+            the labels across files"
+        ))
+        .context(Failure::Write)?;
+    }
+    Ok(format)
+}
+
+/// A writer counting the bytes written through it
+struct Counted<W> {
+    inner: W,
+    count: usize,
+}
 
-    let page = generate_html(num_per_page, configurable_style, args.grid_skip, &labels);
-    fs::write(args.output_html, page.to_html_string()).context("Failed to write output")?;
+impl<W: io::Write> io::Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
 
+/// Fail if the output file already exists, rather than overwrite it
+fn ensure_output_free(path: &Path) -> anyhow::Result<()> {
+    if path != Path::new("-")
+        && fs::exists(path).context("Failed to check is output exists already")?
+    {
+        Err(anyhow!(
+            "Cannot overwrite output file! Please delete it first or change output destination."
+        ))
+        .context(Failure::Write)?;
+    }
     Ok(())
 }
 
-/// Generate the HTML itself
-fn generate_html(
-    num_per_page: usize,
-    configurable_style: String,
-    grid_skip: usize,
-    labels: &[bytes::Bytes],
-) -> HtmlPage {
-    let mut page = HtmlPage::new()
-        .with_title("Homebox Labels")
-        .with_style(include_str!("style.css"))
-        .with_style(configurable_style);
-
-    page.add_paragraph_attr(include_str!("notice.txt"), [("class", "no-print")]);
-
-    let mut skip_first = true;
-    let mut page_div = HtmlElement::new(HtmlTag::Div).with_attribute("class", "page");
-    for i in 0..grid_skip {
-        // Create empty elems
-        if i % num_per_page == 0 {
-            // Create page div
-            if skip_first {
-                skip_first = false;
-            } else {
-                page.add_raw(page_div.to_html_string());
-            }
-            page_div = HtmlElement::new(HtmlTag::Div).with_attribute("class", "page");
+/// Record the sheet state, summarise the run, and open or print the
+/// output as requested
+fn deliver(
+    args: &GenerateArgs,
+    config: &SheetConfig,
+    labels: usize,
+    written: &[(PathBuf, usize)],
+    lang: Lang,
+) -> anyhow::Result<()> {
+    if let Some(path) = &args.sheet_state {
+        let used_cells = config.next_grid_skip(labels);
+        tracing::info!("The next run will start at cell {}", used_cells + 1);
+        sheet_state::SheetState { used_cells }.save(path)?;
+    }
+
+    // Only the output may be written to stdout when it is there
+    if !args.to_stdout() && (args.report.is_none() || args.report_file.is_some()) {
+        print_plan(config, labels, lang);
+        for (path, bytes_written) in written {
+            println!("{}", lang.wrote(*bytes_written, path));
+        }
+    }
+
+    if args.open {
+        tracing::info!("Opening output...");
+        for (path, _) in written {
+            open::that_detached(path).context("Failed to open output")?;
+        }
+    }
+
+    if args.print {
+        tracing::info!("Printing output...");
+        for (path, _) in written {
+            print::submit(path, args.copies, args.printer.as_deref())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Print where the labels are placed, for a dry run or once complete
+fn print_plan(config: &SheetConfig, labels: usize, lang: Lang) {
+    println!("{}", lang.labels(labels));
+    println!("{}", lang.pages(config.page_count(labels)));
+    let mut placed = config
+        .assignments(labels)
+        .filter(|(_, slot)| *slot != LabelSlot::Skipped)
+        .map(|(cell, _)| cell);
+    if let Some(first) = placed.next() {
+        println!("{}", lang.first_cell(first));
+        println!("{}", lang.last_cell(placed.last().unwrap_or(first)));
+    }
+    println!("{}", lang.next_grid_skip(config.next_grid_skip(labels)));
+}
+
+/// Read, parse and validate the asset list, loading it from a file if
+/// given as `@path`, and expand it into individual asset IDs
+fn resolve_assets(assets: &str) -> anyhow::Result<Vec<AssetId>> {
+    let assets = match assets.strip_prefix('@') {
+        Some(path) => fs::read_to_string(path)
+            .context("Failed to read assets file")?
+            .trim()
+            .to_string(),
+        None => assets.to_string(),
+    };
+    parse_assets(&assets)
+}
+
+/// Parse a list of assets given directly, rather than read from a file,
+/// such as one received from the network
+fn parse_assets(assets: &str) -> anyhow::Result<Vec<AssetId>> {
+    let list = asset_list::parse(assets).context(Failure::InvalidAssets)?;
+    tracing::debug!("Assets: {list:?}");
+    list.validate()
+        .context("Failed to validate asset list")
+        .context(Failure::InvalidAssets)?;
+
+    Ok(list.into_iter().flatten().collect())
+}
+
+/// Whether an error was caused by the server rejecting the session
+/// token, so logging in again may help
+fn session_expired(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<ApiError>(),
+            Some(ApiError::Unauthorized { .. })
+        )
+    })
+}
+
+/// Authenticate with the server, prompting for the password if needed
+fn authenticate(connection: &ConnectionArgs) -> anyhow::Result<HomeboxClient> {
+    if connection.stay_logged_in
+        && let Some(cached) = cache::CachedToken::load(&connection.server, &connection.username()?)
+    {
+        tracing::debug!("Reusing the session until {}", cached.expires_at);
+        return Ok(HomeboxClient::with_token(&connection.server, &cached.token));
+    }
+
+    let (username, password) = connection.credentials()?;
+    let (client, expires_at) = HomeboxClient::login_session(
+        &connection.server,
+        &username,
+        &password,
+        connection.stay_logged_in,
+    )
+    .context("Failed to authenticate")
+    .context(Failure::Auth)?;
+    if connection.stay_logged_in {
+        let saved = chrono::DateTime::parse_from_rfc3339(&expires_at)
+            .context("Server gave an invalid expiry time")
+            .and_then(|expires_at| {
+                cache::CachedToken {
+                    username,
+                    token: client.token().to_string(),
+                    expires_at: expires_at.to_utc(),
+                }
+                .save(&connection.server)
+            });
+        if let Err(e) = saved {
+            tracing::warn!("Failed to keep the session for later: {e:#}");
+        }
+    }
+    Ok(client)
+}
+
+/// Log in again after the session has expired, rather than reusing a
+/// kept token that has stopped working
+fn reauthenticate(connection: &ConnectionArgs) -> anyhow::Result<HomeboxClient> {
+    if connection.stay_logged_in {
+        cache::CachedToken::forget(&connection.server)?;
+    }
+    authenticate(connection)
+}
+
+/// The label images, a description of each, and the index and name of
+/// the divider starting each group
+type Labels = (Vec<bytes::Bytes>, Vec<String>, Vec<(usize, String)>);
+
+/// Get the labels for every asset in the list, grouping them with
+/// dividers if requested
+fn get_labels(
+    args: &GenerateArgs,
+    client: &HomeboxClient,
+    asset_ids: &[AssetId],
+    journal: Option<&mut journal::Journal>,
+    lang: Lang,
+) -> anyhow::Result<Labels> {
+    let Some(group_by) = args.group_by else {
+        let labels = get_ungrouped_labels(args, client, asset_ids, journal, lang)?;
+        return Ok((labels, describe_assets(asset_ids, lang), vec![]));
+    };
+
+    let groups = group::groups(client, asset_ids, group_by, lang)?;
+    let sorted: Vec<_> = groups.iter().flat_map(|(_, ids)| ids).copied().collect();
+    let mut fetched = get_ungrouped_labels(args, client, &sorted, journal, lang)?.into_iter();
+
+    let cell_size = args.layout()?.cell_size_mm();
+    let mut labels = vec![];
+    let mut descriptions = vec![];
+    let mut dividers = vec![];
+    for (name, ids) in groups {
+        dividers.push((labels.len(), name.clone()));
+        labels.push(label::render_divider(&name, cell_size));
+        labels.extend(fetched.by_ref().take(ids.len()));
+        descriptions.push(name);
+        descriptions.extend(describe_assets(&ids, lang));
+    }
+    Ok((labels, descriptions, dividers))
+}
+
+/// Check that the QR code on each label links to its asset, by the asset
+/// ID at the end of the link
+fn verify_labels(labels: &[bytes::Bytes], asset_ids: &[AssetId]) -> anyhow::Result<()> {
+    tracing::info!("Verifying labels...");
+    let mut unchecked = 0;
+    for (label, asset_id) in labels.iter().zip(asset_ids) {
+        let text = verify::read_qr(label)
+            .with_context(|| format!("Failed to verify label for asset {asset_id}"))
+            .context(Failure::Verify)?;
+        let Some(text) = text else {
+            unchecked += 1;
+            continue;
+        };
+        let linked = text
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default();
+        if linked != asset_id.to_string() {
+            Err(anyhow!(
+                "The label for asset {asset_id} has a QR code for {text:?}!"
+            ))
+            .context(Failure::Verify)?;
+        }
+    }
+    if unchecked > 0 {
+        tracing::warn!("{unchecked} labels are not PNGs, so could not be verified");
+    }
+    Ok(())
+}
+
+/// The assets to label in this run, with any newly assigned IDs, and
+/// repeated for each unit if asked
+fn run_assets(args: &GenerateArgs, client: &HomeboxClient) -> anyhow::Result<Vec<AssetId>> {
+    let mut asset_ids = resolve_assets(&args.assets)?;
+    if args.assign_missing_ids && !args.dry_run {
+        asset_ids.extend(assign_missing_ids(client)?);
+    }
+    if args.per_unit {
+        asset_ids = per_unit(client, &asset_ids)?;
+    }
+    Ok(asset_ids)
+}
+
+/// Repeat each asset once for each unit of its item, so every unit gets
+/// a label
+fn per_unit(client: &HomeboxClient, asset_ids: &[AssetId]) -> anyhow::Result<Vec<AssetId>> {
+    let items = client.list_items().context("Failed to list items")?;
+    let quantities: HashMap<_, _> = items
+        .iter()
+        .map(|item| (item.asset_id.as_str(), item.quantity))
+        .collect();
+    let mut units = vec![];
+    for &asset_id in asset_ids {
+        let quantity = quantities
+            .get(asset_id.to_string().as_str())
+            .copied()
+            .unwrap_or(1);
+        let count = usize::try_from(quantity).unwrap_or(0).max(1);
+        units.extend(std::iter::repeat_n(asset_id, count));
+    }
+    Ok(units)
+}
+
+/// Describe the label of each asset, for its alternative text
+fn describe_assets(asset_ids: &[AssetId], lang: Lang) -> Vec<String> {
+    asset_ids.iter().map(|id| lang.asset_label(id)).collect()
+}
+
+/// Get the labels for every asset in the list, rendering them locally if
+/// requested and otherwise downloading them
+fn get_ungrouped_labels(
+    args: &GenerateArgs,
+    client: &HomeboxClient,
+    asset_ids: &[AssetId],
+    journal: Option<&mut journal::Journal>,
+    lang: Lang,
+) -> anyhow::Result<Vec<bytes::Bytes>> {
+    if !args.local {
+        let labels = fetch_labels(client, asset_ids, journal)?;
+        if args.verify {
+            verify_labels(&labels, asset_ids)?;
         }
-        page_div.add_child(HtmlElement::new(HtmlTag::Div).with_raw("").into());
-    }
-    for (idx, bytes) in labels.iter().enumerate() {
-        let idx = idx + grid_skip;
-        if idx % num_per_page == 0 {
-            // Create page div
-            if skip_first {
-                skip_first = false;
-            } else {
-                page.add_raw(page_div.to_html_string());
+        return optimize_labels(args, labels);
+    }
+    let mut options = LabelOptions::for_server(&args.connection.server);
+    if let Some(template) = &args.qr_url {
+        options.qr_template.clone_from(template);
+    }
+    options.large_print = args.large_print;
+    options.error_correction = args.error_correction;
+    options.qr_module_mm = args.qr_module_mm;
+    options.qr_quiet_zone = args.qr_quiet_zone;
+    options.id_size_mm = args.id_under_code;
+    if args.match_server
+        && let Some(&asset_id) = asset_ids.first()
+    {
+        options.aspect_ratio = Some(local::server_aspect_ratio(client, asset_id)?);
+    }
+    local::render_labels(
+        client,
+        asset_ids,
+        &options,
+        &local::Content {
+            prefix: None,
+            fields: &args.fields,
+            warranty: args.warranty,
+            service: args.service,
+            quantity: args.quantity,
+            units: args.per_unit,
+            lang,
+        },
+        args.layout()?.cell_size_mm(),
+    )
+}
+
+/// Recompress the downloaded labels if requested, shrinking them to the
+/// size of their cell at the maximum resolution if given
+fn optimize_labels(
+    args: &GenerateArgs,
+    labels: Vec<bytes::Bytes>,
+) -> anyhow::Result<Vec<bytes::Bytes>> {
+    if !args.optimize_images {
+        return Ok(labels);
+    }
+    let max_size = match args.max_dpi {
+        Some(dpi) => {
+            let (width, height) = args.layout()?.cell_size_mm();
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                reason = "cells are a positive size, and much smaller than 4 billion pixels"
+            )]
+            let pixels = |mm: f64| (mm / 25.4 * f64::from(dpi)).ceil() as u32;
+            Some((pixels(width), pixels(height)))
+        }
+        None => None,
+    };
+
+    tracing::info!("Optimizing label images...");
+    labels
+        .iter()
+        .map(|label| optimize::optimize(label, max_size).context("Failed to optimize label image"))
+        .collect()
+}
+
+/// How many times to try downloading each label before giving up on it
+const LABEL_ATTEMPTS: u32 = 3;
+
+/// Download the label for an asset, trying again after a short and
+/// slightly random delay if the network or server fails briefly
+fn get_label_with_retries(
+    client: &HomeboxClient,
+    asset_id: AssetId,
+) -> Result<bytes::Bytes, ApiError> {
+    let mut attempt = 1;
+    loop {
+        match client.get_asset_label(asset_id) {
+            Err(e) if e.is_transient() && attempt < LABEL_ATTEMPTS => {
+                // Jitter the delay so many clients retrying do not all
+                // hit the server at once
+                let jitter = RandomState::new().build_hasher().finish() % 250;
+                let delay = Duration::from_millis(500 * 2_u64.pow(attempt - 1) + jitter);
+                tracing::warn!(
+                    "Failed to get label for asset {asset_id}, trying again in {delay:?}: {e}"
+                );
+                thread::sleep(delay);
+                attempt += 1;
             }
-            page_div = HtmlElement::new(HtmlTag::Div).with_attribute("class", "page");
+            result => return result,
         }
+    }
+}
 
-        let data = BASE64_STANDARD.encode(bytes);
-        page_div.add_child(
-            HtmlElement::new(HtmlTag::Div)
-                .with_attribute(
-                    "style",
-                    format!("background-image: url(data:image/png;base64,{data})"),
-                )
-                .with_raw("")
-                .into(),
-        );
+/// Download the label images for every asset in the list, recording
+/// progress in the journal if given
+fn fetch_labels(
+    client: &HomeboxClient,
+    asset_ids: &[AssetId],
+    mut journal: Option<&mut journal::Journal>,
+) -> anyhow::Result<Vec<bytes::Bytes>> {
+    let mut labels = vec![];
+    for &asset_id in asset_ids {
+        if let Some(label) = journal.as_ref().and_then(|j| j.get(asset_id)) {
+            tracing::info!("Using previously fetched label for asset ID: {asset_id}");
+            labels.push(label);
+            continue;
+        }
+
+        tracing::info!("Getting label for asset ID: {asset_id}");
+        let mut label_bytes = match get_label_with_retries(client, asset_id) {
+            Err(e @ ApiError::NotFound { .. }) => Err(e)
+                .context(Failure::AssetNotFound)
+                .with_context(|| format!("Failed to get label for asset {asset_id}"))?,
+            result => {
+                result.with_context(|| format!("Failed to get label for asset {asset_id}"))?
+            }
+        };
+        if let Some(journal) = journal.as_mut() {
+            label_bytes = journal.record(asset_id, label_bytes)?;
+        }
+        labels.push(label_bytes);
     }
-    page.add_raw(page_div.to_html_string());
 
-    page
+    Ok(labels)
 }
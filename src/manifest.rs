@@ -0,0 +1,33 @@
+use std::{fmt::Write, fs, path::Path};
+
+use anyhow::Context;
+use homebox_label_maker::{asset_list::AssetId, layout::SheetConfig};
+
+use crate::{failure::Failure, report::CellAssignment};
+
+/// Write a manifest of the cell each asset's label was placed in, as CSV
+/// if the file name ends in `.csv` and otherwise as JSON
+pub fn write(path: &Path, config: &SheetConfig, asset_ids: &[AssetId]) -> anyhow::Result<()> {
+    let entries = CellAssignment::all(config, asset_ids);
+
+    let is_csv = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    let manifest = if is_csv {
+        let mut csv = String::from("page,row,column,asset_id\n");
+        for entry in &entries {
+            writeln!(
+                csv,
+                "{},{},{},{}",
+                entry.page, entry.row, entry.column, entry.asset_id
+            )?;
+        }
+        csv
+    } else {
+        serde_json::to_string_pretty(&entries)?
+    };
+
+    fs::write(path, manifest)
+        .context("Failed to write manifest")
+        .context(Failure::Write)
+}
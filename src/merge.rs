@@ -0,0 +1,108 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Context;
+use serde::Deserialize;
+
+use homebox_label_maker::{
+    i18n::Lang,
+    label::LabelOptions,
+    layout::Layout,
+    render::{Registry, Sheet},
+};
+
+use crate::{ConnectionArgs, failure::Failure, local};
+
+#[derive(clap::Args)]
+pub struct MergeArgs {
+    /// A TOML file with a `[[job]]` table for each server, giving the
+    /// `prefix` to show on its labels and the `assets` to print, along
+    /// with the connection options such as `server`, `username` and
+    /// `password_cmd`
+    #[arg(index = 1)]
+    jobs: PathBuf,
+
+    /// The file path to output the result to
+    #[arg(index = 2)]
+    output_html: PathBuf,
+
+    /// The format to write the output in. Defaults to the format of the
+    /// output file's extension, or else HTML.
+    #[arg(
+        long,
+        value_parser = clap::builder::PossibleValuesParser::new(Registry::self_contained().names()),
+    )]
+    format: Option<String>,
+
+    #[command(flatten)]
+    layout: Layout,
+}
+
+#[derive(Deserialize)]
+struct JobsFile {
+    #[serde(rename = "job")]
+    jobs: Vec<Job>,
+}
+
+/// The labels to print from one server
+#[derive(Deserialize)]
+struct Job {
+    prefix: String,
+    assets: String,
+    #[serde(flatten)]
+    connection: ConnectionArgs,
+}
+
+/// Render the labels for every job in turn, and place them all on the
+/// same sheets
+pub fn run(args: &MergeArgs, lang: Lang) -> anyhow::Result<()> {
+    let format = crate::single_file_format(args.format.as_deref(), &args.output_html)?;
+    crate::ensure_output_free(&args.output_html)?;
+    let config = args.layout.build().context(Failure::Layout)?;
+    let JobsFile { jobs } =
+        toml::from_str(&fs::read_to_string(&args.jobs).context("Failed to read jobs file")?)
+            .context("Failed to parse jobs file")?;
+
+    let mut labels = vec![];
+    let mut descriptions = vec![];
+    for job in &jobs {
+        tracing::info!("Rendering labels from {}...", job.connection.server);
+        let client = crate::authenticate(&job.connection)?;
+        let asset_ids = crate::resolve_assets(&job.assets)?;
+        labels.extend(local::render_labels(
+            &client,
+            &asset_ids,
+            &LabelOptions::for_server(&job.connection.server),
+            &local::Content {
+                prefix: Some(&job.prefix),
+                fields: &[],
+                warranty: false,
+                service: false,
+                quantity: false,
+                units: false,
+                lang,
+            },
+            config.cell_size_mm(),
+        )?);
+        descriptions.extend(
+            asset_ids
+                .iter()
+                .map(|id| lang.asset_label(format!("{} {id}", job.prefix))),
+        );
+    }
+
+    let sheet = Sheet {
+        config: &config,
+        labels: &labels,
+        descriptions: &descriptions,
+        lang,
+    };
+    let bytes_written = crate::render_to_file(
+        &Registry::self_contained(),
+        format,
+        &sheet,
+        &args.output_html,
+    )?;
+    crate::print_plan(&config, labels.len(), lang);
+    println!("{}", lang.wrote(bytes_written, &args.output_html));
+    Ok(())
+}
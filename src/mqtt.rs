@@ -0,0 +1,121 @@
+use std::{sync::mpsc::Sender, thread, time::Duration};
+
+use anyhow::Context;
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use serde::Deserialize;
+
+use crate::daemon::Trigger;
+
+/// How long to wait before reconnecting to the broker after losing the
+/// connection
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(clap::Args)]
+#[allow(
+    clippy::struct_field_names,
+    reason = "the fields are flattened into the daemon's arguments, so need the prefix"
+)]
+pub struct MqttArgs {
+    /// The MQTT broker to subscribe to for jobs, as `host` or
+    /// `host:port`
+    #[arg(long, requires = "mqtt_topic")]
+    mqtt_broker: Option<String>,
+
+    /// The topic to subscribe to. Each message is a list of assets, in
+    /// the same format as on the command line, or a JSON job such as
+    /// `{"assets": "000-015"}`, and their labels are printed straight
+    /// away.
+    #[arg(long, requires = "mqtt_broker")]
+    mqtt_topic: Option<String>,
+
+    /// The username to connect to the broker with
+    #[arg(long, requires = "mqtt_broker")]
+    mqtt_username: Option<String>,
+
+    /// The password to connect to the broker with
+    #[arg(long, requires = "mqtt_username")]
+    mqtt_password: Option<String>,
+}
+
+/// A job published to the topic as JSON
+#[derive(Deserialize)]
+struct Job {
+    assets: String,
+}
+
+/// Subscribe to the topic, if one is given, triggering a print for each
+/// message published to it
+pub fn subscribe(args: &MqttArgs, wake: &Sender<Trigger>) -> anyhow::Result<()> {
+    let (Some(broker), Some(topic)) = (&args.mqtt_broker, &args.mqtt_topic) else {
+        return Ok(());
+    };
+    let (host, port) = match broker.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().context("Invalid MQTT broker port")?),
+        None => (broker.as_str(), 1883),
+    };
+
+    let mut options = MqttOptions::new(env!("CARGO_PKG_NAME"), host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &args.mqtt_username {
+        options.set_credentials(username, args.mqtt_password.clone().unwrap_or_default());
+    }
+    let (client, mut connection) = Client::new(options, 10);
+    tracing::info!("Subscribing to MQTT topic {topic} on {broker}");
+
+    let wake = wake.clone();
+    let topic = topic.clone();
+    thread::spawn(move || {
+        for event in connection.iter() {
+            match event {
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    let Some(asset_ids) = parse_job(&publish.payload) else {
+                        continue;
+                    };
+                    if wake.send(Trigger::Print(asset_ids)).is_err() {
+                        break;
+                    }
+                }
+                // Subscriptions are lost with the connection, so
+                // subscribe whenever connected
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    if let Err(e) = client.try_subscribe(&topic, QoS::AtLeastOnce) {
+                        tracing::warn!("Failed to subscribe to MQTT topic: {e}");
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("Lost connection to MQTT broker, reconnecting: {e}");
+                    thread::sleep(RECONNECT_DELAY);
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// The assets in a message, or none if it is not a valid job
+fn parse_job(payload: &[u8]) -> Option<Vec<crate::AssetId>> {
+    let payload = String::from_utf8_lossy(payload);
+    let payload = payload.trim();
+    let assets = if payload.starts_with('{') {
+        match serde_json::from_str::<Job>(payload) {
+            Ok(job) => job.assets,
+            Err(e) => {
+                tracing::warn!("Ignoring invalid MQTT job: {e}");
+                return None;
+            }
+        }
+    } else {
+        payload.to_string()
+    };
+    match crate::parse_assets(&assets) {
+        Ok(asset_ids) => {
+            tracing::info!("MQTT job received for {} assets", asset_ids.len());
+            Some(asset_ids)
+        }
+        Err(e) => {
+            tracing::warn!("Ignoring MQTT job with invalid assets: {e:#}");
+            None
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Making the PNG labels from the server smaller before they are
+//! embedded in a sheet, since they are much larger than they need to be.
+
+use std::io::Cursor;
+
+use png::{BitDepth, ColorType, Compression, Decoder, Encoder, Filter, Transformations};
+
+use crate::{error::RenderError, image::ImageType};
+
+/// Recompress a PNG label, storing it in greyscale if it has no colour,
+/// and shrink it to fit within the given (width, height) in pixels if it
+/// is larger. Labels that are not PNGs, such as those rendered locally,
+/// and labels that would not get smaller are returned unchanged.
+///
+/// # Errors
+///
+/// Fails if the label is a PNG that cannot be decoded or encoded.
+pub fn optimize(
+    label: &bytes::Bytes,
+    max_size: Option<(u32, u32)>,
+) -> Result<bytes::Bytes, RenderError> {
+    if ImageType::detect(label) != Some(ImageType::Png) {
+        return Ok(label.clone());
+    }
+
+    let mut image = decode(label)?;
+    if let Some(max_size) = max_size {
+        image = image.shrink_to_fit(max_size);
+    }
+    let optimized = image.encode()?;
+
+    if optimized.len() < label.len() {
+        Ok(optimized.into())
+    } else {
+        Ok(label.clone())
+    }
+}
+
+/// A decoded image, with four 8-bit channels per pixel
+pub(crate) struct Image {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) pixels: Vec<[u8; 4]>,
+}
+
+/// Decode a PNG image into RGBA pixels
+pub(crate) fn decode(png: &[u8]) -> Result<Image, RenderError> {
+    let mut decoder = Decoder::new(Cursor::new(png));
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .map_err(|e| RenderError::Backend(Box::new(e)))?;
+    let mut buf = vec![0; reader.output_buffer_size().unwrap_or_default()];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|e| RenderError::Backend(Box::new(e)))?;
+
+    let pixels = buf[..info.buffer_size()]
+        .chunks_exact(info.color_type.samples())
+        .map(|pixel| match *pixel {
+            [l] => [l, l, l, u8::MAX],
+            [l, a] => [l, l, l, a],
+            [r, g, b] => [r, g, b, u8::MAX],
+            [r, g, b, a] => [r, g, b, a],
+            _ => unreachable!("PNG pixels have one to four samples"),
+        })
+        .collect();
+    Ok(Image {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
+
+impl Image {
+    /// Shrink the image to fit within the (width, height), keeping its
+    /// proportions, by averaging the pixels that make up each new pixel
+    fn shrink_to_fit(self, (max_width, max_height): (u32, u32)) -> Self {
+        let scale = (f64::from(max_width) / f64::from(self.width))
+            .min(f64::from(max_height) / f64::from(self.height));
+        if scale >= 1.0 {
+            return self;
+        }
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            reason = "the scale is positive and less than one"
+        )]
+        let (width, height) = (
+            ((f64::from(self.width) * scale).round() as u32).max(1),
+            ((f64::from(self.height) * scale).round() as u32).max(1),
+        );
+
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        for y in 0..height {
+            let rows = span(y, height, self.height);
+            for x in 0..width {
+                let columns = span(x, width, self.width);
+                let mut sum = [0u32; 4];
+                let mut count = 0;
+                for row in rows.clone() {
+                    for column in columns.clone() {
+                        let pixel = self.pixels[row * self.width as usize + column];
+                        for (sum, sample) in sum.iter_mut().zip(pixel) {
+                            *sum += u32::from(sample);
+                        }
+                        count += 1;
+                    }
+                }
+                pixels.push(sum.map(|sum| u8::try_from(sum / count).unwrap_or(u8::MAX)));
+            }
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Encode the image as a PNG, with as few channels as it needs
+    fn encode(&self) -> Result<Vec<u8>, RenderError> {
+        let opaque = self.pixels.iter().all(|[_, _, _, a]| *a == u8::MAX);
+        let grey = self.pixels.iter().all(|[r, g, b, _]| r == g && g == b);
+        let (color_type, channels) = match (grey, opaque) {
+            (true, true) => (ColorType::Grayscale, &[0][..]),
+            (true, false) => (ColorType::GrayscaleAlpha, &[0, 3][..]),
+            (false, true) => (ColorType::Rgb, &[0, 1, 2][..]),
+            (false, false) => (ColorType::Rgba, &[0, 1, 2, 3][..]),
+        };
+        let data: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|pixel| channels.iter().map(|&channel| pixel[channel]))
+            .collect();
+
+        let mut png = vec![];
+        let mut encoder = Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(color_type);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.set_compression(Compression::High);
+        encoder.set_filter(Filter::Adaptive);
+        let mut writer = encoder
+            .write_header()
+            .map_err(|e| RenderError::Backend(Box::new(e)))?;
+        writer
+            .write_image_data(&data)
+            .map_err(|e| RenderError::Backend(Box::new(e)))?;
+        writer
+            .finish()
+            .map_err(|e| RenderError::Backend(Box::new(e)))?;
+        Ok(png)
+    }
+}
+
+/// The range of pixels in a row or column of `from` pixels that make up
+/// pixel `idx` of `to` pixels
+fn span(idx: u32, to: u32, from: u32) -> std::ops::Range<usize> {
+    let start = u64::from(idx) * u64::from(from) / u64::from(to);
+    let end = (u64::from(idx + 1) * u64::from(from) / u64::from(to)).max(start + 1);
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "the range is within the image"
+    )]
+    let range = start as usize..end as usize;
+    range
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An RGBA PNG of the given size, black on the left half and white
+    /// on the right
+    fn png(width: u32, height: u32) -> bytes::Bytes {
+        let image = Image {
+            width,
+            height,
+            pixels: (0..width * height)
+                .map(|idx| {
+                    if idx % width < width / 2 {
+                        [0, 0, 0, 255]
+                    } else {
+                        [255, 255, 255, 255]
+                    }
+                })
+                .collect(),
+        };
+        let mut png = vec![];
+        let mut encoder = Encoder::new(&mut png, width, height);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_compression(Compression::NoCompression);
+        let mut writer = encoder.write_header().unwrap();
+        writer
+            .write_image_data(image.pixels.as_flattened())
+            .unwrap();
+        writer.finish().unwrap();
+        png.into()
+    }
+
+    #[test]
+    fn greyscale_labels_are_smaller() {
+        let label = png(100, 40);
+        let optimized = optimize(&label, None).unwrap();
+        assert!(optimized.len() < label.len());
+        let image = decode(&optimized).unwrap();
+        assert_eq!((image.width, image.height), (100, 40));
+        assert_eq!(image.pixels[0], [0, 0, 0, 255]);
+        assert_eq!(image.pixels[99], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn labels_shrink_to_fit() {
+        let image = decode(&optimize(&png(100, 40), Some((50, 50))).unwrap()).unwrap();
+        assert_eq!((image.width, image.height), (50, 20));
+        let image = decode(&optimize(&png(10, 4), Some((50, 50))).unwrap()).unwrap();
+        assert_eq!((image.width, image.height), (10, 4));
+    }
+
+    #[test]
+    fn svg_labels_are_unchanged() {
+        let label = bytes::Bytes::from_static(b"<svg/>");
+        assert_eq!(optimize(&label, Some((1, 1))).unwrap(), label);
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt;
+
+use anyhow::Context;
+use homebox_label_maker::{
+    api::ItemSummary,
+    asset_list::{self, AssetId},
+    i18n::Lang,
+};
+
+use crate::{ConnectionArgs, failure::Failure};
+
+#[derive(clap::Args)]
+pub struct PickArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+}
+
+/// An item to choose from, shown with its asset ID and location
+struct Choice {
+    asset_id: AssetId,
+    item: ItemSummary,
+}
+
+impl fmt::Display for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}  {}", self.asset_id, self.item.name)?;
+        if let Some(location) = &self.item.location {
+            write!(f, " ({})", location.name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Choose items from a list that can be searched by typing, and print
+/// the asset list for the items chosen, to pass to the other commands
+pub fn run(args: &PickArgs, lang: Lang) -> anyhow::Result<()> {
+    let client = crate::authenticate(&args.connection)?;
+    let mut choices = vec![];
+    for item in client.list_items().context("Failed to list items")? {
+        if item.has_asset_id() {
+            for asset_id in crate::resolve_assets(&item.asset_id)? {
+                choices.push(Choice {
+                    asset_id,
+                    item: item.clone(),
+                });
+            }
+        }
+    }
+    choices.sort_by_key(|choice| choice.asset_id);
+
+    let chosen = inquire::MultiSelect::new(lang.pick_prompt(), choices)
+        .prompt()
+        .context("No items were chosen")
+        .context(Failure::InvalidAssets)?;
+    let asset_ids: Vec<_> = chosen.into_iter().map(|choice| choice.asset_id).collect();
+    println!("{}", asset_list::format(&asset_ids));
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::fmt::Write;
+
+use anyhow::{Context, anyhow};
+use build_html::{Html, HtmlContainer};
+
+use homebox_label_maker::{html, i18n::Lang, layout::Layout, render::Sheet};
+
+use crate::ConnectionArgs;
+
+#[derive(clap::Args)]
+pub struct PreviewArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// The assets to generate labels for, in the same format as when
+    /// generating a sheet
+    #[arg(index = 1)]
+    assets: String,
+
+    #[command(flatten)]
+    layout: Layout,
+
+    /// The address to serve the preview on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+}
+
+/// Produce a copy of the layout with any fields present in the query
+/// string replaced
+fn with_query(layout: &Layout, query: &str) -> anyhow::Result<Layout> {
+    let mut fields: Vec<(String, String)> =
+        serde_urlencoded::from_str(&serde_urlencoded::to_string(layout)?)?;
+    for (key, value) in serde_urlencoded::from_str::<Vec<(String, String)>>(query)? {
+        if let Some(field) = fields.iter_mut().find(|(k, _)| *k == key) {
+            field.1 = value;
+        }
+    }
+    Ok(serde_urlencoded::from_str(&serde_urlencoded::to_string(
+        fields,
+    )?)?)
+}
+
+/// Fetch the labels once, then serve the sheet, re-rendering it for
+/// every request with the layout given in the query string
+pub fn run(args: &PreviewArgs, lang: Lang) -> anyhow::Result<()> {
+    let client = crate::authenticate(&args.connection)?;
+    let asset_ids = crate::resolve_assets(&args.assets)?;
+    let labels = crate::fetch_labels(&client, &asset_ids, None)?;
+    let descriptions = crate::describe_assets(&asset_ids, lang);
+
+    let server = tiny_http::Server::http(&args.listen)
+        .map_err(|e| anyhow!(e))
+        .context("Failed to start preview server")?;
+    tracing::info!("Serving preview on http://{}/", args.listen);
+
+    for request in server.incoming_requests() {
+        let query = request
+            .url()
+            .split_once('?')
+            .map_or("", |(_, query)| query)
+            .to_string();
+        tracing::debug!("Rendering preview for query: {query:?}");
+
+        let response = match with_query(&args.layout, &query)
+            .and_then(|layout| Ok((layout.build()?, layout)))
+        {
+            Ok((config, layout)) => {
+                let mut page = html::generate(
+                    &Sheet {
+                        config: &config,
+                        labels: &labels,
+                        descriptions: &descriptions,
+                        lang,
+                    },
+                    &html::Options::default(),
+                );
+                page.add_raw(controls(&layout)?);
+                tiny_http::Response::from_string(page.to_html_string()).with_header(
+                    tiny_http::Header::from_bytes("Content-Type", "text/html; charset=utf-8")
+                        .expect("header is valid"),
+                )
+            }
+            Err(e) => tiny_http::Response::from_string(format!("Invalid layout: {e}"))
+                .with_status_code(400),
+        };
+        if let Err(e) = request.respond(response) {
+            tracing::warn!("Failed to respond to preview request: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Build the floating form used to adjust the layout. Changing any
+/// value resubmits the form, re-rendering the sheet.
+fn controls(layout: &Layout) -> anyhow::Result<String> {
+    let fields: Vec<(String, String)> =
+        serde_urlencoded::from_str(&serde_urlencoded::to_string(layout)?)?;
+
+    let mut form = String::from(
+        r#"<form class="no-print preview-controls" method="get" onchange="this.submit()">"#,
+    );
+    for (key, value) in fields {
+        write!(
+            form,
+            r#"<label>{key} <input type="number" step="any" name="{key}" value="{value}"></label>"#
+        )?;
+    }
+    form.push_str("</form>");
+    Ok(form)
+}
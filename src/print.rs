@@ -0,0 +1,37 @@
+use std::{io, path::Path, process::Command};
+
+use anyhow::{Context, anyhow};
+
+/// Submit a file to the system print queue, trying `lp` then `lpr`
+pub fn submit(path: &Path, copies: usize, printer: Option<&str>) -> anyhow::Result<()> {
+    let mut lp = Command::new("lp");
+    lp.arg("-n").arg(copies.to_string());
+    if let Some(printer) = printer {
+        lp.arg("-d").arg(printer);
+    }
+    lp.arg("--").arg(path);
+
+    let mut lpr = Command::new("lpr");
+    lpr.arg(format!("-#{copies}"));
+    if let Some(printer) = printer {
+        lpr.arg("-P").arg(printer);
+    }
+    lpr.arg(path);
+
+    for mut command in [lp, lpr] {
+        let program = command.get_program().to_string_lossy().to_string();
+        tracing::debug!("Submitting with {program}...");
+        match command.status() {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => Err(anyhow!("{program} failed with {status}"))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                tracing::debug!("{program} not found");
+            }
+            Err(e) => Err(e).context(format!("Failed to run {program}"))?,
+        }
+    }
+
+    Err(anyhow!(
+        "No print command found! Printing requires lp or lpr to be installed."
+    ))
+}
@@ -0,0 +1,139 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use anyhow::{Context, anyhow};
+use homebox_label_maker::layout::{Layout, SheetConfig};
+use serde::Deserialize;
+
+/// The unprintable margins and feed offset of a printer, so the same
+/// layout lines up on any printer. Profiles are kept in
+/// `printers.toml` in the configuration directory, in a table named
+/// after each profile, e.g.
+///
+/// ```toml
+/// [office]
+/// margin_top_mm = 4.2
+/// margin_bottom_mm = 4.2
+/// offset_y_mm = 1.5
+/// ```
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[allow(
+    clippy::struct_field_names,
+    reason = "the units match the layout's fields, as they are written in the same files"
+)]
+pub struct PrinterProfile {
+    /// The strips at the edges of the page that the printer cannot
+    /// print on, in millimeters
+    margin_top_mm: f64,
+    margin_left_mm: f64,
+    margin_bottom_mm: f64,
+    margin_right_mm: f64,
+    /// How far to the right of where it should be everything is
+    /// printed, in millimeters
+    offset_x_mm: f64,
+    /// How far below where it should be everything is printed, in
+    /// millimeters
+    offset_y_mm: f64,
+}
+
+impl PrinterProfile {
+    fn path() -> anyhow::Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .context("Failed to find configuration directory")?
+            .join(env!("CARGO_PKG_NAME"))
+            .join("printers.toml"))
+    }
+
+    /// Load the profile with the given name
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        let path = Self::path()?;
+        let mut profiles: HashMap<String, Self> = toml::from_str(
+            &fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?,
+        )
+        .context("Failed to parse printer profiles")?;
+        let profile = profiles
+            .remove(name)
+            .ok_or_else(|| anyhow!("No printer profile is named {name}"))?;
+        let margins = [
+            profile.margin_top_mm,
+            profile.margin_left_mm,
+            profile.margin_bottom_mm,
+            profile.margin_right_mm,
+        ];
+        if margins.iter().any(|&margin| margin < 0.0) {
+            Err(anyhow!(
+                "The unprintable margins of printer profile {name} cannot be negative"
+            ))?;
+        }
+        Ok(profile)
+    }
+
+    /// Produce a copy of the layout moved against the printer's offset,
+    /// so it is printed where the layout places it, and with its margins
+    /// widened to at least the printer's unprintable margins, so the
+    /// labels are fitted into the area it can print on
+    ///
+    /// Fails if the printer cannot print on enough of the page for the
+    /// layout.
+    pub fn apply(&self, layout: &Layout) -> anyhow::Result<Layout> {
+        let mut layout = layout.clone();
+        layout.page_margin_left_mm =
+            (layout.page_margin_left_mm - self.offset_x_mm).max(self.margin_left_mm);
+        layout.page_margin_right_mm =
+            (layout.page_margin_right_mm + self.offset_x_mm).max(self.margin_right_mm);
+        layout.page_margin_top_mm =
+            (layout.page_margin_top_mm - self.offset_y_mm).max(self.margin_top_mm);
+        layout.page_margin_bottom_mm =
+            (layout.page_margin_bottom_mm + self.offset_y_mm).max(self.margin_bottom_mm);
+
+        let printable_width =
+            layout.page_width_mm - layout.page_margin_left_mm - layout.page_margin_right_mm;
+        let printable_height =
+            layout.page_height_mm - layout.page_margin_top_mm - layout.page_margin_bottom_mm;
+        // A roll is as long as needed, so only its width is limited
+        if printable_width <= 0.0 || (layout.roll_pitch_mm.is_none() && printable_height <= 0.0) {
+            Err(anyhow!(
+                "The printer's unprintable margins leave no space on the {}x{}mm page for labels",
+                layout.page_width_mm,
+                layout.page_height_mm
+            ))?;
+        }
+        Ok(layout)
+    }
+
+    /// Check if any labels still reach into the margins the printer
+    /// cannot print on, as labels of a set size are not shrunk to fit
+    pub fn check(&self, config: &SheetConfig) -> Option<String> {
+        // A roll is as long as needed, with only one label across it
+        let last = if config.is_roll() {
+            config.cell(0)
+        } else {
+            config.cell(config.num_per_page() - 1)
+        };
+        let (left, top) = config.cell_origin_mm(config.cell(0));
+        let (x, y) = config.cell_origin_mm(last);
+        let (width, height) = config.cell_size_mm();
+        let (page_width, page_height) = config.page_size_mm();
+        let mut clipped = vec![];
+        if top < self.margin_top_mm {
+            clipped.push("top");
+        }
+        if left < self.margin_left_mm {
+            clipped.push("left");
+        }
+        if page_width - x - width < self.margin_right_mm {
+            clipped.push("right");
+        }
+        if !config.is_roll() && page_height - y - height < self.margin_bottom_mm {
+            clipped.push("bottom");
+        }
+        (!clipped.is_empty()).then(|| {
+            format!(
+                "Labels reach into the unprintable {} margin of the printer, so would be cut \
+                off. Use smaller labels or fewer rows and columns.",
+                clipped.join(" and ")
+            )
+        })
+    }
+}
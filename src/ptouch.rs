@@ -0,0 +1,97 @@
+//! Writing a database for Brother P-touch Editor, which links each
+//! label's image and description into a layout designed in it.
+//!
+//! P-touch Editor cannot read images embedded in a database, so the CSV
+//! refers to the label images saved in a directory, by the name
+//! [`crate::html::image_file_name`] gives them.
+
+use std::io;
+
+use crate::{
+    error::RenderError,
+    html::image_file_name,
+    render::{Sheet, write_csv_row},
+};
+
+/// Options for the P-touch Editor database
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// The directory the label images are saved in. P-touch Editor
+    /// finds the images by path from wherever the database is opened,
+    /// so this should be absolute.
+    pub image_dir: Option<String>,
+}
+
+/// Write the database as CSV, with a row for each label. The file starts
+/// with a byte order mark and has Windows line endings, as P-touch
+/// Editor otherwise misreads text that isn't ASCII.
+///
+/// # Errors
+///
+/// Fails if there is no image directory to link to, or the output cannot
+/// be written.
+pub fn write(
+    sheet: &Sheet<'_>,
+    options: &Options,
+    out: &mut dyn io::Write,
+) -> Result<(), RenderError> {
+    let Some(dir) = &options.image_dir else {
+        return Err(RenderError::Unsupported(
+            "P-touch Editor can only link to label images, so they must be saved to a directory"
+                .to_string(),
+        ));
+    };
+    let separator = if dir.contains('\\') { '\\' } else { '/' };
+    let dir = dir.trim_end_matches(['/', '\\']);
+
+    out.write_all("\u{feff}".as_bytes())?;
+    write_csv_row(out, &["Number", "Description", "Image"])?;
+    for (idx, label) in sheet.labels.iter().enumerate() {
+        let description = sheet
+            .descriptions
+            .get(idx)
+            .cloned()
+            .unwrap_or_else(|| sheet.lang.numbered_label(idx + 1));
+        write_csv_row(
+            out,
+            &[
+                &(idx + 1).to_string(),
+                &description,
+                &format!("{dir}{separator}{}", image_file_name(label)),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{i18n::Lang, layout::Layout};
+
+    #[test]
+    fn rows_link_to_images() {
+        let config = Layout::default().build().unwrap();
+        let labels = [bytes::Bytes::from_static(b"label")];
+        let sheet = Sheet {
+            config: &config,
+            labels: &labels,
+            descriptions: &["Shelf \"A\", top".to_string()],
+            lang: Lang::En,
+        };
+        let options = Options {
+            image_dir: Some("C:\\labels\\".to_string()),
+        };
+        let mut out = vec![];
+        write(&sheet, &options, &mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        assert_eq!(
+            csv,
+            format!(
+                "\u{feff}\"Number\",\"Description\",\"Image\"\r\n\
+                \"1\",\"Shelf \"\"A\"\", top\",\"C:\\labels\\{}\"\r\n",
+                image_file_name(b"label")
+            )
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Python bindings, built into the `homebox_label_maker` module:
+//!
+//! ```python
+//! import homebox_label_maker
+//!
+//! sheet = homebox_label_maker.generate(
+//!     "https://homebox.example.com", "user", "pass", "000-001--000-010",
+//!     layout={"grid_rows": 13, "grid_columns": 5},
+//! )
+//! open("labels.html", "wb").write(sheet)
+//! ```
+
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+    types::{PyBool, PyBytes, PyDict, PyFloat, PyInt, PyString},
+};
+
+use crate::{
+    asset_list::{self, Validate},
+    bindings::Config,
+    error::describe,
+};
+
+/// Parse and validate an asset list, returning each asset ID in it
+#[pyfunction]
+fn parse_assets(assets: &str) -> PyResult<Vec<String>> {
+    let list = asset_list::parse(assets).map_err(|e| PyValueError::new_err(describe(&e)))?;
+    list.validate()
+        .map_err(|e| PyValueError::new_err(describe(&e)))?;
+    Ok(list
+        .into_iter()
+        .flatten()
+        .map(|id| id.to_string())
+        .collect())
+}
+
+/// Fetch the labels for the assets from the server and render them into
+/// a sheet, returning the contents of the output file. The layout uses
+/// the same names as the command line options, any of which may be
+/// omitted.
+#[pyfunction]
+#[pyo3(signature = (server, username, password, assets, layout=None, format="html", lang=None))]
+#[allow(
+    clippy::too_many_arguments,
+    reason = "mirrors the keyword arguments of the Python function"
+)]
+fn generate<'py>(
+    py: Python<'py>,
+    server: String,
+    username: String,
+    password: String,
+    assets: String,
+    layout: Option<&Bound<'py, PyDict>>,
+    format: &str,
+    lang: Option<String>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let layout = match layout {
+        Some(layout) => layout_from_dict(layout)?,
+        None => crate::layout::Layout::default(),
+    };
+    let config = Config {
+        server,
+        username,
+        password,
+        assets,
+        layout,
+        format: format.to_string(),
+        lang,
+    };
+
+    let sheet = py
+        .detach(|| config.generate())
+        .map_err(|e| PyRuntimeError::new_err(describe(&e)))?;
+    Ok(PyBytes::new(py, &sheet))
+}
+
+/// Convert a dictionary of layout options into a layout
+fn layout_from_dict(dict: &Bound<'_, PyDict>) -> PyResult<crate::layout::Layout> {
+    let mut fields = serde_json::Map::new();
+    for (key, value) in dict {
+        let key: String = key.extract()?;
+        // Python's bool is a subclass of int, so is checked first
+        let value = if value.is_none() {
+            serde_json::Value::Null
+        } else if value.is_instance_of::<PyBool>() {
+            serde_json::Value::from(value.extract::<bool>()?)
+        } else if value.is_instance_of::<PyInt>() {
+            serde_json::Value::from(value.extract::<i64>()?)
+        } else if value.is_instance_of::<PyFloat>() {
+            serde_json::Value::from(value.extract::<f64>()?)
+        } else if value.is_instance_of::<PyString>() {
+            serde_json::Value::from(value.extract::<String>()?)
+        } else {
+            return Err(PyValueError::new_err(format!(
+                "Layout option {key} must be a number, bool, string or None"
+            )));
+        };
+        fields.insert(key, value);
+    }
+    serde_json::from_value(fields.into()).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+#[pymodule]
+fn homebox_label_maker(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(parse_assets, module)?)?;
+    module.add_function(wrap_pyfunction!(generate, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_is_read_from_python_values() {
+        Python::initialize();
+        Python::attach(|py| {
+            let layout = py
+                .eval(
+                    c"{'auto_grid': True, 'skip_cells': '1,2:5', 'label_width_mm': 38.1, \
+                      'label_height_mm': 21.2, 'grid_rows': 7, 'roll_pitch_mm': None}",
+                    None,
+                    None,
+                )
+                .unwrap();
+            let layout = layout_from_dict(layout.cast().unwrap()).unwrap();
+            assert!(layout.auto_grid);
+            assert_eq!(layout.skip_cells.as_deref(), Some("1,2:5"));
+            assert_eq!(layout.label_width_mm, Some(38.1));
+            assert_eq!(layout.grid_rows, 7);
+            assert_eq!(layout.roll_pitch_mm, None);
+
+            let layout = py.eval(c"{'grid_rows': [13]}", None, None).unwrap();
+            assert!(layout_from_dict(layout.cast().unwrap()).is_err());
+        });
+    }
+}
@@ -0,0 +1,297 @@
+//! Output backends for label sheets.
+//!
+//! Each output format is a [`Renderer`]. The built-in renderers are
+//! enabled by crate features, and others can be added to a [`Registry`]
+//! to make them available alongside them:
+//!
+//! ```
+//! use homebox_label_maker::{
+//!     error::RenderError,
+//!     render::{Registry, Renderer, Sheet},
+//! };
+//!
+//! struct LabelCount;
+//!
+//! impl Renderer for LabelCount {
+//!     fn name(&self) -> &'static str {
+//!         "count"
+//!     }
+//!
+//!     fn render(&self, sheet: &Sheet<'_>) -> Result<Vec<u8>, RenderError> {
+//!         Ok(sheet.labels.len().to_string().into_bytes())
+//!     }
+//! }
+//!
+//! let mut registry = Registry::default();
+//! registry.register(LabelCount);
+//! assert!(registry.get("count").is_some());
+//! ```
+
+use std::io;
+
+use crate::{error::RenderError, i18n::Lang, layout::SheetConfig};
+
+/// Everything needed to render a sheet of labels
+pub struct Sheet<'a> {
+    pub config: &'a SheetConfig,
+    /// The label images, in the order they should be placed
+    pub labels: &'a [bytes::Bytes],
+    /// A description of each label, in the same order, used as its
+    /// alternative text, e.g. `Label for asset 000-015`. Labels without
+    /// one are numbered instead.
+    pub descriptions: &'a [String],
+    pub lang: Lang,
+}
+
+/// An output format for label sheets
+pub trait Renderer: Send + Sync {
+    /// The name used to select this renderer, e.g. `html`
+    fn name(&self) -> &'static str;
+
+    /// The file extensions of output in this format, without the dot,
+    /// used to choose the renderer from the output path when no format
+    /// is given
+    fn extensions(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The most labels a file in this format can hold, if it is limited,
+    /// so larger sheets can be split across several files
+    fn max_labels(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether the output links to label images saved beside it rather
+    /// than holding them, so it is only complete once they are saved
+    fn links_images(&self) -> bool {
+        false
+    }
+
+    /// Render the sheet into the bytes of the output file
+    ///
+    /// # Errors
+    ///
+    /// Fails if the sheet cannot be represented in this format.
+    fn render(&self, sheet: &Sheet<'_>) -> Result<Vec<u8>, RenderError>;
+
+    /// Render the sheet, writing the output as it is produced. Renderers
+    /// that can produce their output in parts should override this to
+    /// avoid holding all of it in memory.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the sheet cannot be represented in this format, or the
+    /// output cannot be written.
+    fn render_to(&self, sheet: &Sheet<'_>, out: &mut dyn io::Write) -> Result<(), RenderError> {
+        out.write_all(&self.render(sheet)?)?;
+        Ok(())
+    }
+}
+
+/// Renders the sheet as an HTML page, to be printed from a browser
+#[cfg(feature = "html")]
+#[derive(Default)]
+pub struct HtmlRenderer {
+    pub options: crate::html::Options,
+}
+
+#[cfg(feature = "html")]
+impl Renderer for HtmlRenderer {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["html", "htm"]
+    }
+
+    fn render(&self, sheet: &Sheet<'_>) -> Result<Vec<u8>, RenderError> {
+        let mut output = vec![];
+        self.render_to(sheet, &mut output)?;
+        Ok(output)
+    }
+
+    fn render_to(&self, sheet: &Sheet<'_>, out: &mut dyn io::Write) -> Result<(), RenderError> {
+        crate::html::write(sheet, &self.options, out)?;
+        Ok(())
+    }
+}
+
+/// Renders the sheet as a CSV mail merge data source for Avery Design &
+/// Print or Word, linking to the label images
+#[cfg(feature = "avery")]
+#[derive(Default)]
+pub struct AveryRenderer {
+    pub options: crate::avery::Options,
+}
+
+#[cfg(feature = "avery")]
+impl Renderer for AveryRenderer {
+    fn name(&self) -> &'static str {
+        "avery"
+    }
+
+    fn links_images(&self) -> bool {
+        true
+    }
+
+    fn render(&self, sheet: &Sheet<'_>) -> Result<Vec<u8>, RenderError> {
+        let mut output = vec![];
+        self.render_to(sheet, &mut output)?;
+        Ok(output)
+    }
+
+    fn render_to(&self, sheet: &Sheet<'_>, out: &mut dyn io::Write) -> Result<(), RenderError> {
+        crate::avery::write(sheet, &self.options, out)
+    }
+}
+
+/// Renders a single label as a DYMO label file
+#[cfg(feature = "dymo")]
+#[derive(Default)]
+pub struct DymoRenderer {
+    pub options: crate::dymo::Options,
+}
+
+#[cfg(feature = "dymo")]
+impl Renderer for DymoRenderer {
+    fn name(&self) -> &'static str {
+        "dymo"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["label"]
+    }
+
+    fn max_labels(&self) -> Option<usize> {
+        Some(1)
+    }
+
+    fn render(&self, sheet: &Sheet<'_>) -> Result<Vec<u8>, RenderError> {
+        let mut output = vec![];
+        self.render_to(sheet, &mut output)?;
+        Ok(output)
+    }
+
+    fn render_to(&self, sheet: &Sheet<'_>, out: &mut dyn io::Write) -> Result<(), RenderError> {
+        crate::dymo::write(sheet, &self.options, out)
+    }
+}
+
+/// Renders the sheet as a CSV database for Brother P-touch Editor,
+/// linking to the label images
+#[cfg(feature = "ptouch")]
+#[derive(Default)]
+pub struct PtouchRenderer {
+    pub options: crate::ptouch::Options,
+}
+
+#[cfg(feature = "ptouch")]
+impl Renderer for PtouchRenderer {
+    fn name(&self) -> &'static str {
+        "ptouch"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["csv"]
+    }
+
+    fn links_images(&self) -> bool {
+        true
+    }
+
+    fn render(&self, sheet: &Sheet<'_>) -> Result<Vec<u8>, RenderError> {
+        let mut output = vec![];
+        self.render_to(sheet, &mut output)?;
+        Ok(output)
+    }
+
+    fn render_to(&self, sheet: &Sheet<'_>, out: &mut dyn io::Write) -> Result<(), RenderError> {
+        crate::ptouch::write(sheet, &self.options, out)
+    }
+}
+
+/// Write a row of a CSV file, quoting every field so separators and
+/// quotes in them are kept, with Windows line endings for the office
+/// software the files are opened in
+#[cfg(any(feature = "avery", feature = "ptouch"))]
+pub(crate) fn write_csv_row(out: &mut dyn io::Write, fields: &[&str]) -> io::Result<()> {
+    let fields: Vec<_> = fields
+        .iter()
+        .map(|field| format!("\"{}\"", field.replace('"', "\"\"")))
+        .collect();
+    write!(out, "{}\r\n", fields.join(","))
+}
+
+/// The set of renderers available to choose from by name
+pub struct Registry {
+    renderers: Vec<Box<dyn Renderer>>,
+}
+
+impl Registry {
+    /// A registry with no renderers
+    pub fn empty() -> Self {
+        Self { renderers: vec![] }
+    }
+
+    /// Add a renderer, replacing any existing renderer with the same
+    /// name
+    pub fn register(&mut self, renderer: impl Renderer + 'static) {
+        self.renderers.retain(|r| r.name() != renderer.name());
+        self.renderers.push(Box::new(renderer));
+    }
+
+    /// Find the renderer with the given name
+    pub fn get(&self, name: &str) -> Option<&dyn Renderer> {
+        self.renderers
+            .iter()
+            .find(|r| r.name() == name)
+            .map(AsRef::as_ref)
+    }
+
+    /// Find the renderer for output with the given file extension
+    pub fn for_extension(&self, extension: &str) -> Option<&dyn Renderer> {
+        self.renderers
+            .iter()
+            .find(|r| {
+                r.extensions()
+                    .iter()
+                    .any(|e| e.eq_ignore_ascii_case(extension))
+            })
+            .map(AsRef::as_ref)
+    }
+
+    /// A registry of the built-in renderers that write a whole sheet to
+    /// a single file that holds its label images, for output that can
+    /// neither be split nor have images saved beside it
+    pub fn self_contained() -> Self {
+        let mut registry = Self::default();
+        registry
+            .renderers
+            .retain(|r| !r.links_images() && r.max_labels().is_none());
+        registry
+    }
+
+    /// The names of all registered renderers
+    pub fn names(&self) -> impl Iterator<Item = &'static str> {
+        self.renderers.iter().map(|r| r.name())
+    }
+}
+
+impl Default for Registry {
+    /// A registry containing the built-in renderers enabled by crate
+    /// features
+    fn default() -> Self {
+        #[allow(unused_mut, reason = "no renderers are built in without features")]
+        let mut registry = Self::empty();
+        #[cfg(feature = "html")]
+        registry.register(HtmlRenderer::default());
+        #[cfg(feature = "avery")]
+        registry.register(AveryRenderer::default());
+        #[cfg(feature = "dymo")]
+        registry.register(DymoRenderer::default());
+        #[cfg(feature = "ptouch")]
+        registry.register(PtouchRenderer::default());
+        registry
+    }
+}
@@ -0,0 +1,85 @@
+use std::{fs, path::Path, time::Instant};
+
+use anyhow::Context;
+use homebox_label_maker::{
+    asset_list::AssetId,
+    layout::{LabelSlot, SheetConfig},
+};
+use serde::Serialize;
+
+/// The format of a machine-readable run report
+#[derive(Clone, Copy, clap::ValueEnum)]
+pub enum Format {
+    Json,
+}
+
+/// A summary of a run, for consumption by other tooling
+#[derive(Serialize)]
+pub struct Report {
+    /// The asset IDs resolved from the asset list, in order
+    assets: Vec<AssetId>,
+    /// The number of cells skipped at the start of the first page
+    skipped_cells: usize,
+    /// The number of pages produced
+    pages: usize,
+    /// The cell each asset was placed in
+    cells: Vec<CellAssignment>,
+    /// The time taken for the run, in milliseconds, unless the report
+    /// must be deterministic
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u128>,
+}
+
+/// The cell an asset's label was placed in, as listed in reports and
+/// manifests
+#[derive(Serialize)]
+pub struct CellAssignment {
+    pub asset_id: AssetId,
+    pub page: usize,
+    pub row: usize,
+    pub column: usize,
+}
+
+impl CellAssignment {
+    /// The cell of each asset's label, in the order they are placed
+    pub fn all(config: &SheetConfig, asset_ids: &[AssetId]) -> Vec<Self> {
+        config
+            .assignments(asset_ids.len())
+            .filter_map(|(cell, slot)| match slot {
+                LabelSlot::Label(idx) => Some(Self {
+                    asset_id: asset_ids[idx],
+                    page: cell.page,
+                    row: cell.row,
+                    column: cell.column,
+                }),
+                LabelSlot::Skipped => None,
+            })
+            .collect()
+    }
+}
+
+impl Report {
+    /// Summarise a run, including its duration if it is given when it
+    /// started
+    pub fn new(config: &SheetConfig, asset_ids: &[AssetId], started: Option<Instant>) -> Self {
+        Self {
+            assets: asset_ids.to_vec(),
+            skipped_cells: config.grid_skip(),
+            pages: config.page_count(asset_ids.len()),
+            cells: CellAssignment::all(config, asset_ids),
+            duration_ms: started.map(|started| started.elapsed().as_millis()),
+        }
+    }
+
+    /// Write the report to the given file, or stdout if none is given
+    pub fn write(&self, format: Format, path: Option<&Path>) -> anyhow::Result<()> {
+        let report = match format {
+            Format::Json => serde_json::to_string_pretty(self)?,
+        };
+        match path {
+            Some(path) => fs::write(path, report).context("Failed to write report")?,
+            None => println!("{report}"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use std::io::Read;
+
+use anyhow::{Context, anyhow};
+use serde::Deserialize;
+
+use homebox_label_maker::{
+    api::HomeboxClient,
+    i18n::Lang,
+    layout::Layout,
+    render::{Registry, Sheet},
+};
+
+use crate::ConnectionArgs;
+
+/// The largest request body accepted, in bytes
+const MAX_BODY: u64 = 1024 * 1024;
+
+#[derive(clap::Args)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// The address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// The most labels a single request may generate
+    #[arg(long, default_value_t = 500)]
+    max_labels: usize,
+}
+
+/// A request for a sheet, as the JSON body of `POST /generate`
+#[derive(Deserialize)]
+struct GenerateRequest {
+    /// The assets to generate labels for, in the same format as on the
+    /// command line, except that they cannot be read from a file
+    assets: String,
+    #[serde(default)]
+    layout: Layout,
+    #[serde(default = "default_format")]
+    format: String,
+}
+
+/// The fields of `GET /generate` besides the layout
+#[derive(Deserialize)]
+struct GenerateQuery {
+    assets: String,
+    #[serde(default = "default_format")]
+    format: String,
+}
+
+fn default_format() -> String {
+    "html".to_string()
+}
+
+/// A failed request, with the status to respond with
+struct Rejection {
+    status: u16,
+    message: String,
+    /// Whether the server rejected the session, so logging in again may
+    /// help
+    session_expired: bool,
+}
+
+impl Rejection {
+    fn new(status: u16, message: impl std::fmt::Display) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+            session_expired: false,
+        }
+    }
+
+    fn bad_request(e: impl std::fmt::Display) -> Self {
+        Self::new(400, e)
+    }
+}
+
+impl From<anyhow::Error> for Rejection {
+    fn from(e: anyhow::Error) -> Self {
+        Self {
+            session_expired: crate::session_expired(&e),
+            ..Self::new(500, format!("{e:#}"))
+        }
+    }
+}
+
+/// Serve an HTTP API generating sheets on request, with the labels from
+/// the server given on the command line. `POST /generate` takes a JSON
+/// body with the assets, layout and format, and `GET /generate` takes
+/// the same as query parameters, with the layout fields at the top
+/// level, e.g. for a bookmarklet.
+pub fn run(args: &ServeArgs, lang: Lang) -> anyhow::Result<()> {
+    let mut client = crate::authenticate(&args.connection)?;
+
+    let server = tiny_http::Server::http(&args.listen)
+        .map_err(|e| anyhow!(e))
+        .context("Failed to start server")?;
+    tracing::info!("Serving on http://{}/generate", args.listen);
+
+    for mut request in server.incoming_requests() {
+        tracing::debug!("Request: {} {}", request.method(), request.url());
+        let result = parse(&mut request).and_then(|generate| {
+            let Some(generate) = generate else {
+                return Ok(("text/plain", vec![]));
+            };
+            match render(args, &client, &generate, lang) {
+                // The session expires eventually, so log in again and
+                // retry once if it was rejected
+                Err(Rejection {
+                    session_expired: true,
+                    ..
+                }) => {
+                    client = crate::reauthenticate(&args.connection)?;
+                    render(args, &client, &generate, lang)
+                }
+                result => result,
+            }
+        });
+
+        let response = match result {
+            Ok((content_type, body)) => tiny_http::Response::from_data(body)
+                .with_header(header("Content-Type", content_type)),
+            Err(Rejection {
+                status, message, ..
+            }) => {
+                tracing::warn!("Failed to generate sheet: {message}");
+                tiny_http::Response::from_string(message).with_status_code(status)
+            }
+        }
+        .with_header(header("Access-Control-Allow-Origin", "*"))
+        .with_header(header("Access-Control-Allow-Headers", "Content-Type"));
+        if let Err(e) = request.respond(response) {
+            tracing::warn!("Failed to respond to request: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Read what is requested, or nothing for a preflight request from a
+/// browser, which only needs the headers
+fn parse(request: &mut tiny_http::Request) -> Result<Option<GenerateRequest>, Rejection> {
+    let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+    if path != "/generate" {
+        return Err(Rejection::new(404, "Not found"));
+    }
+
+    let generate = match request.method() {
+        tiny_http::Method::Get => {
+            let GenerateQuery { assets, format } =
+                serde_urlencoded::from_str(query).map_err(Rejection::bad_request)?;
+            GenerateRequest {
+                assets,
+                layout: serde_urlencoded::from_str(query).map_err(Rejection::bad_request)?,
+                format,
+            }
+        }
+        tiny_http::Method::Post => {
+            let mut body = String::new();
+            request
+                .as_reader()
+                .take(MAX_BODY)
+                .read_to_string(&mut body)
+                .map_err(Rejection::bad_request)?;
+            serde_json::from_str(&body).map_err(Rejection::bad_request)?
+        }
+        tiny_http::Method::Options => return Ok(None),
+        _ => return Err(Rejection::new(405, "Method not allowed")),
+    };
+    Ok(Some(generate))
+}
+
+/// Generate the requested sheet, returning its content type and
+/// contents
+fn render(
+    args: &ServeArgs,
+    client: &HomeboxClient,
+    generate: &GenerateRequest,
+    lang: Lang,
+) -> Result<(&'static str, Vec<u8>), Rejection> {
+    let registry = Registry::self_contained();
+    let renderer = registry
+        .get(&generate.format)
+        .ok_or_else(|| Rejection::bad_request(format!("Unknown format: {}", generate.format)))?;
+    let config = generate.layout.build().map_err(Rejection::bad_request)?;
+    let asset_ids = crate::parse_assets(&generate.assets)
+        .map_err(|e| Rejection::bad_request(format!("{e:#}")))?;
+    if asset_ids.len() > args.max_labels {
+        return Err(Rejection::bad_request(format!(
+            "Too many labels! At most {} can be generated at once.",
+            args.max_labels
+        )));
+    }
+
+    let labels = crate::fetch_labels(client, &asset_ids, None)?;
+    let body = renderer
+        .render(&Sheet {
+            config: &config,
+            labels: &labels,
+            descriptions: &crate::describe_assets(&asset_ids, lang),
+            lang,
+        })
+        .context("Failed to render sheet")?;
+    let content_type = match renderer.name() {
+        "html" => "text/html; charset=utf-8",
+        _ => "application/octet-stream",
+    };
+    Ok((content_type, body))
+}
+
+fn header(name: &str, value: &str) -> tiny_http::Header {
+    tiny_http::Header::from_bytes(name, value).expect("header is valid")
+}
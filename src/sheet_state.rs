@@ -0,0 +1,27 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+/// How much of the physical sheet currently in use has been consumed,
+/// persisted between runs
+#[derive(Default, Serialize, Deserialize)]
+pub struct SheetState {
+    /// The number of cells already used on the current sheet
+    pub used_cells: usize,
+}
+
+impl SheetState {
+    /// Load the state, treating a missing file as a fresh sheet
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !fs::exists(path).context("Failed to check if sheet state exists")? {
+            return Ok(Self::default());
+        }
+        toml::from_str(&fs::read_to_string(path).context("Failed to read sheet state")?)
+            .context("Failed to parse sheet state")
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, toml::to_string(self)?).context("Failed to write sheet state")
+    }
+}
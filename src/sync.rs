@@ -0,0 +1,146 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use homebox_label_maker::{
+    api::HomeboxClient,
+    asset_list::AssetId,
+    i18n::Lang,
+    layout::Layout,
+    render::{Registry, Sheet},
+};
+
+use crate::{ConnectionArgs, cache, failure::Failure};
+
+#[derive(clap::Args)]
+pub struct SyncArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// The file path to output the result to
+    #[arg(index = 1)]
+    output_html: PathBuf,
+
+    /// The format to write the output in. Defaults to the format of the
+    /// output file's extension, or else HTML.
+    #[arg(
+        long,
+        value_parser = clap::builder::PossibleValuesParser::new(Registry::self_contained().names()),
+    )]
+    format: Option<String>,
+
+    #[command(flatten)]
+    layout: Layout,
+}
+
+/// When labels were last synced with a server, kept in the user's data
+/// directory
+#[derive(Default, Serialize, Deserialize)]
+pub struct SyncState {
+    last_run: Option<DateTime<Utc>>,
+}
+
+impl SyncState {
+    fn path(base_url: &str) -> anyhow::Result<PathBuf> {
+        Ok(dirs::data_dir()
+            .context("Failed to find data directory")?
+            .join(env!("CARGO_PKG_NAME"))
+            .join("sync")
+            .join(format!("{}.toml", cache::server_key(base_url))))
+    }
+
+    /// Load the state for a server, treating a missing file as never
+    /// having synced
+    fn load(base_url: &str) -> anyhow::Result<Self> {
+        let path = Self::path(base_url)?;
+        if !fs::exists(&path).context("Failed to check if sync state exists")? {
+            return Ok(Self::default());
+        }
+        toml::from_str(&fs::read_to_string(path).context("Failed to read sync state")?)
+            .context("Failed to parse sync state")
+    }
+
+    pub fn save(&self, base_url: &str) -> anyhow::Result<()> {
+        let path = Self::path(base_url)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).context("Failed to create data directory")?;
+        }
+        fs::write(path, toml::to_string(self)?).context("Failed to write sync state")
+    }
+}
+
+/// Print labels for every item created since the last successful sync
+/// with the server, or for every item on the first sync. Items whose
+/// creation date cannot be read are only printed on the first sync.
+pub fn run(args: &SyncArgs, lang: Lang) -> anyhow::Result<()> {
+    let format = crate::single_file_format(args.format.as_deref(), &args.output_html)?;
+    crate::ensure_output_free(&args.output_html)?;
+    let config = args.layout.build().context(Failure::Layout)?;
+
+    let client = crate::authenticate(&args.connection)?;
+    let (asset_ids, state) = pending(&client)?;
+
+    if asset_ids.is_empty() {
+        println!("{}", lang.labels(0));
+    } else {
+        let labels = crate::fetch_labels(&client, &asset_ids, None)?;
+        let sheet = Sheet {
+            config: &config,
+            labels: &labels,
+            descriptions: &crate::describe_assets(&asset_ids, lang),
+            lang,
+        };
+        let bytes_written = crate::render_to_file(
+            &Registry::self_contained(),
+            format,
+            &sheet,
+            &args.output_html,
+        )?;
+
+        crate::print_plan(&config, labels.len(), lang);
+        println!("{}", lang.wrote(bytes_written, &args.output_html));
+    }
+
+    state.save(client.base_url())
+}
+
+/// The assets of every item created since the last sync with the
+/// server, and the state to save once their labels are printed
+pub fn pending(client: &HomeboxClient) -> anyhow::Result<(Vec<AssetId>, SyncState)> {
+    let state = SyncState::load(client.base_url())?;
+    // Items created while this run is in progress are picked up next time
+    let started = Utc::now();
+
+    let mut asset_ids = vec![];
+    for item in client.list_items().context("Failed to list items")? {
+        let created = DateTime::parse_from_rfc3339(&item.created_at);
+        let is_new = match (state.last_run, created) {
+            (None, _) => true,
+            (Some(last_run), Ok(created)) => created > last_run,
+            // Without a date, it cannot be told whether the item is new,
+            // and printing it would print it again on every sync
+            (Some(_), Err(e)) => {
+                tracing::warn!(
+                    "Skipping {}, as when it was created cannot be read ({:?}): {e}",
+                    item.name,
+                    item.created_at
+                );
+                false
+            }
+        };
+        if is_new && item.has_asset_id() {
+            asset_ids.extend(crate::resolve_assets(&item.asset_id)?);
+        }
+    }
+    asset_ids.sort();
+    tracing::info!("{} items are new since the last sync", asset_ids.len());
+
+    Ok((
+        asset_ids,
+        SyncState {
+            last_run: Some(started),
+        },
+    ))
+}
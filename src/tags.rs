@@ -0,0 +1,126 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, anyhow};
+
+use homebox_label_maker::{
+    i18n::Lang,
+    label::{self, LabelOptions},
+    layout::Layout,
+    render::{Registry, Sheet},
+};
+
+use crate::{ConnectionArgs, failure::Failure};
+
+#[derive(clap::Args)]
+pub struct TagsArgs {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// The file path to output the result to
+    #[arg(index = 1)]
+    output_html: PathBuf,
+
+    /// The name of a label (tag) to print. Can be given more than once.
+    /// Every label is printed if none are given.
+    #[arg(long = "tag", value_name = "NAME")]
+    tags: Vec<String>,
+
+    /// Print in large, bold print with a QR code of larger modules, for
+    /// reading at a distance or with poor sight
+    #[arg(long)]
+    large_print: bool,
+
+    /// How much of the QR code can be damaged and still read. Defaults
+    /// to `m`, or `l` for large print.
+    #[arg(long, value_name = "LEVEL")]
+    error_correction: Option<label::ErrorCorrection>,
+
+    /// The width of each module of the QR code in millimeters, rather
+    /// than filling the height of the label, to keep codes on small
+    /// labels scannable
+    #[arg(long, value_name = "MM")]
+    qr_module_mm: Option<f64>,
+
+    /// The width of the blank margin around the QR code, in modules.
+    /// Scanners need at least one, and read best with four.
+    #[arg(long, value_name = "MODULES", default_value_t = 1)]
+    qr_quiet_zone: usize,
+
+    /// The format to write the output in
+    #[arg(
+        long,
+        default_value = "html",
+        value_parser = clap::builder::PossibleValuesParser::new(Registry::self_contained().names()),
+    )]
+    format: String,
+
+    #[command(flatten)]
+    layout: Layout,
+}
+
+/// Print a sticker for each label (tag), with a QR code linking to the
+/// items it is applied to
+pub fn run(args: &TagsArgs, lang: Lang) -> anyhow::Result<()> {
+    crate::ensure_output_free(&args.output_html)?;
+    let config = args.layout.build().context(Failure::Layout)?;
+
+    let client = crate::authenticate(&args.connection)?;
+    let mut tags = client.list_tags().context("Failed to list labels")?;
+    if !args.tags.is_empty() {
+        if let Some(missing) = args
+            .tags
+            .iter()
+            .find(|name| !tags.iter().any(|tag| tag.name.eq_ignore_ascii_case(name)))
+        {
+            Err(anyhow!("No label is named {missing}")).context(Failure::InvalidAssets)?;
+        }
+        tags.retain(|tag| {
+            args.tags
+                .iter()
+                .any(|name| tag.name.eq_ignore_ascii_case(name))
+        });
+    }
+
+    let server = args.connection.server.trim_end_matches('/');
+    let options = LabelOptions {
+        large_print: args.large_print,
+        error_correction: args.error_correction,
+        qr_module_mm: args.qr_module_mm,
+        qr_quiet_zone: args.qr_quiet_zone,
+        ..LabelOptions::for_server(server)
+    };
+    let mut labels = vec![];
+    let mut descriptions = vec![];
+    for tag in &tags {
+        tracing::info!("Rendering label for {}", tag.name);
+        let description: Vec<_> = (!tag.description.is_empty())
+            .then(|| tag.description.clone())
+            .into_iter()
+            .collect();
+        labels.push(label::render_sticker(
+            &format!("{server}/label/{}", tag.id),
+            &tag.name,
+            &description,
+            None,
+            &options,
+            config.cell_size_mm(),
+        )?);
+        descriptions.push(lang.tag_label(&tag.name));
+    }
+
+    let sheet = Sheet {
+        config: &config,
+        labels: &labels,
+        descriptions: &descriptions,
+        lang,
+    };
+    let bytes_written = crate::render_to_file(
+        &Registry::self_contained(),
+        &args.format,
+        &sheet,
+        &args.output_html,
+    )?;
+    crate::print_plan(&config, labels.len(), lang);
+    println!("{}", lang.wrote(bytes_written, &args.output_html));
+    Ok(())
+}
@@ -0,0 +1,298 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+use clap::Subcommand;
+use homebox_label_maker::layout::Layout;
+
+use crate::glabels;
+
+/// The templates for common label sheets, by name, which is the brand
+/// followed by the product code. The first line of each is a comment
+/// describing the sheet. Add a sheet by adding its file to `templates`
+/// and a line here.
+const BUILT_IN: &[(&str, &str)] = &[
+    ("avery-l7160", include_str!("templates/avery-l7160.toml")),
+    ("avery-l7163", include_str!("templates/avery-l7163.toml")),
+    ("avery-l7165", include_str!("templates/avery-l7165.toml")),
+    ("avery-l7651", include_str!("templates/avery-l7651.toml")),
+    ("avery-5160", include_str!("templates/avery-5160.toml")),
+    ("avery-5163", include_str!("templates/avery-5163.toml")),
+    ("avery-5167", include_str!("templates/avery-5167.toml")),
+    (
+        "zweckform-3474",
+        include_str!("templates/zweckform-3474.toml"),
+    ),
+    (
+        "zweckform-3475",
+        include_str!("templates/zweckform-3475.toml"),
+    ),
+    (
+        "zweckform-3651",
+        include_str!("templates/zweckform-3651.toml"),
+    ),
+    (
+        "zweckform-3657",
+        include_str!("templates/zweckform-3657.toml"),
+    ),
+    (
+        "zweckform-3666",
+        include_str!("templates/zweckform-3666.toml"),
+    ),
+    ("herma-4360", include_str!("templates/herma-4360.toml")),
+    ("herma-4474", include_str!("templates/herma-4474.toml")),
+    (
+        "generic-a4-24",
+        include_str!("templates/generic-a4-24.toml"),
+    ),
+    (
+        "generic-a4-65",
+        include_str!("templates/generic-a4-65.toml"),
+    ),
+];
+
+#[derive(clap::Args)]
+pub struct TemplatesArgs {
+    #[command(subcommand)]
+    command: TemplatesCommand,
+}
+
+#[derive(Subcommand)]
+enum TemplatesCommand {
+    /// List the built-in templates and those saved in the configuration
+    /// directory, with the size and number of labels on each sheet
+    List,
+    /// Print the layout a template gives, with every option, as a
+    /// template to start from
+    Show {
+        /// The name of the template, or the path to a template file
+        name: PathBuf,
+    },
+}
+
+/// A template, as given with `--template`
+pub enum Template {
+    /// A template for a common label sheet, with its name
+    BuiltIn(&'static str, &'static str),
+    /// A template file, either given by path or saved in the
+    /// configuration directory
+    File(PathBuf),
+    /// A template from a gLabels catalog, with the part number chosen
+    /// from it if it has several
+    GLabels(PathBuf, Option<String>),
+}
+
+impl Template {
+    /// The directory templates can be saved in to use them by name
+    fn dir() -> anyhow::Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .context("Failed to find configuration directory")?
+            .join(env!("CARGO_PKG_NAME"))
+            .join("templates"))
+    }
+
+    /// Find a template by its path, or by the name of a saved or
+    /// built-in template, or the product code of a built-in template.
+    /// Saved templates take precedence over built-in ones of the same
+    /// name.
+    /// gLabels catalogs are given by path, followed by `#` and the part
+    /// number if they have more than one template.
+    pub fn find(name_or_path: &Path) -> anyhow::Result<Self> {
+        let name = name_or_path.to_string_lossy();
+        if let Some((path, part)) = name.rsplit_once('#')
+            && is_glabels(Path::new(path))
+            && fs::exists(path).context("Failed to check if template exists")?
+        {
+            return Ok(Self::GLabels(path.into(), Some(part.to_string())));
+        }
+        if fs::exists(name_or_path).context("Failed to check if template exists")? {
+            if is_glabels(name_or_path) {
+                return Ok(Self::GLabels(name_or_path.to_path_buf(), None));
+            }
+            return Ok(Self::File(name_or_path.to_path_buf()));
+        }
+        let saved = Self::dir()?.join(format!("{name}.toml"));
+        if fs::exists(&saved).context("Failed to check if template exists")? {
+            return Ok(Self::File(saved));
+        }
+        // Built-in templates can also be found by their product code
+        // alone, e.g. `3474` for `zweckform-3474`
+        BUILT_IN
+            .iter()
+            .find(|(built_in, _)| built_in.eq_ignore_ascii_case(&name))
+            .or_else(|| {
+                BUILT_IN.iter().find(|(built_in, _)| {
+                    built_in
+                        .split_once('-')
+                        .is_some_and(|(_, code)| code.eq_ignore_ascii_case(&name))
+                })
+            })
+            .map(|&(name, source)| Self::BuiltIn(name, source))
+            .ok_or_else(|| anyhow!("No template is named {name}, and there is no such file"))
+    }
+
+    /// Every built-in and saved template, with the saved templates
+    /// after the built-in ones
+    fn all() -> anyhow::Result<Vec<Self>> {
+        let mut templates: Vec<_> = BUILT_IN
+            .iter()
+            .map(|&(name, source)| Self::BuiltIn(name, source))
+            .collect();
+        let dir = Self::dir()?;
+        if fs::exists(&dir).context("Failed to check if templates exist")? {
+            let mut saved = vec![];
+            for entry in fs::read_dir(dir).context("Failed to read templates")? {
+                let path = entry.context("Failed to read templates")?.path();
+                if path
+                    .extension()
+                    .is_some_and(|extension| extension == "toml")
+                {
+                    saved.push(path);
+                }
+            }
+            saved.sort();
+            templates.extend(saved.into_iter().map(Self::File));
+        }
+        Ok(templates)
+    }
+
+    /// The file the template is read from, if it is not built in
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::BuiltIn(..) => None,
+            Self::File(path) | Self::GLabels(path, _) => Some(path),
+        }
+    }
+
+    /// The name to use the template by
+    fn name(&self) -> String {
+        match self {
+            Self::BuiltIn(name, _) => (*name).to_string(),
+            Self::GLabels(_, Some(part)) => part.clone(),
+            Self::File(path) | Self::GLabels(path, None) => path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        }
+    }
+
+    fn source(&self) -> anyhow::Result<String> {
+        match self {
+            Self::BuiltIn(_, source) => Ok((*source).to_string()),
+            Self::File(path) | Self::GLabels(path, _) => {
+                fs::read_to_string(path).context("Failed to read template")
+            }
+        }
+    }
+
+    /// The layout fields the template sets
+    fn table(&self) -> anyhow::Result<toml::Table> {
+        match self {
+            Self::GLabels(path, part) => glabels::read(path, part.as_deref()),
+            _ => self.source()?.parse().context("Failed to parse template"),
+        }
+    }
+
+    /// Produce a copy of the layout with any fields set in the template
+    /// replaced
+    pub fn apply(&self, layout: &Layout) -> anyhow::Result<Layout> {
+        let template = self.table()?;
+
+        let mut fields = toml::Table::try_from(layout)?;
+        // Optional fields are left out when not set, so check the keys
+        // against a layout with every field set
+        let known = toml::Table::try_from(Layout {
+            label_width_mm: Some(0.0),
+            label_height_mm: Some(0.0),
+            auto_grid: true,
+            skip_cells: Some(String::new()),
+            roll_pitch_mm: Some(0.0),
+            ..Layout::default()
+        })?;
+        for (key, value) in template {
+            if !known.contains_key(&key) {
+                Err(anyhow!("Unknown template option: {key}"))?;
+            }
+            fields.insert(key, value);
+        }
+        fields
+            .try_into()
+            .context("Template contains an invalid value")
+    }
+}
+
+/// Whether a template file is a gLabels catalog, by its extension
+fn is_glabels(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            glabels::EXTENSIONS
+                .iter()
+                .any(|glabels| glabels.eq_ignore_ascii_case(extension))
+        })
+}
+
+/// List the templates or show one
+pub fn run(args: &TemplatesArgs) -> anyhow::Result<()> {
+    match &args.command {
+        TemplatesCommand::List => {
+            for template in Template::all()? {
+                let geometry = match geometry(&template) {
+                    Ok(geometry) => geometry,
+                    Err(e) => {
+                        tracing::warn!("Ignoring invalid template {}: {e:#}", template.name());
+                        continue;
+                    }
+                };
+                let description = template
+                    .source()?
+                    .lines()
+                    .next()
+                    .and_then(|line| line.strip_prefix('#'))
+                    .map(str::trim)
+                    .unwrap_or_default()
+                    .to_string();
+                println!("{}\t{geometry}\t{description}", template.name());
+            }
+        }
+        TemplatesCommand::Show { name } => {
+            let template = Template::find(name)?;
+            let layout = template.apply(&Layout::default())?;
+            println!("# {}", geometry(&template)?);
+            print!("{}", toml::to_string(&layout)?);
+        }
+    }
+    Ok(())
+}
+
+/// A summary of the sheet a template is for, e.g.
+/// `13x5 labels of 38.1x21.2mm on 210x297mm`
+fn geometry(template: &Template) -> anyhow::Result<String> {
+    let config = template.apply(&Layout::default())?.build()?;
+    let (width, height) = config.cell_size_mm();
+    if config.is_roll() {
+        return Ok(format!(
+            "roll of {}x{}mm labels",
+            round(width),
+            round(height)
+        ));
+    }
+    let last = config.cell(config.num_per_page() - 1);
+    let (page_width, page_height) = config.page_size_mm();
+    Ok(format!(
+        "{}x{} labels of {}x{}mm on {page_width}x{page_height}mm",
+        last.row,
+        last.column,
+        round(width),
+        round(height),
+    ))
+}
+
+/// Round a size to a hundredth of a millimeter, so it prints as it was
+/// given rather than with rounding errors
+fn round(mm: f64) -> f64 {
+    (mm * 100.0).round() / 100.0
+}
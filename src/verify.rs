@@ -0,0 +1,72 @@
+//! Reading back the QR codes on labels from the server, to catch mixed
+//! up or corrupted labels before they are printed.
+
+use crate::{error::VerifyError, image::ImageType, optimize};
+
+/// Read the text of the QR code on a PNG label. Labels in other formats,
+/// such as SVG, cannot be read, so give `None`.
+///
+/// # Errors
+///
+/// Fails if the label is a PNG that cannot be decoded, or that has no
+/// QR code which can be read.
+pub fn read_qr(label: &[u8]) -> Result<Option<String>, VerifyError> {
+    if ImageType::detect(label) != Some(ImageType::Png) {
+        return Ok(None);
+    }
+    let image = optimize::decode(label).map_err(VerifyError::Image)?;
+
+    let width = image.width as usize;
+    let mut prepared =
+        rqrr::PreparedImage::prepare_from_greyscale(width, image.height as usize, |x, y| {
+            // Transparent pixels show the white page behind them
+            let [r, g, b, a] = image.pixels[y * width + x].map(u32::from);
+            let luma = (r * 299 + g * 587 + b * 114) / 1000;
+            u8::try_from((luma * a + 255 * (255 - a)) / 255).unwrap_or(u8::MAX)
+        });
+    prepared
+        .detect_grids()
+        .into_iter()
+        .find_map(|grid| grid.decode().ok())
+        .map(|(_, text)| Some(text))
+        .ok_or(VerifyError::NoCode)
+}
+
+#[cfg(all(test, feature = "local"))]
+mod tests {
+    use super::*;
+
+    /// A greyscale PNG of a QR code encoding the text, with each module
+    /// four pixels wide and a four module quiet zone
+    fn qr_png(text: &str) -> Vec<u8> {
+        let qr = qrcode::QrCode::new(text).unwrap();
+        let modules = qr.width();
+        let colors = qr.to_colors();
+        let size = (modules + 8) * 4;
+        let mut pixels = vec![u8::MAX; size * size];
+        for (idx, color) in colors.iter().enumerate() {
+            if *color == qrcode::Color::Dark {
+                let (x, y) = (idx % modules + 4, idx / modules + 4);
+                for row in y * 4..y * 4 + 4 {
+                    pixels[row * size + x * 4..row * size + x * 4 + 4].fill(0);
+                }
+            }
+        }
+
+        let mut png = vec![];
+        let size = u32::try_from(size).unwrap();
+        let mut encoder = png::Encoder::new(&mut png, size, size);
+        encoder.set_color(png::ColorType::Grayscale);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&pixels).unwrap();
+        writer.finish().unwrap();
+        png
+    }
+
+    #[test]
+    fn qr_codes_are_read() {
+        let text = "https://homebox.example.com/a/000-042";
+        assert_eq!(read_qr(&qr_png(text)).unwrap().as_deref(), Some(text));
+        assert!(read_qr(b"<svg/>").unwrap().is_none());
+    }
+}
@@ -0,0 +1,61 @@
+//! Bindings for generating label sheets from JavaScript.
+//!
+//! The page fetches the label images from Homebox itself, so everything
+//! else happens in the browser. See `web/index.html` for an example.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    asset_list::{self, Validate},
+    error::describe,
+    i18n::Lang,
+    layout::Layout,
+    render::{HtmlRenderer, Renderer, Sheet},
+};
+
+/// Parse and validate an asset list, returning each asset ID in it
+///
+/// # Errors
+///
+/// Fails if the list is invalid.
+#[wasm_bindgen(js_name = parseAssets)]
+pub fn parse_assets(assets: &str) -> Result<Vec<String>, JsError> {
+    let list = asset_list::parse(assets).map_err(|e| JsError::new(&describe(&e)))?;
+    list.validate().map_err(|e| JsError::new(&describe(&e)))?;
+    Ok(list
+        .into_iter()
+        .flatten()
+        .map(|id| id.to_string())
+        .collect())
+}
+
+/// Generate the HTML for a label sheet. The layout is a JSON object with
+/// the same field names as the command line flags, any of which may be
+/// omitted, and the labels are the image data for each label in order.
+///
+/// # Errors
+///
+/// Fails if the layout is invalid.
+#[wasm_bindgen(js_name = generateSheet)]
+pub fn generate_sheet(
+    layout: &str,
+    labels: Vec<js_sys::Uint8Array>,
+    lang: &str,
+) -> Result<String, JsError> {
+    let layout: Layout = serde_json::from_str(layout)?;
+    let config = layout.build().map_err(|e| JsError::new(&describe(&e)))?;
+    let labels: Vec<bytes::Bytes> = labels
+        .into_iter()
+        .map(|label| label.to_vec().into())
+        .collect();
+
+    let html = HtmlRenderer::default()
+        .render(&Sheet {
+            config: &config,
+            labels: &labels,
+            descriptions: &[],
+            lang: Lang::from_locale(lang).unwrap_or_default(),
+        })
+        .map_err(|e| JsError::new(&describe(&e)))?;
+    Ok(String::from_utf8(html)?)
+}
@@ -0,0 +1,52 @@
+use std::{fs, path::PathBuf, thread, time::Duration, time::SystemTime};
+
+/// How often to check watched files for modifications
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The input files that a sheet is generated from
+pub struct Watched {
+    template: Option<PathBuf>,
+    assets: Option<PathBuf>,
+}
+
+/// Which of the watched files changed
+pub struct Change {
+    pub assets: bool,
+}
+
+impl Watched {
+    /// Watch the template, if any, and the assets list, if it was given
+    /// as a file
+    pub fn new(template: Option<&std::path::Path>, assets: &str) -> anyhow::Result<Self> {
+        let watched = Self {
+            template: template.map(ToOwned::to_owned),
+            assets: assets.strip_prefix('@').map(PathBuf::from),
+        };
+        if watched.template.is_none() && watched.assets.is_none() {
+            Err(anyhow::anyhow!(
+                "Nothing to watch! Provide a template or an assets file."
+            ))?;
+        }
+        Ok(watched)
+    }
+
+    /// Block until one of the watched files is modified
+    pub fn wait_for_change(&self) -> anyhow::Result<Change> {
+        let template = modified(self.template.as_ref());
+        let assets = modified(self.assets.as_ref());
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let assets_changed = modified(self.assets.as_ref()) != assets;
+            if assets_changed || modified(self.template.as_ref()) != template {
+                tracing::info!("Change detected, regenerating...");
+                return Ok(Change {
+                    assets: assets_changed,
+                });
+            }
+        }
+    }
+}
+
+fn modified(path: Option<&PathBuf>) -> Option<SystemTime> {
+    fs::metadata(path?).and_then(|m| m.modified()).ok()
+}